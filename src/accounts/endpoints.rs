@@ -1,9 +1,13 @@
 use log::{error, warn};
-use rocket::{delete, get, http::Status, post, put, routes, serde::json::Json, State};
-use crate::{accounts::schema::AccountDto, projects::schema::{Project, ProjectDto}, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::Session}, users::{roles::is_admin, schema::User}};
+use bson::oid::ObjectId;
+use rocket::{data::{Data, ToByteUnit}, delete, get, http::Status, post, put, routes, serde::json::Json, State};
+use crate::{accounts::schema::AccountDto, projects::{archive::{get_project_archive_user_ids, read_project_archive, remap_project_archive}, endpoints::insert_project_archive, schema::{Project, ProjectDto, ProjectImportResult}, service::check_project_flaky_policy}, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::Session}, testchecks::schema::Testcheck, testconfigurations::schema::Testconfiguration, testenvironments::schema::Testenvironment, testlists::schema::Testlist, testplans::schema::Testplan, testreports::schema::Testreport, testresults::schema::Testresult, testrevisions::schema::Testrevision, testsections::schema::Testsection, users::{roles::is_admin, schema::User}};
 
 use super::schema::{Account, AccountsList};
 
+// Largest project archive accepted by the import, in MiB
+const MAX_PROJECT_ARCHIVE_SIZE: u64 = 256;
+
 #[get("/?<page>&<per_page>&<sort_by>&<sort_dir>")]
 pub async fn get_accounts(jwt: Result<JWT, JsonError>, page: usize, per_page: usize, sort_by: &str, sort_dir: &str, sessions_repos: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, account_repo: &State<MongoRepo<Account>>) -> Result<Json<AccountsList>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repos, users_repo, jwt).await?;
@@ -175,6 +179,9 @@ pub async fn get_account_projects(jwt: Result<JWT, JsonError>, account_id: &str,
 #[post("/<account_id>/projects", format = "json", data = "<project>")]
 pub async fn create_account_project(account_id: &str, project: Json<ProjectDto>, project_repo: &State<MongoRepo<Project>>) -> Result<Json<Project>, JsonError> {
   let data = project.into_inner();
+  if let Some(flaky) = &data.flaky {
+    check_project_flaky_policy(flaky).map_err(JsonError::BadRequest)?;
+  }
   match project_repo.create_project(account_id, data).await {
     Ok(inserted) => {
       let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
@@ -200,7 +207,67 @@ pub async fn create_account_project(account_id: &str, project: Json<ProjectDto>,
 }
 
 
+// Creates a project of the account from an archive exported by any instance
+#[post("/<account_id>/projects/import", data = "<archive>")]
+pub async fn import_account_project(jwt: Result<JWT, JsonError>, account_id: &str, archive: Data<'_>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, accounts_repo: &State<MongoRepo<Account>>, project_repo: &State<MongoRepo<Project>>, testlist_repo: &State<MongoRepo<Testlist>>, testsection_repo: &State<MongoRepo<Testsection>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testplan_repo: &State<MongoRepo<Testplan>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<ProjectImportResult>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let is_in_accounts = jwts.user.accounts.as_ref().is_some_and(|accounts| accounts.iter().any(|a| a.account_id.to_hex() == account_id));
+  if !is_in_accounts && !is_admin(&jwts.user) {
+    return Err(JsonError::Forbidden(
+      "You are not allowed to import projects in this account".to_string(),
+    ));
+  }
+
+  let account = match accounts_repo.get_account_by_id(account_id).await {
+    Ok(Some(account)) => account,
+    Ok(None) => return Err(JsonError::NotFound("Account not found".to_string())),
+    Err(e) => {
+      error!("Error getting account: {}", e);
+      return Err(JsonError::Internal("Error getting account".to_string()));
+    },
+  };
+
+  let archive = match archive.open(MAX_PROJECT_ARCHIVE_SIZE.mebibytes()).into_bytes().await {
+    Ok(archive) if archive.is_complete() => archive.into_inner(),
+    Ok(_) => return Err(JsonError::BadRequest(format!("Project archive is larger than {} MiB", MAX_PROJECT_ARCHIVE_SIZE))),
+    Err(e) => {
+      warn!("Error reading project archive: {}", e);
+      return Err(JsonError::BadRequest("Error reading project archive".to_string()));
+    },
+  };
+  // Checked entirely before anything is written
+  let archive = read_project_archive(&archive).map_err(JsonError::BadRequest)?;
+
+  let user_ids = get_project_archive_user_ids(&archive);
+  let members: Vec<ObjectId> = match users_repo.get_users_by_ids(&user_ids).await {
+    Ok(users) => users.iter()
+      .filter(|user| user.accounts.as_ref().is_some_and(|accounts| accounts.iter().any(|a| a.account_id == account.id)))
+      .map(|user| user.id)
+      .collect(),
+    Err(e) => {
+      error!("Error getting users: {}", e);
+      return Err(JsonError::Internal("Error getting users".to_string()));
+    },
+  };
+  let archive = remap_project_archive(archive, account.id, &members);
+  insert_project_archive(&archive, project_repo, testlist_repo, testsection_repo, testcheck_repo, testrevision_repo, testenvironment_repo, testconfiguration_repo, testplan_repo, testreport_repo, testresult_repo).await?;
+
+  Ok(Json(ProjectImportResult {
+    testlists: archive.testlists.len(),
+    testsections: archive.testsections.len(),
+    testchecks: archive.testchecks.len(),
+    testrevisions: archive.testrevisions.len(),
+    testenvironments: archive.testenvironments.len(),
+    testconfigurations: archive.testconfigurations.len(),
+    testplans: archive.testplans.len(),
+    testreports: archive.testreports.len(),
+    testresults: archive.testresults.len(),
+    attachments: archive.count_attachments(),
+    project: archive.project,
+  }))
+}
+
 
 pub fn get_accounts_routes() -> Vec<rocket::Route> {
-  routes![get_accounts, get_account, create_account, update_account, delete_account, get_account_projects, create_account_project]
+  routes![get_accounts, get_account, create_account, update_account, delete_account, get_account_projects, create_account_project, import_account_project]
 }
@@ -0,0 +1,131 @@
+use bson::{oid::ObjectId, DateTime};
+use log::error;
+use rocket::{get, routes, serde::json::Json, State};
+use crate::{projects::{endpoints::allowed_for_project, schema::Project}, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::Session}, testreports::schema::Testreport, testresults::schema::Testresult, users::schema::User};
+
+use super::schema::{AnalyticsFilter, AnalyticsInterval, AutomationCoverage, ExecutorThroughput, FixTime, PassRateTrend, ProjectAnalytics, TagFailures};
+
+#[get("/<project_id>/analytics?<from>&<to>&<testlist_id>&<interval>")]
+async fn get_project_analytics(jwt: Result<JWT, JsonError>, project_id: &str, from: Option<&str>, to: Option<&str>, testlist_id: Option<&str>, interval: Option<&str>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<ProjectAnalytics>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+  let filter = get_analytics_filter(testreport_repo, project_id, from, to, testlist_id, interval).await?;
+
+  Ok(Json(ProjectAnalytics {
+    trend: analytics_result(testresult_repo.get_pass_rate_trend(&filter).await)?,
+    tags: analytics_result(testresult_repo.get_failures_by_tag(&filter).await)?,
+    fix_time: analytics_result(testresult_repo.get_fix_time(&filter).await)?,
+    automation: analytics_result(testresult_repo.get_automation_coverage(&filter).await)?,
+    throughput: analytics_result(testresult_repo.get_executor_throughput(&filter).await)?,
+  }))
+}
+
+#[get("/<project_id>/analytics/trend?<from>&<to>&<testlist_id>&<interval>")]
+async fn get_project_pass_rate_trend(jwt: Result<JWT, JsonError>, project_id: &str, from: Option<&str>, to: Option<&str>, testlist_id: Option<&str>, interval: Option<&str>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Vec<PassRateTrend>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+  let filter = get_analytics_filter(testreport_repo, project_id, from, to, testlist_id, interval).await?;
+
+  analytics_result(testresult_repo.get_pass_rate_trend(&filter).await).map(Json)
+}
+
+#[get("/<project_id>/analytics/tags?<from>&<to>&<testlist_id>")]
+async fn get_project_failures_by_tag(jwt: Result<JWT, JsonError>, project_id: &str, from: Option<&str>, to: Option<&str>, testlist_id: Option<&str>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Vec<TagFailures>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+  let filter = get_analytics_filter(testreport_repo, project_id, from, to, testlist_id, None).await?;
+
+  analytics_result(testresult_repo.get_failures_by_tag(&filter).await).map(Json)
+}
+
+#[get("/<project_id>/analytics/fix-time?<from>&<to>&<testlist_id>")]
+async fn get_project_fix_time(jwt: Result<JWT, JsonError>, project_id: &str, from: Option<&str>, to: Option<&str>, testlist_id: Option<&str>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<FixTime>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+  let filter = get_analytics_filter(testreport_repo, project_id, from, to, testlist_id, None).await?;
+
+  analytics_result(testresult_repo.get_fix_time(&filter).await).map(Json)
+}
+
+#[get("/<project_id>/analytics/automation?<from>&<to>&<testlist_id>")]
+async fn get_project_automation_coverage(jwt: Result<JWT, JsonError>, project_id: &str, from: Option<&str>, to: Option<&str>, testlist_id: Option<&str>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<AutomationCoverage>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+  let filter = get_analytics_filter(testreport_repo, project_id, from, to, testlist_id, None).await?;
+
+  analytics_result(testresult_repo.get_automation_coverage(&filter).await).map(Json)
+}
+
+#[get("/<project_id>/analytics/throughput?<from>&<to>&<testlist_id>&<interval>")]
+async fn get_project_executor_throughput(jwt: Result<JWT, JsonError>, project_id: &str, from: Option<&str>, to: Option<&str>, testlist_id: Option<&str>, interval: Option<&str>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Vec<ExecutorThroughput>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+  let filter = get_analytics_filter(testreport_repo, project_id, from, to, testlist_id, interval).await?;
+
+  analytics_result(testresult_repo.get_executor_throughput(&filter).await).map(Json)
+}
+
+// Mounted under the projects path
+pub fn get_analytics_routes() -> Vec<rocket::Route> {
+  routes![get_project_analytics, get_project_pass_rate_trend, get_project_failures_by_tag, get_project_fix_time, get_project_automation_coverage, get_project_executor_throughput]
+}
+
+async fn get_analytics_filter(testreport_repo: &State<MongoRepo<Testreport>>, project_id: &str, from: Option<&str>, to: Option<&str>, testlist_id: Option<&str>, interval: Option<&str>) -> Result<AnalyticsFilter, JsonError> {
+  let testlist_id = match testlist_id {
+    Some(testlist_id) => match ObjectId::parse_str(testlist_id) {
+      Ok(oid) => Some(oid),
+      Err(_) => return Err(JsonError::BadRequest(format!("Invalid testlist id: {}", testlist_id))),
+    },
+    None => None,
+  };
+  let interval = match interval {
+    Some(interval) => match AnalyticsInterval::parse(interval) {
+      Some(interval) => interval,
+      None => return Err(JsonError::BadRequest("Interval must be day, week or month".to_string())),
+    },
+    None => AnalyticsInterval::Day,
+  };
+
+  let testreport_ids = match testreport_repo.get_project_testreports(project_id).await {
+    Ok(testreports) => testreports.iter()
+      .filter(|testreport| testlist_id.is_none_or(|testlist_id| testreport.testlist_id == testlist_id))
+      .map(|testreport| testreport.id)
+      .collect(),
+    Err(e) => {
+      error!("Error getting testreports: {}", e);
+      return Err(JsonError::Internal("Error getting testreports".to_string()));
+    },
+  };
+
+  Ok(AnalyticsFilter {
+    testreport_ids,
+    from: parse_analytics_date(from, false)?,
+    to: parse_analytics_date(to, true)?,
+    interval,
+  })
+}
+
+// Accepts RFC 3339 dates or plain days, a plain `to` day is included entirely
+fn parse_analytics_date(date: Option<&str>, end_of_day: bool) -> Result<Option<DateTime>, JsonError> {
+  let date = match date {
+    Some(date) => date,
+    None => return Ok(None),
+  };
+  if let Ok(date) = chrono::DateTime::parse_from_rfc3339(date) {
+    return Ok(Some(DateTime::from_chrono(date.with_timezone(&chrono::Utc))));
+  }
+  match chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+    Ok(day) => {
+      let time = if end_of_day { day.and_hms_milli_opt(23, 59, 59, 999) } else { day.and_hms_opt(0, 0, 0) };
+      Ok(time.map(|time| DateTime::from_chrono(time.and_utc())))
+    },
+    Err(_) => Err(JsonError::BadRequest(format!("Invalid date: {}", date))),
+  }
+}
+
+fn analytics_result<T>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> Result<T, JsonError> {
+  result.map_err(|e| {
+    error!("Error computing analytics: {}", e);
+    JsonError::Internal("Error computing analytics".to_string())
+  })
+}
@@ -0,0 +1,99 @@
+use bson::DateTime;
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::service::db::serialize_object_id;
+
+// Analytics cover the executed results of the selected reports, within the execution date range
+#[derive(Debug, Clone)]
+pub struct AnalyticsFilter {
+  pub testreport_ids: Vec<ObjectId>,
+  pub from: Option<DateTime>,
+  pub to: Option<DateTime>,
+  pub interval: AnalyticsInterval,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsInterval {
+  Day,
+  Week,
+  Month,
+}
+
+impl AnalyticsInterval {
+  pub fn parse(interval: &str) -> Option<AnalyticsInterval> {
+    match interval {
+      "day" => Some(AnalyticsInterval::Day),
+      "week" => Some(AnalyticsInterval::Week),
+      "month" => Some(AnalyticsInterval::Month),
+      _ => None,
+    }
+  }
+
+  // $dateToString format of the period
+  pub fn date_format(&self) -> &'static str {
+    match self {
+      AnalyticsInterval::Day => "%Y-%m-%d",
+      AnalyticsInterval::Week => "%G-W%V",
+      AnalyticsInterval::Month => "%Y-%m",
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PassRateTrend {
+  #[serde(rename = "_id")]
+  pub period: String,
+  pub executed: u32,
+  pub passed: u32,
+  pub blocked: u32,
+  #[serde(default)]
+  pub failed: u32,
+  #[serde(default)]
+  pub pass_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TagFailures {
+  #[serde(rename = "_id")]
+  pub tag: String,
+  pub executed: u32,
+  pub failed: u32,
+  #[serde(default)]
+  pub failure_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FixTime {
+  // Failures followed by a pass of the same check
+  pub fixes: u32,
+  pub mean_hours: f64,
+  // Checks whose latest execution is still failing
+  pub open_failures: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AutomationCoverage {
+  pub total: u32,
+  pub automated: u32,
+  #[serde(default)]
+  pub coverage: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecutorThroughput {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub user_id: ObjectId,
+  pub period: String,
+  pub executed: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectAnalytics {
+  pub trend: Vec<PassRateTrend>,
+  pub tags: Vec<TagFailures>,
+  pub fix_time: FixTime,
+  pub automation: AutomationCoverage,
+  pub throughput: Vec<ExecutorThroughput>,
+}
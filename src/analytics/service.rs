@@ -0,0 +1,154 @@
+use std::error::Error;
+
+use bson::DateTime;
+use mongodb::bson::{self, doc, Document};
+use serde::de::DeserializeOwned;
+use crate::{service::db::MongoRepo, testresults::schema::Testresult};
+use super::schema::{AnalyticsFilter, AutomationCoverage, ExecutorThroughput, FixTime, PassRateTrend, TagFailures};
+use rocket::futures::TryStreamExt;
+
+impl MongoRepo<Testresult> {
+  pub async fn get_pass_rate_trend(&self, filter: &AnalyticsFilter) -> Result<Vec<PassRateTrend>, Box<dyn Error + Send + Sync>> {
+    let pipeline = vec![
+      match_executed(filter),
+      doc! { "$group": {
+        "_id": { "$dateToString": { "format": filter.interval.date_format(), "date": "$updated_at" } },
+        "executed": { "$sum": 1 },
+        "passed": { "$sum": { "$cond": [{ "$and": ["$pass", { "$ne": ["$blocked", true] }] }, 1, 0] } },
+        "blocked": { "$sum": { "$cond": [{ "$eq": ["$blocked", true] }, 1, 0] } }
+      } },
+      doc! { "$sort": { "_id": 1 } },
+    ];
+    let mut trend: Vec<PassRateTrend> = self.aggregate_into(pipeline).await?;
+    for period in trend.iter_mut() {
+      period.failed = period.executed - period.passed - period.blocked;
+      period.pass_rate = percentage(period.passed, period.executed);
+    }
+    Ok(trend)
+  }
+
+  pub async fn get_failures_by_tag(&self, filter: &AnalyticsFilter) -> Result<Vec<TagFailures>, Box<dyn Error + Send + Sync>> {
+    let pipeline = vec![
+      match_executed(filter),
+      doc! { "$unwind": "$tags" },
+      doc! { "$group": {
+        "_id": "$tags",
+        "executed": { "$sum": 1 },
+        // Blocked results could not be executed, they did not fail
+        "failed": { "$sum": { "$cond": [{ "$or": ["$pass", { "$eq": ["$blocked", true] }] }, 0, 1] } }
+      } },
+      doc! { "$sort": { "failed": -1, "_id": 1 } },
+    ];
+    let mut tags: Vec<TagFailures> = self.aggregate_into(pipeline).await?;
+    for tag in tags.iter_mut() {
+      tag.failure_rate = percentage(tag.failed, tag.executed);
+    }
+    Ok(tags)
+  }
+
+  // Time from the first failure of a check to its next pass, blocked runs are neither
+  pub async fn get_fix_time(&self, filter: &AnalyticsFilter) -> Result<FixTime, Box<dyn Error + Send + Sync>> {
+    let pipeline = vec![
+      match_executed(filter),
+      doc! { "$match": { "blocked": { "$ne": true } } },
+      doc! { "$sort": { "updated_at": 1 } },
+      doc! { "$group": {
+        "_id": "$testcheck_id",
+        "runs": { "$push": { "pass": "$pass", "updated_at": "$updated_at" } }
+      } },
+    ];
+    let testchecks: Vec<Document> = self.aggregate_into(pipeline).await?;
+
+    let mut fixes: Vec<i64> = vec![];
+    let mut open_failures = 0;
+    for testcheck in testchecks {
+      let mut failing_since: Option<DateTime> = None;
+      for run in testcheck.get_array("runs")?.iter().filter_map(|run| run.as_document()) {
+        let pass = run.get_bool("pass")?;
+        let updated_at = *run.get_datetime("updated_at")?;
+        match (pass, failing_since) {
+          (false, None) => failing_since = Some(updated_at),
+          (true, Some(since)) => {
+            fixes.push(updated_at.timestamp_millis() - since.timestamp_millis());
+            failing_since = None;
+          },
+          _ => (),
+        }
+      }
+      if failing_since.is_some() {
+        open_failures += 1;
+      }
+    }
+
+    let mean_hours = if fixes.is_empty() { 0.0 } else { fixes.iter().sum::<i64>() as f64 / fixes.len() as f64 / 3_600_000.0 };
+    Ok(FixTime {
+      fixes: fixes.len() as u32,
+      mean_hours,
+      open_failures,
+    })
+  }
+
+  pub async fn get_automation_coverage(&self, filter: &AnalyticsFilter) -> Result<AutomationCoverage, Box<dyn Error + Send + Sync>> {
+    let pipeline = vec![
+      match_executed(filter),
+      doc! { "$group": {
+        "_id": null,
+        "total": { "$sum": 1 },
+        "automated": { "$sum": { "$cond": ["$automated", 1, 0] } }
+      } },
+    ];
+    let coverage: Vec<AutomationCoverage> = self.aggregate_into(pipeline).await?;
+    let mut coverage = coverage.into_iter().next().unwrap_or(AutomationCoverage { total: 0, automated: 0, coverage: 0.0 });
+    coverage.coverage = percentage(coverage.automated, coverage.total);
+    Ok(coverage)
+  }
+
+  pub async fn get_executor_throughput(&self, filter: &AnalyticsFilter) -> Result<Vec<ExecutorThroughput>, Box<dyn Error + Send + Sync>> {
+    let pipeline = vec![
+      match_executed(filter),
+      doc! { "$unwind": "$executors" },
+      doc! { "$group": {
+        "_id": {
+          "user_id": "$executors.user_id",
+          "period": { "$dateToString": { "format": filter.interval.date_format(), "date": "$updated_at" } }
+        },
+        "executed": { "$sum": 1 }
+      } },
+      doc! { "$project": { "_id": 0, "user_id": "$_id.user_id", "period": "$_id.period", "executed": 1 } },
+      doc! { "$sort": { "period": 1, "executed": -1 } },
+    ];
+    self.aggregate_into(pipeline).await
+  }
+
+  async fn aggregate_into<R: DeserializeOwned>(&self, pipeline: Vec<Document>) -> Result<Vec<R>, Box<dyn Error + Send + Sync>> {
+    let cursor = self.col.aggregate(pipeline, None).await?;
+    let documents: Vec<Document> = cursor.try_collect().await?;
+    let mut results = vec![];
+    for document in documents {
+      results.push(bson::from_document(document)?);
+    }
+    Ok(results)
+  }
+}
+
+fn match_executed(filter: &AnalyticsFilter) -> Document {
+  let mut criteria = doc! {
+    "testreport_id": { "$in": &filter.testreport_ids },
+    "updated": true
+  };
+  let mut updated_at = Document::new();
+  if let Some(from) = filter.from {
+    updated_at.insert("$gte", from);
+  }
+  if let Some(to) = filter.to {
+    updated_at.insert("$lte", to);
+  }
+  if !updated_at.is_empty() {
+    criteria.insert("updated_at", updated_at);
+  }
+  doc! { "$match": criteria }
+}
+
+fn percentage(part: u32, total: u32) -> f64 {
+  if total > 0 { part as f64 * 100.0 / total as f64 } else { 0.0 }
+}
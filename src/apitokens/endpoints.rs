@@ -0,0 +1,60 @@
+use log::{error, warn};
+use rocket::{delete, routes, serde::json::Json, State};
+use crate::{service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, users::{roles::is_admin, schema::User}};
+
+use super::schema::{Apitoken, ApitokenRes};
+
+#[delete("/<id>")]
+async fn delete_apitoken(jwt: Result<JWT, JsonError>, id: &str, apitoken_repo: &State<MongoRepo<Apitoken>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<ApitokenRes>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let apitoken = allowed_for_apitoken(jwts, apitoken_repo, id).await?;
+
+  match apitoken_repo.delete_apitoken(id.to_string()).await {
+    Ok(deleted) => {
+      if deleted.deleted_count == 0 {
+        warn!("Apitoken not found: {}", id);
+        return Err(JsonError::NotFound("Apitoken not found".to_string()));
+      }
+      Ok(Json(apitoken.into()))
+    },
+    Err(e) => {
+      error!("Error deleting apitoken: {}", e);
+      Err(JsonError::Internal("Error deleting apitoken".to_string()))
+    },
+  }
+}
+
+pub fn get_apitokens_routes() -> Vec<rocket::Route> {
+  routes![delete_apitoken]
+}
+
+pub async fn get_apitoken_by_id(apitoken_repo: &State<MongoRepo<Apitoken>>, id: &str) -> Result<Apitoken, JsonError> {
+  match apitoken_repo.get_apitoken_by_id(id).await {
+    Ok(apitoken) => match apitoken {
+      Some(apitoken) => Ok(apitoken),
+      None => {
+        warn!("Apitoken not found: {}", id);
+        Err(JsonError::NotFound("Apitoken not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting apitoken: {}", e);
+      Err(JsonError::Internal("Error getting apitoken".to_string()))
+    },
+  }
+}
+
+async fn allowed_for_apitoken(jwts: JWTSessionAndUser, apitoken_repo: &State<MongoRepo<Apitoken>>, apitoken_id: &str) -> Result<Apitoken, JsonError>  {
+  if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
+    return Err(JsonError::Forbidden(
+      "You are not allowed to retrieve this apitoken".to_string(),
+    ));
+  }
+  let apitoken = get_apitoken_by_id(apitoken_repo, apitoken_id).await?;
+  if let Some(accounts) = &jwts.user.accounts {
+    if !accounts.iter().any(|account| account.account_id == apitoken.account_id) && !is_admin(&jwts.user) {
+      return Err(JsonError::Forbidden("You are not allowed to retrieve this apitoken".to_string()));
+    }
+  }
+  Ok(apitoken)
+}
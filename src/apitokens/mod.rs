@@ -0,0 +1,4 @@
+pub mod endpoints;
+pub mod schema;
+pub mod service;
+pub mod token;
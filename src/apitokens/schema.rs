@@ -0,0 +1,68 @@
+use bson::DateTime;
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::service::db::{serialize_datetime, serialize_object_id, serialize_option_datetime};
+
+// Project scoped token for CI, only the SHA-256 of the token is stored
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Apitoken {
+  #[serde(rename = "_id", serialize_with = "serialize_object_id")]
+  pub id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub account_id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub project_id: ObjectId,
+  pub name: String,
+  // First characters of the token, to recognize it
+  pub prefix: String,
+  pub token_hash: String,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub created_by: ObjectId,
+  #[serde(serialize_with = "serialize_option_datetime")]
+  pub last_used_at: Option<DateTime>,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApitokenRes {
+  #[serde(rename = "_id", serialize_with = "serialize_object_id")]
+  pub id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub project_id: ObjectId,
+  pub name: String,
+  pub prefix: String,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub created_by: ObjectId,
+  #[serde(serialize_with = "serialize_option_datetime")]
+  pub last_used_at: Option<DateTime>,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub created_at: DateTime,
+}
+
+// The token is returned only once, when created
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApitokenCreatedRes {
+  pub apitoken: ApitokenRes,
+  pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApitokenDto {
+  pub name: String,
+}
+
+impl From<Apitoken> for ApitokenRes {
+  fn from(apitoken: Apitoken) -> Self {
+    ApitokenRes {
+      id: apitoken.id,
+      project_id: apitoken.project_id,
+      name: apitoken.name,
+      prefix: apitoken.prefix,
+      created_by: apitoken.created_by,
+      last_used_at: apitoken.last_used_at,
+      created_at: apitoken.created_at,
+    }
+  }
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+
+use bson::DateTime;
+use mongodb::{
+  bson::{doc, oid::ObjectId}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use crate::{projects::schema::Project, service::db::{ self, MongoRepo}};
+use super::schema::{Apitoken, ApitokenDto};
+use rocket::futures::TryStreamExt;
+
+pub const APITOKEN_PREFIX: &str = "tb_";
+
+pub fn get_apitokens_repo(client: Client) -> MongoRepo<Apitoken> {
+  return db::get_mongo_repo(client, "test_boss", "apitokens");
+}
+
+impl MongoRepo<Apitoken> {
+  pub async fn get_project_apitokens(&self, project_id: &str) -> Result<Vec<Apitoken>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "project_id": ObjectId::parse_str(project_id)? };
+    let cursor = self.col.find(filter, None).await?;
+    let apitokens: Vec<Apitoken> = cursor.try_collect().await?;
+    Ok(apitokens)
+  }
+
+  pub async fn get_apitoken_by_id(&self, id: &str) -> Result<Option<Apitoken>, Box<dyn Error + Send + Sync>> {
+    let oid = ObjectId::parse_str(id)?;
+    let filter = doc! { "_id": oid };
+    let result = self.col.find_one(filter, None).await?;
+    Ok(result)
+  }
+
+  pub async fn get_apitoken_by_token(&self, token: &str) -> Result<Option<Apitoken>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "token_hash": hash_apitoken(token) };
+    let result = self.col.find_one(filter, None).await?;
+    Ok(result)
+  }
+
+  // Returns the clear token, it cannot be recovered later
+  pub async fn create_apitoken(&self, project: &Project, created_by: ObjectId, data: ApitokenDto) -> Result<(InsertOneResult, String), Box<dyn Error + Send + Sync>> {
+    let mut secret = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let token = format!("{}{}", APITOKEN_PREFIX, secret.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+
+    let new_doc = Apitoken {
+      id: ObjectId::new(),
+      account_id: project.account_id,
+      project_id: project.id,
+      name: data.name,
+      prefix: token.chars().take(APITOKEN_PREFIX.len() + 6).collect(),
+      token_hash: hash_apitoken(&token),
+      created_by,
+      last_used_at: None,
+      created_at: DateTime::from_chrono(chrono::Utc::now()),
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok((result, token))
+  }
+
+  pub async fn touch_apitoken(&self, id: ObjectId) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": id };
+    let upd_doc = doc! { "$set": {
+      "last_used_at": DateTime::from_chrono(chrono::Utc::now())
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn delete_apitoken(&self, id: String) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let result = self.col.delete_one(filter, None).await?;
+    Ok(result)
+  }
+}
+
+fn hash_apitoken(token: &str) -> String {
+  Sha256::digest(token.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,53 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use mongodb::bson::oid::ObjectId;
+use rocket::{Request, State};
+
+use crate::service::db::MongoRepo;
+use crate::service::http_errors::JsonError;
+
+use super::schema::Apitoken;
+
+// Token sent by CI in the X-Api-Token header
+#[derive(Debug)]
+pub struct ApiToken {
+  pub token: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiToken {
+  type Error = JsonError;
+
+  async fn from_request(req: &'r Request<'_>) -> Outcome<Self, JsonError> {
+    match req.headers().get_one("x-api-token") {
+      None => {
+        let response = JsonError::Unauthorized("Error validating API token - No Token Provided".to_string());
+        Outcome::Error((Status::Unauthorized, response))
+      },
+      Some(token) => Outcome::Success(ApiToken { token: token.trim().to_string() }),
+    }
+  }
+}
+
+pub async fn get_apitoken(apitoken_repo: &State<MongoRepo<Apitoken>>, api_token: ApiToken) -> Result<Apitoken, JsonError> {
+  match apitoken_repo.get_apitoken_by_token(&api_token.token).await {
+    Ok(Some(apitoken)) => {
+      let _ = apitoken_repo.touch_apitoken(apitoken.id).await;
+      Ok(apitoken)
+    },
+    Ok(None) => Err(JsonError::Unauthorized("Invalid API token".to_string())),
+    Err(e) => {
+      log::error!("Error getting API token: {}", e);
+      Err(JsonError::Internal("Error getting API token".to_string()))
+    },
+  }
+}
+
+// The token must have been created for the project
+pub async fn authorize_project_apitoken(apitoken_repo: &State<MongoRepo<Apitoken>>, api_token: ApiToken, project_id: ObjectId) -> Result<Apitoken, JsonError> {
+  let apitoken = get_apitoken(apitoken_repo, api_token).await?;
+  if apitoken.project_id != project_id {
+    return Err(JsonError::Forbidden("API token not valid for this project".to_string()));
+  }
+  Ok(apitoken)
+}
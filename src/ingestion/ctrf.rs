@@ -0,0 +1,413 @@
+// Common Test Report Format (https://ctrf.io), read into report results and written from any
+// report. Manual results are exported as tests of the `test-boss` tool, blocked ones as `other`.
+
+use std::collections::HashMap;
+
+use rocket::serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{projects::schema::Project, testreports::schema::Testreport, testresults::schema::{TestresultAttachment, TestresultStatus, TestresultStep, Testresult}};
+
+use super::{schema::{IngestedStatus, IngestedTest}, service::keep_attachment};
+
+pub const CTRF_SPEC_VERSION: &str = "0.0.0";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CtrfReport {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  report_format: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  spec_version: Option<String>,
+  results: CtrfResults,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct CtrfResults {
+  tool: CtrfTool,
+  summary: CtrfSummary,
+  tests: Vec<CtrfTest>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  environment: Option<CtrfEnvironment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct CtrfTool {
+  name: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct CtrfSummary {
+  tests: u32,
+  passed: u32,
+  failed: u32,
+  pending: u32,
+  skipped: u32,
+  other: u32,
+  // Milliseconds since the epoch
+  start: i64,
+  stop: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct CtrfTest {
+  name: String,
+  status: String,
+  // Milliseconds
+  duration: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  suite: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  message: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  trace: Option<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  tags: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  file_path: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  retries: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  flaky: Option<bool>,
+  // Base64 image
+  #[serde(skip_serializing_if = "Option::is_none")]
+  screenshot: Option<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  steps: Vec<CtrfStep>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  extra: Option<Map<String, Value>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct CtrfStep {
+  name: String,
+  status: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct CtrfEnvironment {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  app_name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  app_version: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  build_name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  build_number: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  build_url: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  repository_url: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  commit: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  branch_name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  test_environment: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  extra: Option<Map<String, Value>>,
+}
+
+pub fn parse_ctrf(content: &str) -> Result<Vec<IngestedTest>, String> {
+  let report: CtrfReport = serde_json::from_str(content).map_err(|e| format!("Invalid CTRF JSON: {}", e))?;
+  if let Some(format) = &report.report_format {
+    if format != "CTRF" {
+      return Err(format!("Unexpected report format {}", format));
+    }
+  }
+
+  let tests = report.results.tests.into_iter().map(|test| {
+    let status = match test.status.as_str() {
+      "passed" => IngestedStatus::Passed,
+      "failed" => IngestedStatus::Failed,
+      _ => IngestedStatus::Skipped,
+    };
+    let steps = test.steps.into_iter().map(|step| TestresultStep {
+      status: get_ctrf_step_status(&step.status),
+      name: step.name,
+      duration_ms: None,
+      message: String::new(),
+    }).collect();
+    let attachments = test.screenshot.into_iter()
+      .map(|screenshot| TestresultAttachment { name: "screenshot".to_string(), content_type: "image/png".to_string(), data: screenshot })
+      .filter(keep_attachment)
+      .collect();
+    let suite: Vec<String> = test.file_path.into_iter().chain(test.suite).filter(|suite| !suite.is_empty()).collect();
+    let mut key_path = suite.clone();
+    key_path.push(test.name.trim().to_string());
+    let message = test.message.into_iter().chain(test.trace).collect::<Vec<String>>().join("\n");
+
+    IngestedTest {
+      name: test.name.trim().to_string(),
+      key: Some(key_path.join(" > ")),
+      suite,
+      tags: test.tags,
+      status,
+      retries: test.retries.unwrap_or(0),
+      flaky: test.flaky.unwrap_or(false),
+      duration_ms: Some(test.duration),
+      message,
+      steps,
+      attachments,
+    }
+  }).collect();
+  Ok(tests)
+}
+
+fn get_ctrf_step_status(status: &str) -> TestresultStatus {
+  match status {
+    "passed" => TestresultStatus::Passed,
+    "failed" => TestresultStatus::Failed,
+    _ => TestresultStatus::NotExecuted,
+  }
+}
+
+fn get_ctrf_status(status: TestresultStatus) -> &'static str {
+  match status {
+    TestresultStatus::Passed => "passed",
+    TestresultStatus::Failed => "failed",
+    TestresultStatus::Blocked => "other",
+    TestresultStatus::NotExecuted => "skipped",
+  }
+}
+
+pub fn get_testreport_ctrf(project: &Project, testreport: &Testreport, testresults: &[Testresult]) -> CtrfReport {
+  let sections: HashMap<_, &str> = testreport.sections.iter()
+    .map(|section| (section.testsection_id, section.name.as_str()))
+    .collect();
+
+  let mut summary = CtrfSummary {
+    tests: testresults.len() as u32,
+    start: testreport.created_at.timestamp_millis(),
+    stop: testresults.iter().filter(|testresult| testresult.updated)
+      .map(|testresult| testresult.updated_at.timestamp_millis())
+      .max()
+      .unwrap_or(testreport.updated_at.timestamp_millis()),
+    ..Default::default()
+  };
+
+  let tests = testresults.iter().map(|testresult| {
+    let status = testresult.status();
+    match status {
+      TestresultStatus::Passed => summary.passed += 1,
+      TestresultStatus::Failed => summary.failed += 1,
+      TestresultStatus::Blocked => summary.other += 1,
+      TestresultStatus::NotExecuted => summary.skipped += 1,
+    }
+    let mut extra = Map::new();
+    extra.insert("testcheck_id".to_string(), Value::from(testresult.testcheck_id.to_hex()));
+    extra.insert("testresult_id".to_string(), Value::from(testresult.id.to_hex()));
+    extra.insert("automated".to_string(), Value::from(testresult.automated));
+    if !testresult.url_issue.is_empty() {
+      extra.insert("url_issue".to_string(), Value::from(testresult.url_issue.clone()));
+    }
+    if !testresult.url_result.is_empty() {
+      extra.insert("url_result".to_string(), Value::from(testresult.url_result.clone()));
+    }
+
+    CtrfTest {
+      name: testresult.name.clone(),
+      status: get_ctrf_status(status).to_string(),
+      duration: testresult.duration_ms.unwrap_or(0),
+      suite: testresult.section_id.and_then(|section_id| sections.get(&section_id)).map(|name| name.to_string()),
+      message: Some(testresult.notes.clone()).filter(|notes| !notes.is_empty()),
+      tags: testresult.tags.clone(),
+      retries: Some(testresult.retries),
+      flaky: Some(testresult.flacky),
+      screenshot: testresult.attachments.iter()
+        .find(|attachment| attachment.content_type.starts_with("image/"))
+        .map(|attachment| attachment.data.clone()),
+      steps: testresult.steps.iter().map(|step| CtrfStep {
+        name: step.name.clone(),
+        status: get_ctrf_status(step.status).to_string(),
+      }).collect(),
+      extra: Some(extra),
+      ..Default::default()
+    }
+  }).collect();
+
+  let mut environment_extra = Map::new();
+  if let Some(testconfiguration) = &testreport.testconfiguration {
+    environment_extra.insert("configuration".to_string(), Value::from(testconfiguration.name.clone()));
+    for parameter in &testconfiguration.parameters {
+      environment_extra.insert(parameter.name.clone(), Value::from(parameter.value.clone()));
+    }
+  }
+  let build = testreport.build.clone().unwrap_or_default();
+  if let Some(tag) = build.tag {
+    environment_extra.insert("tag".to_string(), Value::from(tag));
+  }
+  if let Some(actor) = build.actor {
+    environment_extra.insert("actor".to_string(), Value::from(actor));
+  }
+  let environment = CtrfEnvironment {
+    app_name: Some(project.name.clone()),
+    app_version: Some(project.version.clone()).filter(|version| !version.is_empty()),
+    build_name: Some(testreport.execution.clone()).filter(|execution| !execution.is_empty()),
+    build_number: build.build_number,
+    build_url: build.pipeline_url,
+    repository_url: Some(project.repository.clone()).filter(|repository| !repository.is_empty()),
+    commit: build.commit_sha,
+    branch_name: build.branch,
+    test_environment: build.environment.or_else(|| testreport.testconfiguration.as_ref().and_then(|testconfiguration| testconfiguration.environment.clone())),
+    extra: Some(environment_extra).filter(|extra| !extra.is_empty()),
+  };
+
+  CtrfReport {
+    report_format: Some("CTRF".to_string()),
+    spec_version: Some(CTRF_SPEC_VERSION.to_string()),
+    results: CtrfResults {
+      tool: CtrfTool { name: "test-boss".to_string(), version: Some(env!("CARGO_PKG_VERSION").to_string()) },
+      summary,
+      tests,
+      environment: Some(environment),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bson::{oid::ObjectId, DateTime};
+
+  use super::*;
+  use crate::testreports::schema::{TestreportSection, TestreportStatus};
+
+  const REPORT: &str = r#"{
+  "reportFormat": "CTRF",
+  "specVersion": "0.0.0",
+  "results": {
+    "tool": { "name": "jest", "version": "29.7.0" },
+    "summary": { "tests": 3, "passed": 1, "failed": 1, "pending": 0, "skipped": 1, "other": 0, "start": 1715680000000, "stop": 1715680005000 },
+    "tests": [
+      { "name": "adds a product", "status": "passed", "duration": 120, "suite": "cart", "filePath": "cart.test.ts", "tags": ["smoke"] },
+      {
+        "name": "checks out", "status": "failed", "duration": 300, "suite": "cart", "filePath": "cart.test.ts",
+        "message": "expected 200", "trace": "at checkout (cart.test.ts:12)", "retries": 2, "flaky": false,
+        "screenshot": "iVBORw0KGgo=",
+        "steps": [{ "name": "open the cart", "status": "passed" }, { "name": "pay", "status": "failed" }]
+      },
+      { "name": "refunds", "status": "pending", "duration": 0 }
+    ]
+  }
+}"#;
+
+  #[test]
+  fn parses_tests() {
+    let tests = parse_ctrf(REPORT).unwrap();
+    assert_eq!(tests.len(), 3);
+    assert_eq!(tests[0].key.as_deref(), Some("cart.test.ts > cart > adds a product"));
+    assert_eq!(tests[0].suite, ["cart.test.ts", "cart"]);
+    assert_eq!(tests[0].tags, ["smoke"]);
+    assert_eq!(tests[0].status, IngestedStatus::Passed);
+
+    assert_eq!(tests[1].status, IngestedStatus::Failed);
+    assert_eq!(tests[1].message, "expected 200\nat checkout (cart.test.ts:12)");
+    assert_eq!(tests[1].retries, 2);
+    assert_eq!(tests[1].steps[1].status, TestresultStatus::Failed);
+    assert_eq!(tests[1].attachments.len(), 1);
+
+    assert_eq!(tests[2].status, IngestedStatus::Skipped);
+    assert_eq!(tests[2].key.as_deref(), Some("refunds"));
+  }
+
+  #[test]
+  fn rejects_other_formats() {
+    assert!(parse_ctrf(&REPORT.replace("\"CTRF\"", "\"JUnit\"")).is_err());
+    assert!(parse_ctrf("<testsuites />").is_err());
+  }
+
+  #[test]
+  fn export_imports_back() {
+    let now = DateTime::now();
+    let project = Project {
+      id: ObjectId::new(),
+      name: "Shop".to_string(),
+      version: "2.1".to_string(),
+      description: String::new(),
+      repository: String::new(),
+      signoff: Default::default(),
+      flaky: Default::default(),
+      quality_gate: Vec::new(),
+      account_id: ObjectId::new(),
+      created_at: now,
+      updated_at: now,
+    };
+    let section_id = ObjectId::new();
+    let testreport = Testreport {
+      id: ObjectId::new(),
+      account_id: project.account_id,
+      project_id: project.id,
+      testlist_id: ObjectId::new(),
+      parent_id: None,
+      name: "Release 2.1".to_string(),
+      description: String::new(),
+      execution: "nightly".to_string(),
+      tags_filter: None,
+      status: TestreportStatus::InProgress,
+      signoff: None,
+      history: Vec::new(),
+      executors: None,
+      sections: vec![TestreportSection { testsection_id: section_id, parent_id: None, name: "Cart".to_string(), description: String::new(), position: 0 }],
+      testconfiguration: None,
+      build: None,
+      created_at: now,
+      updated_at: now,
+    };
+    let testresult = |name: &str, updated: bool, pass: bool, blocked: bool| Testresult {
+      id: ObjectId::new(),
+      account_id: project.account_id,
+      testreport_id: testreport.id,
+      testcheck_id: ObjectId::new(),
+      section_id: Some(section_id),
+      testcheck_revision: 1,
+      name: name.to_string(),
+      description: String::new(),
+      expected: String::new(),
+      tags: vec!["smoke".to_string()],
+      position: 0,
+      updated,
+      pass,
+      blocked,
+      flacky: false,
+      automated: false,
+      notes: if updated && !pass { "broken".to_string() } else { String::new() },
+      url_issue: String::new(),
+      url_result: String::new(),
+      executors: Vec::new(),
+      assignee_id: None,
+      steps: Vec::new(),
+      retries: 0,
+      duration_ms: Some(50),
+      attachments: Vec::new(),
+      created_at: now,
+      updated_at: now,
+    };
+    let testresults = vec![
+      testresult("Add a product", true, true, false),
+      testresult("Check out", true, false, false),
+      testresult("Pay by card", true, false, true),
+      testresult("Refund", false, false, false),
+    ];
+
+    let ctrf = serde_json::to_string(&get_testreport_ctrf(&project, &testreport, &testresults)).unwrap();
+    let tests = parse_ctrf(&ctrf).unwrap();
+    let statuses: Vec<(&str, IngestedStatus)> = tests.iter().map(|test| (test.name.as_str(), test.status)).collect();
+    assert_eq!(statuses, [
+      ("Add a product", IngestedStatus::Passed),
+      ("Check out", IngestedStatus::Failed),
+      ("Pay by card", IngestedStatus::Skipped),
+      ("Refund", IngestedStatus::Skipped),
+    ]);
+    assert_eq!(tests[0].key.as_deref(), Some("Cart > Add a product"));
+    assert_eq!(tests[1].message, "broken");
+    assert_eq!(tests[1].duration_ms, Some(50));
+  }
+}
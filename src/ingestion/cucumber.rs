@@ -0,0 +1,282 @@
+// Cucumber JSON reports, as written by the `json` formatter of cucumber-js, cucumber-jvm and
+// cucumber-ruby. Retried scenarios appear once per attempt, the last attempt is the outcome.
+
+use rocket::serde::Deserialize;
+
+use crate::testresults::schema::{TestresultAttachment, TestresultStatus, TestresultStep};
+
+use super::{schema::{IngestedStatus, IngestedTest}, service::keep_attachment};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CucumberFeature {
+  uri: String,
+  name: String,
+  tags: Vec<CucumberTag>,
+  elements: Vec<CucumberElement>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CucumberElement {
+  id: String,
+  // Rows of a scenario outline share their id with some formatters
+  line: Option<u32>,
+  name: String,
+  #[serde(rename = "type")]
+  kind: String,
+  tags: Vec<CucumberTag>,
+  before: Vec<CucumberStep>,
+  steps: Vec<CucumberStep>,
+  after: Vec<CucumberStep>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CucumberTag {
+  name: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CucumberStep {
+  keyword: String,
+  name: String,
+  result: CucumberResult,
+  embeddings: Vec<CucumberEmbedding>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CucumberResult {
+  status: String,
+  // Nanoseconds
+  duration: Option<u64>,
+  error_message: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CucumberEmbedding {
+  mime_type: String,
+  data: String,
+  name: Option<String>,
+}
+
+pub fn parse_cucumber(content: &str) -> Result<Vec<IngestedTest>, String> {
+  let features: Vec<CucumberFeature> = serde_json::from_str(content).map_err(|e| format!("Invalid Cucumber JSON: {}", e))?;
+
+  // Attempts of each scenario, in report order
+  let mut tests: Vec<(String, IngestedTest, bool)> = Vec::new();
+  for feature in features {
+    let mut background: &[CucumberStep] = &[];
+    for element in &feature.elements {
+      if element.kind == "background" {
+        background = &element.steps;
+        continue;
+      }
+      let id = format!("{}:{}:{}", feature.uri, element.line.unwrap_or(0), if element.id.is_empty() { &element.name } else { &element.id });
+      let test = get_scenario_test(&feature, element, background);
+      background = &[];
+      match tests.iter_mut().find(|(test_id, _, _)| *test_id == id) {
+        Some((_, previous, failed_before)) => {
+          *failed_before |= previous.status == IngestedStatus::Failed;
+          // Screenshots of the failed attempts are kept
+          let mut attachments = std::mem::take(&mut previous.attachments);
+          attachments.extend(test.attachments.iter().cloned());
+          *previous = IngestedTest {
+            retries: previous.retries + 1,
+            flaky: *failed_before && test.status == IngestedStatus::Passed,
+            attachments,
+            ..test
+          };
+        },
+        None => tests.push((id, test, false)),
+      }
+    }
+  }
+  Ok(tests.into_iter().map(|(_, test, _)| test).collect())
+}
+
+fn get_scenario_test(feature: &CucumberFeature, element: &CucumberElement, background: &[CucumberStep]) -> IngestedTest {
+  let hooks_and_steps: Vec<&CucumberStep> = element.before.iter()
+    .chain(background.iter())
+    .chain(element.steps.iter())
+    .chain(element.after.iter())
+    .collect();
+
+  let mut status = IngestedStatus::Passed;
+  let mut message = String::new();
+  let mut duration_ns: Option<u64> = None;
+  let mut attachments = Vec::new();
+  for step in &hooks_and_steps {
+    match step.result.status.as_str() {
+      "passed" => {},
+      "failed" | "ambiguous" => {
+        status = IngestedStatus::Failed;
+        if message.is_empty() {
+          message = step.result.error_message.clone().unwrap_or_default();
+        }
+      },
+      _ if status == IngestedStatus::Passed => status = IngestedStatus::Skipped,
+      _ => {},
+    }
+    if let Some(duration) = step.result.duration {
+      duration_ns = Some(duration_ns.unwrap_or(0) + duration);
+    }
+    for (index, embedding) in step.embeddings.iter().enumerate() {
+      let attachment = TestresultAttachment {
+        name: embedding.name.clone().unwrap_or_else(|| format!("{} {}", step.name.trim(), index + 1).trim().to_string()),
+        content_type: embedding.mime_type.clone(),
+        data: embedding.data.clone(),
+      };
+      if keep_attachment(&attachment) {
+        attachments.push(attachment);
+      }
+    }
+  }
+
+  // Hooks are reported without keyword, only the steps are kept
+  let steps = background.iter().chain(element.steps.iter())
+    .filter(|step| !step.keyword.trim().is_empty())
+    .map(|step| TestresultStep {
+      name: format!("{} {}", step.keyword.trim(), step.name.trim()),
+      status: match step.result.status.as_str() {
+        "passed" => TestresultStatus::Passed,
+        "failed" | "ambiguous" => TestresultStatus::Failed,
+        _ => TestresultStatus::NotExecuted,
+      },
+      duration_ms: step.result.duration.map(|duration| duration / 1_000_000),
+      message: step.result.error_message.clone().unwrap_or_default(),
+    })
+    .collect();
+
+  let mut tags: Vec<String> = Vec::new();
+  for tag in feature.tags.iter().chain(element.tags.iter()) {
+    let tag = tag.name.trim_start_matches('@').to_string();
+    if !tag.is_empty() && !tags.contains(&tag) {
+      tags.push(tag);
+    }
+  }
+
+  IngestedTest {
+    name: element.name.trim().to_string(),
+    key: Some(format!("{} > {}", feature.name.trim(), element.name.trim())),
+    suite: vec![feature.name.trim().to_string()],
+    tags,
+    status,
+    retries: 0,
+    flaky: false,
+    duration_ms: duration_ns.map(|duration| duration / 1_000_000),
+    message,
+    steps,
+    attachments,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const REPORT: &str = r#"[
+  {
+    "uri": "features/cart.feature",
+    "name": "Shopping cart",
+    "tags": [{ "name": "@web" }],
+    "elements": [
+      {
+        "type": "background",
+        "name": "",
+        "steps": [{ "keyword": "Given ", "name": "a signed in customer", "result": { "status": "passed", "duration": 1000000 } }]
+      },
+      {
+        "id": "shopping-cart;add-a-product",
+        "line": 6,
+        "type": "scenario",
+        "name": "Add a product",
+        "tags": [{ "name": "@smoke" }, { "name": "@web" }],
+        "before": [{ "keyword": "", "name": "", "result": { "status": "passed", "duration": 500000 } }],
+        "steps": [
+          { "keyword": "When ", "name": "they add a product", "result": { "status": "passed", "duration": 2000000 } },
+          {
+            "keyword": "Then ", "name": "the cart shows it",
+            "result": { "status": "failed", "duration": 3000000, "error_message": "expected 1, got 0" },
+            "embeddings": [{ "mime_type": "image/png", "data": "iVBORw0KGgo=" }]
+          }
+        ]
+      },
+      {
+        "id": "shopping-cart;checkout",
+        "line": 12,
+        "type": "scenario",
+        "name": "Checkout",
+        "steps": [{ "keyword": "When ", "name": "they pay", "result": { "status": "failed", "error_message": "timeout" } }]
+      },
+      {
+        "id": "shopping-cart;checkout",
+        "line": 12,
+        "type": "scenario",
+        "name": "Checkout",
+        "steps": [{ "keyword": "When ", "name": "they pay", "result": { "status": "passed" } }]
+      },
+      {
+        "id": "shopping-cart;discount;;2",
+        "line": 20,
+        "type": "scenario",
+        "name": "Discount",
+        "steps": [{ "keyword": "When ", "name": "they use a code", "result": { "status": "pending" } }]
+      },
+      {
+        "id": "shopping-cart;discount;;3",
+        "line": 21,
+        "type": "scenario",
+        "name": "Discount",
+        "steps": [{ "keyword": "When ", "name": "they use a code", "result": { "status": "passed" } }]
+      }
+    ]
+  }
+]"#;
+
+  #[test]
+  fn parses_scenarios_steps_and_embeddings() {
+    let tests = parse_cucumber(REPORT).unwrap();
+    let test = &tests[0];
+    assert_eq!(test.name, "Add a product");
+    assert_eq!(test.key.as_deref(), Some("Shopping cart > Add a product"));
+    assert_eq!(test.suite, ["Shopping cart"]);
+    assert_eq!(test.tags, ["web", "smoke"]);
+    assert_eq!(test.status, IngestedStatus::Failed);
+    assert_eq!(test.message, "expected 1, got 0");
+    assert_eq!(test.duration_ms, Some(6));
+    // The background runs before the scenario steps, hooks are left out
+    let steps: Vec<&str> = test.steps.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(steps, ["Given a signed in customer", "When they add a product", "Then the cart shows it"]);
+    assert_eq!(test.attachments.len(), 1);
+    assert_eq!(test.attachments[0].content_type, "image/png");
+  }
+
+  #[test]
+  fn merges_retried_attempts() {
+    let tests = parse_cucumber(REPORT).unwrap();
+    let checkout: Vec<&IngestedTest> = tests.iter().filter(|test| test.name == "Checkout").collect();
+    assert_eq!(checkout.len(), 1);
+    assert_eq!(checkout[0].status, IngestedStatus::Passed);
+    assert_eq!(checkout[0].retries, 1);
+    assert!(checkout[0].flaky);
+  }
+
+  #[test]
+  fn keeps_outline_rows_sharing_a_name() {
+    let tests = parse_cucumber(REPORT).unwrap();
+    let discount: Vec<&IngestedTest> = tests.iter().filter(|test| test.name == "Discount").collect();
+    assert_eq!(discount.len(), 2);
+    assert_eq!(discount[0].key, discount[1].key);
+    assert_eq!(discount[0].status, IngestedStatus::Skipped);
+    assert_eq!(discount[1].status, IngestedStatus::Passed);
+  }
+
+  #[test]
+  fn rejects_other_documents() {
+    assert!(parse_cucumber("{\"suites\": []}").is_err());
+  }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use bson::oid::ObjectId;
+use log::{error, warn};
+use rocket::{post, routes, serde::json::Json, State};
+use crate::{apitokens::{schema::Apitoken, token::{authorize_project_apitoken, ApiToken}}, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::Session}, testchecks::{endpoints::create_initial_testrevision, schema::{Testcheck, TestcheckDto, TestcheckMode}}, testreports::{endpoints::{allowed_for_testreport, get_testreport_by_id}, schema::{Testreport, TestreportStatus}, service::normalize_testreport_build}, projects::schema::Project, testresults::{endpoints::{flag_flaky_testresult, get_auto_flag_flaky_policy}, schema::Testresult}, testrevisions::schema::Testrevision, users::schema::User};
+
+use super::{ctrf::parse_ctrf, cucumber::parse_cucumber, playwright::parse_playwright, schema::{IngestedStatus, IngestedTest, IngestionDto, IngestionEntry, IngestionFormat, IngestionOutcome, IngestionSummary}, service::{find_ingested_testresult, get_ingested_status, merge_ingested_test, suggest_ingested_testchecks}, tap::parse_tap, trx::parse_trx, xunit::parse_xunit};
+
+// Fills the report results from an automated test report, callable from CI with an API token
+#[post("/<testreport_id>/ingest", format = "json", data = "<data>")]
+async fn ingest_testreport(api_token: Result<ApiToken, JsonError>, jwt: Result<JWT, JsonError>, testreport_id: &str, data: Json<IngestionDto>, apitoken_repo: &State<MongoRepo<Apitoken>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, project_repo: &State<MongoRepo<Project>>) -> Result<Json<IngestionSummary>, JsonError> {
+  let (testreport, author_id) = authorize_ingestion(api_token, jwt, testreport_id, apitoken_repo, sessions_repo, users_repo, testreport_repo).await?;
+  let data = data.into_inner();
+  let build = data.build.map(normalize_testreport_build).transpose().map_err(JsonError::BadRequest)?;
+
+  let tests = match data.format {
+    IngestionFormat::Ctrf => parse_ctrf(&data.content),
+    IngestionFormat::Cucumber => parse_cucumber(&data.content),
+    IngestionFormat::Playwright => parse_playwright(&data.content),
+    IngestionFormat::Tap => parse_tap(&data.content),
+    IngestionFormat::Trx => parse_trx(&data.content),
+    IngestionFormat::Xunit => parse_xunit(&data.content),
+  }.map_err(JsonError::BadRequest)?;
+
+  let summary = ingest_testreport_tests(testcheck_repo, testresult_repo, testrevision_repo, project_repo, &testreport, data.format, tests, data.create_missing, data.dry_run, author_id).await?;
+  if let Some(build) = build.filter(|build| !build.is_empty() && !data.dry_run) {
+    if let Err(e) = testreport_repo.update_testreport_build(testreport.id, &build).await {
+      error!("Error updating testreport build: {}", e);
+      return Err(JsonError::Internal("Error updating testreport build".to_string()));
+    }
+  }
+  Ok(Json(summary))
+}
+
+pub fn get_ingestion_routes() -> Vec<rocket::Route> {
+  routes![ingest_testreport]
+}
+
+// Returns the report and the user recorded as author of the created checks
+async fn authorize_ingestion(api_token: Result<ApiToken, JsonError>, jwt: Result<JWT, JsonError>, testreport_id: &str, apitoken_repo: &State<MongoRepo<Apitoken>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>) -> Result<(Testreport, ObjectId), JsonError> {
+  let (testreport, author_id) = match api_token {
+    Ok(api_token) => {
+      let testreport = get_testreport_by_id(testreport_repo, testreport_id).await?;
+      let apitoken = authorize_project_apitoken(apitoken_repo, api_token, testreport.project_id).await?;
+      (testreport, apitoken.created_by)
+    },
+    Err(_) => {
+      let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+      let user_id = jwts.user.id;
+      (allowed_for_testreport(jwts, testreport_repo, testreport_id).await?, user_id)
+    },
+  };
+  if testreport.status == TestreportStatus::SignedOff {
+    return Err(JsonError::Forbidden("Testresults of a signed off testreport are locked".to_string()));
+  }
+  Ok((testreport, author_id))
+}
+
+// Matching and upsert shared by every format: each test updates the result of its check,
+// tests of the same result are merged, tests skipped by the tool leave results untouched
+pub async fn ingest_testreport_tests(testcheck_repo: &State<MongoRepo<Testcheck>>, testresult_repo: &State<MongoRepo<Testresult>>, testrevision_repo: &State<MongoRepo<Testrevision>>, project_repo: &State<MongoRepo<Project>>, testreport: &Testreport, format: IngestionFormat, tests: Vec<IngestedTest>, create_missing: bool, dry_run: bool, author_id: ObjectId) -> Result<IngestionSummary, JsonError> {
+  let testreport_id = testreport.id.to_hex();
+  let testresults = match testresult_repo.get_testreport_testresults(&testreport_id).await {
+    Ok(testresults) => testresults,
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      return Err(JsonError::Internal("Error getting testresults".to_string()));
+    },
+  };
+  let testchecks = match testcheck_repo.get_testlist_testchecks(&testreport.testlist_id.to_hex()).await {
+    Ok(testchecks) => testchecks,
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      return Err(JsonError::Internal("Error getting testchecks".to_string()));
+    },
+  };
+
+  // Tests of the same result, or creating the same check, are merged before being recorded
+  let mut matched: Vec<(ObjectId, IngestedTest)> = Vec::new();
+  let mut matched_indexes: HashMap<ObjectId, usize> = HashMap::new();
+  let mut created: Vec<(IngestedTest, Vec<usize>)> = Vec::new();
+  let mut created_indexes: HashMap<String, usize> = HashMap::new();
+  let mut entries = Vec::new();
+  for test in tests {
+    let mut entry = IngestionEntry {
+      name: test.name.clone(),
+      outcome: IngestionOutcome::Skipped,
+      testcheck_id: None,
+      testresult_id: None,
+      status: get_ingested_status(&test),
+      message: String::new(),
+      suggestions: Vec::new(),
+    };
+    if test.name.is_empty() {
+      entry.outcome = IngestionOutcome::Errored;
+      entry.message = "Test without name".to_string();
+      entries.push(entry);
+      continue;
+    }
+
+    let testresult = find_ingested_testresult(&test, &testresults, &testchecks);
+    match testresult {
+      Some(testresult) => {
+        entry.testcheck_id = Some(testresult.testcheck_id);
+        entry.testresult_id = Some(testresult.id);
+      },
+      None => entry.suggestions = suggest_ingested_testchecks(&test, &testchecks),
+    }
+    if test.status == IngestedStatus::Skipped {
+      entry.message = "Skipped by the test tool".to_string();
+      entries.push(entry);
+      continue;
+    }
+
+    match testresult {
+      Some(testresult) => {
+        entry.outcome = IngestionOutcome::Matched;
+        if testchecks.iter().any(|testcheck| testcheck.id == testresult.testcheck_id && testcheck.mode == TestcheckMode::Manual) {
+          entry.message = "Testcheck is marked as manual".to_string();
+        }
+        match matched_indexes.get(&testresult.id) {
+          Some(&index) => {
+            merge_ingested_test(&mut matched[index].1, test);
+            entry.message = "Merged with a previous test of the same check".to_string();
+          },
+          None => {
+            matched_indexes.insert(testresult.id, matched.len());
+            matched.push((testresult.id, test));
+          },
+        }
+      },
+      None if create_missing => {
+        match created_indexes.get(&test.name.to_lowercase()) {
+          Some(&index) => {
+            merge_ingested_test(&mut created[index].0, test);
+            created[index].1.push(entries.len());
+            entry.outcome = IngestionOutcome::Matched;
+            entry.message = "Merged with a previous test creating the same check".to_string();
+          },
+          None => {
+            created_indexes.insert(test.name.to_lowercase(), created.len());
+            created.push((test, vec![entries.len()]));
+            entry.outcome = IngestionOutcome::Created;
+          },
+        }
+      },
+      None => {
+        entry.message = "No matching testcheck".to_string();
+      },
+    }
+    entries.push(entry);
+  }
+
+  if !dry_run {
+    // Results not reported flaky by the tool go through the project flaky policy, as manual ones
+    let policy = get_auto_flag_flaky_policy(project_repo, testreport).await?;
+    for (testresult_id, test) in &matched {
+      ingest_testresult(testresult_repo, *testresult_id, test).await?;
+      if let Some(policy) = policy.as_ref().filter(|_| !test.flaky) {
+        let testcheck_id = testresults.iter().find(|testresult| testresult.id == *testresult_id).map(|testresult| testresult.testcheck_id);
+        if let Some(testcheck_id) = testcheck_id {
+          flag_flaky_testresult(testresult_repo, policy, &testcheck_id, &testresult_id.to_hex()).await?;
+        }
+      }
+    }
+    for (test, indexes) in &created {
+      let (testcheck_id, testresult_id) = create_ingested_testresult(testcheck_repo, testresult_repo, testrevision_repo, testreport, &testchecks, test, author_id).await?;
+      for &index in indexes {
+        entries[index].testcheck_id = Some(testcheck_id);
+        entries[index].testresult_id = Some(testresult_id);
+      }
+    }
+  }
+
+  let count = |outcome: IngestionOutcome| entries.iter().filter(|entry| entry.outcome == outcome).count() as u32;
+  Ok(IngestionSummary {
+    format,
+    dry_run,
+    total: entries.len() as u32,
+    matched: count(IngestionOutcome::Matched),
+    created: count(IngestionOutcome::Created),
+    skipped: count(IngestionOutcome::Skipped),
+    errored: count(IngestionOutcome::Errored),
+    entries,
+  })
+}
+
+async fn ingest_testresult(testresult_repo: &State<MongoRepo<Testresult>>, id: ObjectId, test: &IngestedTest) -> Result<(), JsonError> {
+  match testresult_repo.ingest_testresult(id, test).await {
+    Ok(_) => Ok(()),
+    Err(e) => {
+      error!("Error updating testresult: {}", e);
+      Err(JsonError::Internal("Error updating testresult".to_string()))
+    },
+  }
+}
+
+// Adds a check for the test at the root of the report testlist, and its result in the report.
+// The test key becomes the check automation key unless another check of the testlist has it.
+async fn create_ingested_testresult(testcheck_repo: &State<MongoRepo<Testcheck>>, testresult_repo: &State<MongoRepo<Testresult>>, testrevision_repo: &State<MongoRepo<Testrevision>>, testreport: &Testreport, testchecks: &[Testcheck], test: &IngestedTest, author_id: ObjectId) -> Result<(ObjectId, ObjectId), JsonError> {
+  let automation_key = test.key.clone()
+    .filter(|key| !testchecks.iter().any(|testcheck| testcheck.automation_keys().any(|automation_key| automation_key == key)));
+  let data = TestcheckDto {
+    name: test.name.clone(),
+    description: test.suite.join(" > "),
+    expected: String::new(),
+    tags: test.tags.clone(),
+    section_id: None,
+    external_key: None,
+    automation_key,
+    automation_aliases: Vec::new(),
+    mode: TestcheckMode::Automated,
+  };
+  let testcheck_id = match testcheck_repo.create_testcheck(&testreport.account_id.to_hex(), &testreport.project_id.to_hex(), &testreport.testlist_id.to_hex(), data).await {
+    Ok(inserted) => inserted.inserted_id.as_object_id().unwrap().to_hex(),
+    Err(e) => {
+      error!("Error creating testcheck: {}", e);
+      return Err(JsonError::Internal("Error creating testcheck".to_string()));
+    },
+  };
+  let testcheck = match testcheck_repo.get_testcheck_by_id(&testcheck_id).await {
+    Ok(Some(testcheck)) => testcheck,
+    Ok(None) => {
+      warn!("Testcheck not found: {}", testcheck_id);
+      return Err(JsonError::NotFound("Testcheck not found".to_string()));
+    },
+    Err(e) => {
+      error!("Error getting testcheck: {}", e);
+      return Err(JsonError::Internal("Error getting testcheck".to_string()));
+    },
+  };
+  create_initial_testrevision(testrevision_repo, &testcheck, author_id).await?;
+
+  let testcheck_id = testcheck.id;
+  let testresult_id = match testresult_repo.create_testresult(&testreport.id.to_hex(), testcheck).await {
+    Ok(inserted) => inserted.inserted_id.as_object_id().unwrap(),
+    Err(e) => {
+      error!("Error creating testresult: {}", e);
+      return Err(JsonError::Internal("Error creating testresult".to_string()));
+    },
+  };
+  ingest_testresult(testresult_repo, testresult_id, test).await?;
+  Ok((testcheck_id, testresult_id))
+}
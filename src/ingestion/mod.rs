@@ -0,0 +1,9 @@
+pub mod ctrf;
+pub mod cucumber;
+pub mod endpoints;
+pub mod playwright;
+pub mod schema;
+pub mod service;
+pub mod tap;
+pub mod trx;
+pub mod xunit;
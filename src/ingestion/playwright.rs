@@ -0,0 +1,235 @@
+// Playwright JSON reporter output. Each spec run by a project is a test, its results are the
+// attempts: a test passing after failed attempts is reported as flaky by Playwright.
+
+use rocket::serde::Deserialize;
+
+use crate::testresults::schema::{TestresultAttachment, TestresultStatus, TestresultStep};
+
+use super::{schema::{IngestedStatus, IngestedTest}, service::keep_attachment};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PlaywrightReport {
+  suites: Vec<PlaywrightSuite>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PlaywrightSuite {
+  title: String,
+  suites: Vec<PlaywrightSuite>,
+  specs: Vec<PlaywrightSpec>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PlaywrightSpec {
+  title: String,
+  tags: Vec<String>,
+  tests: Vec<PlaywrightTest>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct PlaywrightTest {
+  project_name: String,
+  status: String,
+  results: Vec<PlaywrightResult>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct PlaywrightResult {
+  status: String,
+  duration: Option<u64>,
+  error: Option<PlaywrightError>,
+  steps: Vec<PlaywrightStep>,
+  attachments: Vec<PlaywrightAttachment>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PlaywrightError {
+  message: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct PlaywrightStep {
+  title: String,
+  duration: Option<u64>,
+  error: Option<PlaywrightError>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct PlaywrightAttachment {
+  name: String,
+  content_type: String,
+  // Base64 content, missing for attachments written to disk
+  body: Option<String>,
+}
+
+pub fn parse_playwright(content: &str) -> Result<Vec<IngestedTest>, String> {
+  let report: PlaywrightReport = serde_json::from_str(content).map_err(|e| format!("Invalid Playwright JSON: {}", e))?;
+  let mut tests = Vec::new();
+  for suite in &report.suites {
+    collect_suite_tests(suite, &[], &mut tests);
+  }
+  Ok(tests)
+}
+
+fn collect_suite_tests(suite: &PlaywrightSuite, parents: &[String], tests: &mut Vec<IngestedTest>) {
+  let mut path = parents.to_vec();
+  if !suite.title.is_empty() {
+    path.push(suite.title.clone());
+  }
+  for spec in &suite.specs {
+    for test in &spec.tests {
+      tests.push(get_spec_test(spec, test, &path));
+    }
+  }
+  for child in &suite.suites {
+    collect_suite_tests(child, &path, tests);
+  }
+}
+
+fn get_spec_test(spec: &PlaywrightSpec, test: &PlaywrightTest, path: &[String]) -> IngestedTest {
+  let last = test.results.last();
+  let status = match test.status.as_str() {
+    "expected" | "flaky" if last.map(|result| result.status.as_str()) != Some("skipped") => IngestedStatus::Passed,
+    "unexpected" => IngestedStatus::Failed,
+    _ => IngestedStatus::Skipped,
+  };
+
+  let steps = last.map(|result| result.steps.iter().map(|step| TestresultStep {
+    name: step.title.clone(),
+    status: if step.error.is_some() { TestresultStatus::Failed } else { TestresultStatus::Passed },
+    duration_ms: step.duration,
+    message: step.error.as_ref().map(|error| error.message.clone()).unwrap_or_default(),
+  }).collect()).unwrap_or_default();
+
+  let attachments = test.results.iter()
+    .flat_map(|result| result.attachments.iter())
+    .filter_map(|attachment| attachment.body.as_ref().map(|body| TestresultAttachment {
+      name: attachment.name.clone(),
+      content_type: attachment.content_type.clone(),
+      data: body.clone(),
+    }))
+    .filter(keep_attachment)
+    .collect();
+
+  let mut suite = path.to_vec();
+  if !test.project_name.is_empty() {
+    suite.push(test.project_name.clone());
+  }
+  // The project is left out of the key, the runs of every project are merged into one result
+  let mut key_path = path.to_vec();
+  key_path.push(spec.title.clone());
+
+  IngestedTest {
+    name: spec.title.trim().to_string(),
+    key: Some(key_path.join(" > ")),
+    suite,
+    tags: spec.tags.iter().map(|tag| tag.trim_start_matches('@').to_string()).collect(),
+    status,
+    retries: test.results.len().saturating_sub(1) as u32,
+    flaky: test.status == "flaky",
+    duration_ms: last.and_then(|result| result.duration),
+    message: last.and_then(|result| result.error.as_ref()).map(|error| error.message.clone()).unwrap_or_default(),
+    steps,
+    attachments,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const REPORT: &str = r#"{
+  "config": { "projects": [{ "name": "chromium" }, { "name": "firefox" }] },
+  "suites": [
+    {
+      "title": "cart.spec.ts",
+      "file": "cart.spec.ts",
+      "suites": [
+        {
+          "title": "cart",
+          "specs": [
+            {
+              "title": "adds a product",
+              "tags": ["@smoke"],
+              "tests": [
+                {
+                  "projectName": "chromium",
+                  "status": "expected",
+                  "results": [{ "status": "passed", "duration": 120, "steps": [{ "title": "click add", "duration": 20 }] }]
+                },
+                {
+                  "projectName": "firefox",
+                  "status": "unexpected",
+                  "results": [{
+                    "status": "failed",
+                    "duration": 300,
+                    "error": { "message": "expected 1 item" },
+                    "steps": [{ "title": "click add", "duration": 30, "error": { "message": "expected 1 item" } }],
+                    "attachments": [
+                      { "name": "screenshot", "contentType": "image/png", "body": "iVBORw0KGgo=" },
+                      { "name": "trace", "contentType": "application/zip", "path": "/tmp/trace.zip" }
+                    ]
+                  }]
+                }
+              ]
+            },
+            {
+              "title": "checks out",
+              "tests": [{
+                "projectName": "chromium",
+                "status": "flaky",
+                "results": [{ "status": "failed", "duration": 500 }, { "status": "passed", "duration": 400 }]
+              }]
+            },
+            {
+              "title": "refunds",
+              "tests": [{ "projectName": "chromium", "status": "skipped", "results": [{ "status": "skipped" }] }]
+            }
+          ]
+        }
+      ]
+    }
+  ]
+}"#;
+
+  #[test]
+  fn parses_each_project_run_under_the_same_key() {
+    let tests = parse_playwright(REPORT).unwrap();
+    assert_eq!(tests.len(), 4);
+    assert_eq!(tests[0].key, tests[1].key);
+    assert_eq!(tests[0].key.as_deref(), Some("cart.spec.ts > cart > adds a product"));
+    assert_eq!(tests[0].suite, ["cart.spec.ts", "cart", "chromium"]);
+    assert_eq!(tests[1].suite, ["cart.spec.ts", "cart", "firefox"]);
+    assert_eq!(tests[0].tags, ["smoke"]);
+
+    assert_eq!(tests[0].status, IngestedStatus::Passed);
+    assert_eq!(tests[1].status, IngestedStatus::Failed);
+    assert_eq!(tests[1].message, "expected 1 item");
+    assert_eq!(tests[1].steps[0].status, TestresultStatus::Failed);
+    // Attachments written to disk are not in the report
+    assert_eq!(tests[1].attachments.len(), 1);
+  }
+
+  #[test]
+  fn parses_flaky_and_skipped_tests() {
+    let tests = parse_playwright(REPORT).unwrap();
+    assert_eq!(tests[2].status, IngestedStatus::Passed);
+    assert!(tests[2].flaky);
+    assert_eq!(tests[2].retries, 1);
+    assert_eq!(tests[2].duration_ms, Some(400));
+    assert_eq!(tests[3].status, IngestedStatus::Skipped);
+  }
+
+  #[test]
+  fn rejects_invalid_json() {
+    assert!(parse_playwright("<testsuites />").is_err());
+  }
+}
@@ -0,0 +1,114 @@
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::{service::db::{serialize_object_id, serialize_option_object_id}, testreports::schema::TestreportBuild, testresults::schema::{TestresultAttachment, TestresultStatus, TestresultStep}};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestionFormat {
+  Ctrf,
+  Cucumber,
+  Playwright,
+  Tap,
+  Trx,
+  Xunit,
+}
+
+impl std::fmt::Display for IngestionFormat {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      IngestionFormat::Ctrf => write!(f, "ctrf"),
+      IngestionFormat::Cucumber => write!(f, "cucumber"),
+      IngestionFormat::Playwright => write!(f, "playwright"),
+      IngestionFormat::Tap => write!(f, "tap"),
+      IngestionFormat::Trx => write!(f, "trx"),
+      IngestionFormat::Xunit => write!(f, "xunit"),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IngestionDto {
+  pub format: IngestionFormat,
+  // Report produced by the test tool
+  pub content: String,
+  // Tests without a matching check get a new check in the testlist of the report
+  #[serde(default)]
+  pub create_missing: bool,
+  #[serde(default)]
+  pub dry_run: bool,
+  // CI build that ran the tests, replaces the build of the report
+  #[serde(default)]
+  pub build: Option<TestreportBuild>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestedStatus {
+  Passed,
+  Failed,
+  Skipped,
+}
+
+// A test of an automated report, whatever its format
+#[derive(Debug, Clone)]
+pub struct IngestedTest {
+  pub name: String,
+  // Identifies the test across runs, e.g. `Feature > Scenario`
+  pub key: Option<String>,
+  // Feature, file or describe blocks containing the test
+  pub suite: Vec<String>,
+  pub tags: Vec<String>,
+  pub status: IngestedStatus,
+  pub retries: u32,
+  // Passed after failed attempts
+  pub flaky: bool,
+  pub duration_ms: Option<u64>,
+  pub message: String,
+  pub steps: Vec<TestresultStep>,
+  pub attachments: Vec<TestresultAttachment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestionOutcome {
+  Matched,
+  Created,
+  Skipped,
+  Errored,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IngestionEntry {
+  pub name: String,
+  pub outcome: IngestionOutcome,
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub testcheck_id: Option<ObjectId>,
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub testresult_id: Option<ObjectId>,
+  pub status: Option<TestresultStatus>,
+  pub message: String,
+  // Checks that may be the unmatched test, to give it an automation key or alias
+  pub suggestions: Vec<IngestionSuggestion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IngestionSuggestion {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testcheck_id: ObjectId,
+  pub name: String,
+  // Similarity of the names, from 0 to 1
+  pub score: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IngestionSummary {
+  pub format: IngestionFormat,
+  pub dry_run: bool,
+  pub total: u32,
+  pub matched: u32,
+  pub created: u32,
+  pub skipped: u32,
+  pub errored: u32,
+  pub entries: Vec<IngestionEntry>,
+}
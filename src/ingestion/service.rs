@@ -0,0 +1,233 @@
+use std::{collections::HashMap, error::Error};
+
+use bson::DateTime;
+use mongodb::{bson::{self, doc, oid::ObjectId}, results::UpdateResult};
+use quick_xml::events::BytesStart;
+
+use crate::{service::db::MongoRepo, testchecks::schema::Testcheck, testresults::schema::{TestresultAttachment, TestresultStatus, Testresult}};
+
+use super::schema::{IngestedStatus, IngestedTest, IngestionSuggestion};
+
+// Larger attachments are dropped, results are stored in a single document
+pub const MAX_ATTACHMENT_SIZE: usize = 1024 * 1024;
+// Attachments of a result beyond this total are dropped, far below the 16 MB limit of a document
+pub const MAX_ATTACHMENTS_SIZE: usize = 8 * MAX_ATTACHMENT_SIZE;
+
+pub const MIN_SUGGESTION_SCORE: f64 = 0.5;
+pub const MAX_SUGGESTIONS: usize = 3;
+
+impl MongoRepo<Testresult> {
+  // Records the outcome of an automated test, a flaky test flags the result without clearing a manual flag
+  pub async fn ingest_testresult(&self, id: ObjectId, test: &IngestedTest) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": id };
+    let mut set_doc = doc! {
+      "updated": true,
+      "pass": test.status == IngestedStatus::Passed,
+      "blocked": false,
+      "automated": true,
+      "retries": test.retries,
+      "duration_ms": test.duration_ms.map(|duration| duration as i64),
+      "steps": bson::to_bson(&test.steps)?,
+      "attachments": bson::to_bson(&get_stored_attachments(&test.attachments))?,
+      "updated_at": DateTime::from_chrono(chrono::Utc::now())
+    };
+    if test.flaky {
+      set_doc.insert("flacky", true);
+    }
+    if test.status == IngestedStatus::Failed && !test.message.is_empty() {
+      set_doc.insert("notes", test.message.clone());
+    }
+    let result = self.col.update_one(filter, doc! { "$set": set_doc }, None).await?;
+    Ok(result)
+  }
+}
+
+// Merges a test into a previous one recording the same result, e.g. a spec run by several
+// Playwright projects or outline rows sharing a name: a failure wins over a pass
+pub fn merge_ingested_test(previous: &mut IngestedTest, test: IngestedTest) {
+  if test.status == IngestedStatus::Failed && previous.status != IngestedStatus::Failed {
+    previous.status = IngestedStatus::Failed;
+    previous.steps = test.steps;
+  }
+  previous.retries += test.retries;
+  previous.flaky |= test.flaky;
+  previous.duration_ms = match (previous.duration_ms, test.duration_ms) {
+    (Some(previous), Some(duration)) => Some(previous + duration),
+    (previous, duration) => previous.or(duration),
+  };
+  if !test.message.is_empty() {
+    if !previous.message.is_empty() {
+      previous.message.push('\n');
+    }
+    previous.message.push_str(&test.message);
+  }
+  previous.attachments.extend(test.attachments);
+}
+
+pub fn get_ingested_status(test: &IngestedTest) -> Option<TestresultStatus> {
+  match test.status {
+    IngestedStatus::Passed => Some(TestresultStatus::Passed),
+    IngestedStatus::Failed => Some(TestresultStatus::Failed),
+    IngestedStatus::Skipped => None,
+  }
+}
+
+// Result of the check matching the test: by automation key or alias of the check, then by
+// external key for checks created before automation keys, then by check name
+pub fn find_ingested_testresult<'a>(test: &IngestedTest, testresults: &'a [Testresult], testchecks: &[Testcheck]) -> Option<&'a Testresult> {
+  let testcheck_testresult = |testcheck: &Testcheck| testresults.iter().find(|testresult| testresult.testcheck_id == testcheck.id);
+  for key in test.key.iter().chain(std::iter::once(&test.name)) {
+    let testcheck = testchecks.iter().find(|testcheck| testcheck.automation_keys().any(|automation_key| automation_key == key));
+    if let Some(testresult) = testcheck.and_then(testcheck_testresult) {
+      return Some(testresult);
+    }
+  }
+  if let Some(key) = &test.key {
+    let testcheck = testchecks.iter().find(|testcheck| testcheck.external_key.as_ref() == Some(key));
+    if let Some(testresult) = testcheck.and_then(testcheck_testresult) {
+      return Some(testresult);
+    }
+  }
+  let name = normalize_test_name(&test.name);
+  testresults.iter().find(|testresult| normalize_test_name(&testresult.name) == name)
+}
+
+// Checks with a name close to the one of an unmatched test, best first
+pub fn suggest_ingested_testchecks(test: &IngestedTest, testchecks: &[Testcheck]) -> Vec<IngestionSuggestion> {
+  let bigrams = get_name_bigrams(&test.name);
+  let mut suggestions: Vec<IngestionSuggestion> = testchecks.iter()
+    .map(|testcheck| IngestionSuggestion {
+      testcheck_id: testcheck.id,
+      name: testcheck.name.clone(),
+      score: (get_bigrams_similarity(&bigrams, &get_name_bigrams(&testcheck.name)) * 100.0).round() / 100.0,
+    })
+    .filter(|suggestion| suggestion.score >= MIN_SUGGESTION_SCORE)
+    .collect();
+  suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+  suggestions.truncate(MAX_SUGGESTIONS);
+  suggestions
+}
+
+// Character pairs of the name words, `test_user_login` and `User login` sharing theirs
+fn get_name_bigrams(name: &str) -> HashMap<(char, char), u32> {
+  let mut words: Vec<String> = Vec::new();
+  let mut word = String::new();
+  let mut previous_lowercase = false;
+  for c in name.chars() {
+    if (!c.is_alphanumeric() || (c.is_uppercase() && previous_lowercase)) && !word.is_empty() {
+      words.push(std::mem::take(&mut word));
+    }
+    if c.is_alphanumeric() {
+      word.extend(c.to_lowercase());
+    }
+    previous_lowercase = c.is_lowercase();
+  }
+  if !word.is_empty() {
+    words.push(word);
+  }
+
+  let mut bigrams = HashMap::new();
+  for word in words {
+    let chars: Vec<char> = format!(" {} ", word).chars().collect();
+    for pair in chars.windows(2) {
+      *bigrams.entry((pair[0], pair[1])).or_insert(0) += 1;
+    }
+  }
+  bigrams
+}
+
+// Dice coefficient of the bigrams, 1 for identical names
+fn get_bigrams_similarity(a: &HashMap<(char, char), u32>, b: &HashMap<(char, char), u32>) -> f64 {
+  let total: u32 = a.values().sum::<u32>() + b.values().sum::<u32>();
+  if total == 0 {
+    return 0.0;
+  }
+  let shared: u32 = a.iter().map(|(bigram, count)| (*count).min(*b.get(bigram).unwrap_or(&0))).sum();
+  2.0 * shared as f64 / total as f64
+}
+
+pub fn normalize_test_name(name: &str) -> String {
+  name.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+pub fn keep_attachment(attachment: &TestresultAttachment) -> bool {
+  !attachment.data.is_empty() && attachment.data.len() <= MAX_ATTACHMENT_SIZE
+}
+
+// First attachments of a result fitting in MAX_ATTACHMENTS_SIZE
+pub fn get_stored_attachments(attachments: &[TestresultAttachment]) -> Vec<&TestresultAttachment> {
+  let mut size = 0;
+  attachments.iter().filter(|attachment| {
+    if size + attachment.data.len() > MAX_ATTACHMENTS_SIZE {
+      return false;
+    }
+    size += attachment.data.len();
+    true
+  }).collect()
+}
+
+// Unescaped value of an attribute of an XML report element
+pub fn xml_attribute(element: &BytesStart, name: &str) -> Option<String> {
+  match element.try_get_attribute(name) {
+    Ok(Some(attribute)) => attribute.unescape_value().ok().map(|value| value.trim().to_string()),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test(status: IngestedStatus, duration_ms: Option<u64>, message: &str) -> IngestedTest {
+    IngestedTest {
+      name: "login".to_string(),
+      key: Some("auth > login".to_string()),
+      suite: vec!["auth".to_string()],
+      tags: Vec::new(),
+      status,
+      retries: 0,
+      flaky: false,
+      duration_ms,
+      message: message.to_string(),
+      steps: Vec::new(),
+      attachments: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn merged_failure_wins_over_pass() {
+    let mut previous = test(IngestedStatus::Passed, Some(100), "");
+    let mut failed = test(IngestedStatus::Failed, Some(50), "timeout");
+    failed.retries = 2;
+    merge_ingested_test(&mut previous, failed);
+    assert_eq!(previous.status, IngestedStatus::Failed);
+    assert_eq!(previous.retries, 2);
+    assert_eq!(previous.duration_ms, Some(150));
+    assert_eq!(previous.message, "timeout");
+
+    merge_ingested_test(&mut previous, test(IngestedStatus::Passed, None, ""));
+    assert_eq!(previous.status, IngestedStatus::Failed);
+    assert_eq!(previous.duration_ms, Some(150));
+  }
+
+  #[test]
+  fn merged_messages_are_kept() {
+    let mut previous = test(IngestedStatus::Failed, None, "chromium: timeout");
+    merge_ingested_test(&mut previous, test(IngestedStatus::Failed, None, "firefox: timeout"));
+    assert_eq!(previous.message, "chromium: timeout\nfirefox: timeout");
+  }
+
+  #[test]
+  fn stored_attachments_are_capped() {
+    let attachment = |name: &str, size: usize| TestresultAttachment { name: name.to_string(), content_type: "image/png".to_string(), data: "A".repeat(size) };
+    let mut attachments: Vec<TestresultAttachment> = (0..12).map(|index| attachment(&format!("screenshot-{}.png", index), 1_000_000)).collect();
+    attachments.push(attachment("trace.txt", 100));
+
+    let stored = get_stored_attachments(&attachments);
+    assert_eq!(stored.len(), 9);
+    assert_eq!(stored[7].name, "screenshot-7.png");
+    // Smaller attachments after the cap still fit
+    assert_eq!(stored[8].name, "trace.txt");
+    assert!(stored.iter().map(|attachment| attachment.data.len()).sum::<usize>() <= MAX_ATTACHMENTS_SIZE);
+  }
+}
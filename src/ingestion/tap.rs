@@ -0,0 +1,171 @@
+// Test Anything Protocol, versions 12 to 14, read line by line. Only the top level test points
+// are results, indented subtests are summarized by their parent point. SKIP and TODO directives
+// are reported as skipped, the YAML diagnostics `message` and `duration_ms` are kept. Top level
+// comments name the group of the following points, as written by tape.
+
+use super::schema::{IngestedStatus, IngestedTest};
+
+#[derive(Default)]
+struct TapParser {
+  tests: Vec<IngestedTest>,
+  suite: Option<String>,
+  // Inside the YAML diagnostics block of the latest test point
+  diagnostics: bool,
+  bailed_out: bool,
+}
+
+impl TapParser {
+  fn feed_line(&mut self, line: &str) {
+    let line = line.trim_end();
+    if self.diagnostics {
+      match line.trim() {
+        "..." => self.diagnostics = false,
+        diagnostic => self.feed_diagnostic(diagnostic),
+      }
+      return;
+    }
+    if line.starts_with(' ') || line.starts_with('\t') {
+      if line.trim() == "---" && !self.tests.is_empty() {
+        self.diagnostics = true;
+      }
+      return;
+    }
+    if line.starts_with("Bail out!") {
+      self.bailed_out = true;
+      return;
+    }
+    if let Some(comment) = line.strip_prefix('#') {
+      let comment = comment.trim();
+      if !comment.is_empty() && !comment.starts_with("Subtest:") {
+        self.suite = Some(comment.to_string());
+      }
+      return;
+    }
+    let (pass, rest) = if let Some(rest) = line.strip_prefix("not ok") {
+      (false, rest)
+    } else if let Some(rest) = line.strip_prefix("ok") {
+      (true, rest)
+    } else {
+      // Plan, version, comments and unknown lines
+      return;
+    };
+    // Unknown lines like `okay` or stray reporter output are ignored as well
+    if !rest.is_empty() && !rest.starts_with(' ') {
+      return;
+    }
+
+    let (description, directive) = match rest.split_once(" # ") {
+      Some((description, directive)) => (description, directive.trim().to_uppercase()),
+      None => (rest, String::new()),
+    };
+    let description = description.trim();
+    let number_end = description.find(|c: char| !c.is_ascii_digit()).unwrap_or(description.len());
+    let name = description[number_end..].trim_start().trim_start_matches('-').trim();
+    let name = if name.is_empty() { format!("test {}", &description[..number_end]) } else { name.to_string() };
+
+    let status = if directive.starts_with("SKIP") || directive.starts_with("TODO") {
+      IngestedStatus::Skipped
+    } else if pass {
+      IngestedStatus::Passed
+    } else {
+      IngestedStatus::Failed
+    };
+    let suite: Vec<String> = self.suite.iter().cloned().collect();
+    let mut key_path = suite.clone();
+    key_path.push(name.clone());
+    self.tests.push(IngestedTest {
+      name,
+      key: Some(key_path.join(" > ")),
+      suite,
+      tags: Vec::new(),
+      status,
+      retries: 0,
+      flaky: false,
+      duration_ms: None,
+      message: String::new(),
+      steps: Vec::new(),
+      attachments: Vec::new(),
+    });
+  }
+
+  fn feed_diagnostic(&mut self, diagnostic: &str) {
+    let test = match self.tests.last_mut() {
+      Some(test) => test,
+      None => return,
+    };
+    if let Some((key, value)) = diagnostic.split_once(':') {
+      let value = value.trim().trim_matches(|c| c == '\'' || c == '"');
+      match key.trim() {
+        "message" => test.message = value.to_string(),
+        "duration_ms" => test.duration_ms = value.parse::<f64>().ok().map(|duration| duration as u64),
+        _ => {},
+      }
+    }
+  }
+}
+
+pub fn parse_tap(content: &str) -> Result<Vec<IngestedTest>, String> {
+  let mut parser = TapParser::default();
+  for line in content.lines() {
+    parser.feed_line(line);
+    if parser.bailed_out {
+      break;
+    }
+  }
+  Ok(parser.tests)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const REPORT: &str = "TAP version 13
+1..6
+# login
+ok 1 - opens the form
+not ok 2 - rejects a wrong password
+  ---
+  message: 'expected 401, got 200'
+  duration_ms: 12.5
+  ...
+ok 3 - remembers the user # SKIP no cookies
+not ok 4 - resets the password # TODO not implemented
+    # Subtest: nested
+    ok 1 - inner
+    1..1
+ok 5
+Bail out! Database down
+ok 6 - never read
+";
+
+  #[test]
+  fn parses_points_directives_and_diagnostics() {
+    let tests = parse_tap(REPORT).unwrap();
+    let names: Vec<&str> = tests.iter().map(|test| test.name.as_str()).collect();
+    assert_eq!(names, ["opens the form", "rejects a wrong password", "remembers the user", "resets the password", "test 5"]);
+    assert_eq!(tests[0].status, IngestedStatus::Passed);
+    assert_eq!(tests[0].key.as_deref(), Some("login > opens the form"));
+    assert_eq!(tests[0].suite, ["login"]);
+
+    assert_eq!(tests[1].status, IngestedStatus::Failed);
+    assert_eq!(tests[1].message, "expected 401, got 200");
+    assert_eq!(tests[1].duration_ms, Some(12));
+
+    assert_eq!(tests[2].status, IngestedStatus::Skipped);
+    assert_eq!(tests[3].status, IngestedStatus::Skipped);
+    assert_eq!(tests[4].status, IngestedStatus::Passed);
+  }
+
+  #[test]
+  fn stops_at_bail_out() {
+    let tests = parse_tap("ok 1 - first\nBail out! Out of memory\nnot ok 2 - second\n").unwrap();
+    assert_eq!(tests.len(), 1);
+  }
+
+  #[test]
+  fn ignores_unknown_lines() {
+    let tests = parse_tap("1..2\nokay 1 - nope\nok 1 - first\nokapi: listening on 8080\nnot okay\nnot ok 2 - second\n").unwrap();
+    let names: Vec<&str> = tests.iter().map(|test| test.name.as_str()).collect();
+    assert_eq!(names, ["first", "second"]);
+  }
+}
@@ -0,0 +1,202 @@
+// Visual Studio test results (.trx) of vstest and dotnet test, read as an XML event stream.
+// Results come before the test definitions giving their class, which are resolved at the end.
+// Rows of data driven tests are inner results, only their parent result is kept.
+
+use std::collections::HashMap;
+
+use quick_xml::{events::{BytesStart, Event}, Reader};
+
+use super::{schema::{IngestedStatus, IngestedTest}, service::xml_attribute};
+
+struct TrxResult {
+  test_id: String,
+  test: IngestedTest,
+  trace: String,
+}
+
+pub fn parse_trx(content: &str) -> Result<Vec<IngestedTest>, String> {
+  let mut reader = Reader::from_str(content);
+  reader.config_mut().trim_text(true);
+
+  let mut results: Vec<TrxResult> = Vec::new();
+  let mut current: Option<TrxResult> = None;
+  let mut inner_depth = 0;
+  let mut class_names: HashMap<String, String> = HashMap::new();
+  let mut definition_id: Option<String> = None;
+  let mut text_element: Option<Vec<u8>> = None;
+  let mut root_checked = false;
+  loop {
+    let event = reader.read_event().map_err(|e| format!("Invalid TRX XML at position {}: {}", reader.error_position(), e))?;
+    match event {
+      Event::Start(element) | Event::Empty(element) if !root_checked => {
+        if element.local_name().as_ref() != b"TestRun" {
+          return Err("Not a TRX report, TestRun element expected".to_string());
+        }
+        root_checked = true;
+      },
+      Event::Start(element) => match element.local_name().as_ref() {
+        b"UnitTestResult" if current.is_none() => current = Some(get_trx_result(&element)),
+        b"InnerResults" if current.is_some() => inner_depth += 1,
+        b"Message" | b"StackTrace" if current.is_some() && inner_depth == 0 => text_element = Some(element.local_name().as_ref().to_vec()),
+        b"UnitTest" => definition_id = xml_attribute(&element, "id"),
+        _ => {},
+      },
+      Event::Empty(element) => match element.local_name().as_ref() {
+        b"UnitTestResult" if current.is_none() => results.push(get_trx_result(&element)),
+        b"TestMethod" => {
+          if let (Some(id), Some(class_name)) = (&definition_id, xml_attribute(&element, "className")) {
+            class_names.insert(id.clone(), class_name);
+          }
+        },
+        _ => {},
+      },
+      Event::Text(text) => {
+        let text = text.unescape().map_err(|e| format!("Invalid TRX XML: {}", e))?;
+        append_trx_text(&mut current, &text_element, &text);
+      },
+      Event::CData(text) => {
+        let text = text.decode().map_err(|e| format!("Invalid TRX XML: {}", e))?;
+        append_trx_text(&mut current, &text_element, &text);
+      },
+      Event::End(element) => {
+        text_element = None;
+        match element.local_name().as_ref() {
+          b"InnerResults" if inner_depth > 0 => inner_depth -= 1,
+          b"UnitTestResult" if inner_depth == 0 => results.extend(current.take()),
+          b"UnitTest" => definition_id = None,
+          _ => {},
+        }
+      },
+      Event::Eof => break,
+      _ => {},
+    }
+  }
+  if !root_checked {
+    return Err("Empty TRX report".to_string());
+  }
+
+  let tests = results.into_iter().map(|result| {
+    let mut test = result.test;
+    if !result.trace.is_empty() {
+      if !test.message.is_empty() {
+        test.message.push('\n');
+      }
+      test.message.push_str(&result.trace);
+    }
+    if let Some(class_name) = class_names.get(&result.test_id) {
+      if !test.name.starts_with(class_name.as_str()) {
+        test.key = Some(format!("{}.{}", class_name, test.name));
+      }
+      test.suite = vec![class_name.clone()];
+    }
+    test
+  }).collect();
+  Ok(tests)
+}
+
+fn get_trx_result(element: &BytesStart) -> TrxResult {
+  let name = xml_attribute(element, "testName").unwrap_or_default();
+  // Passed, Failed, NotExecuted, Inconclusive, Timeout, Aborted...
+  let status = match xml_attribute(element, "outcome").unwrap_or_default().as_str() {
+    "Passed" | "PassedButRunAborted" => IngestedStatus::Passed,
+    "Failed" | "Error" | "Timeout" | "Aborted" => IngestedStatus::Failed,
+    _ => IngestedStatus::Skipped,
+  };
+  TrxResult {
+    test_id: xml_attribute(element, "testId").unwrap_or_default(),
+    test: IngestedTest {
+      key: Some(name.clone()),
+      name,
+      suite: Vec::new(),
+      tags: Vec::new(),
+      status,
+      retries: 0,
+      flaky: false,
+      duration_ms: xml_attribute(element, "duration").and_then(|duration| parse_trx_duration(&duration)),
+      message: String::new(),
+      steps: Vec::new(),
+      attachments: Vec::new(),
+    },
+    trace: String::new(),
+  }
+}
+
+fn append_trx_text(current: &mut Option<TrxResult>, text_element: &Option<Vec<u8>>, text: &str) {
+  let (result, text_element) = match (current, text_element) {
+    (Some(result), Some(text_element)) => (result, text_element),
+    _ => return,
+  };
+  let target = if text_element == b"Message" { &mut result.test.message } else { &mut result.trace };
+  target.push_str(text.trim());
+}
+
+// hh:mm:ss.fffffff
+fn parse_trx_duration(duration: &str) -> Option<u64> {
+  let parts: Vec<&str> = duration.split(':').collect();
+  if parts.len() != 3 {
+    return None;
+  }
+  let hours = parts[0].parse::<f64>().ok()?;
+  let minutes = parts[1].parse::<f64>().ok()?;
+  let seconds = parts[2].parse::<f64>().ok()?;
+  Some(((hours * 3600.0 + minutes * 60.0 + seconds) * 1000.0).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const REPORT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<TestRun id="7d3b" name="build@agent" xmlns="http://microsoft.com/schemas/VisualStudio/TeamTest/2010">
+  <Results>
+    <UnitTestResult executionId="e1" testId="t1" testName="Login" duration="00:00:01.2500000" outcome="Passed" />
+    <UnitTestResult executionId="e2" testId="t2" testName="Checkout" duration="00:00:00.5000000" outcome="Failed">
+      <Output>
+        <ErrorInfo>
+          <Message>Expected 3 items</Message>
+          <StackTrace>at Shop.Tests.CartTests.Checkout()</StackTrace>
+        </ErrorInfo>
+      </Output>
+      <InnerResults>
+        <UnitTestResult executionId="e3" testId="t2" testName="Checkout (1)" outcome="Passed" />
+        <UnitTestResult executionId="e4" testId="t2" testName="Checkout (2)" outcome="Failed">
+          <Output><ErrorInfo><Message>Row 2</Message></ErrorInfo></Output>
+        </UnitTestResult>
+      </InnerResults>
+    </UnitTestResult>
+    <UnitTestResult executionId="e5" testId="t3" testName="Refund" outcome="NotExecuted" />
+  </Results>
+  <TestDefinitions>
+    <UnitTest name="Login" id="t1"><TestMethod className="Shop.Tests.AuthTests" name="Login" /></UnitTest>
+    <UnitTest name="Checkout" id="t2"><TestMethod className="Shop.Tests.CartTests" name="Checkout" /></UnitTest>
+  </TestDefinitions>
+</TestRun>"#;
+
+  #[test]
+  fn parses_results_and_resolves_classes() {
+    let tests = parse_trx(REPORT).unwrap();
+    let names: Vec<&str> = tests.iter().map(|test| test.name.as_str()).collect();
+    assert_eq!(names, ["Login", "Checkout", "Refund"]);
+
+    assert_eq!(tests[0].status, IngestedStatus::Passed);
+    assert_eq!(tests[0].key.as_deref(), Some("Shop.Tests.AuthTests.Login"));
+    assert_eq!(tests[0].suite, ["Shop.Tests.AuthTests"]);
+    assert_eq!(tests[0].duration_ms, Some(1250));
+
+    assert_eq!(tests[2].status, IngestedStatus::Skipped);
+    assert_eq!(tests[2].key.as_deref(), Some("Refund"));
+  }
+
+  #[test]
+  fn keeps_the_parent_of_inner_results() {
+    let tests = parse_trx(REPORT).unwrap();
+    assert_eq!(tests[1].status, IngestedStatus::Failed);
+    assert_eq!(tests[1].message, "Expected 3 items\nat Shop.Tests.CartTests.Checkout()");
+  }
+
+  #[test]
+  fn rejects_other_documents() {
+    assert!(parse_trx("<testsuites><testsuite /></testsuites>").is_err());
+    assert!(parse_trx("").is_err());
+  }
+}
@@ -0,0 +1,179 @@
+// xUnit.net v2 XML results (`-xml` option of the console runner), read as an XML event stream.
+// Tests are grouped by assembly and collection, `Category` traits and other traits become tags.
+
+use quick_xml::{events::{BytesStart, Event}, Reader};
+
+use super::{schema::{IngestedStatus, IngestedTest}, service::xml_attribute};
+
+pub fn parse_xunit(content: &str) -> Result<Vec<IngestedTest>, String> {
+  let mut reader = Reader::from_str(content);
+  reader.config_mut().trim_text(true);
+
+  let mut tests: Vec<IngestedTest> = Vec::new();
+  let mut current: Option<(IngestedTest, String)> = None;
+  let mut assembly = String::new();
+  let mut collection = String::new();
+  let mut text_element: Option<Vec<u8>> = None;
+  let mut root_checked = false;
+  loop {
+    let event = reader.read_event().map_err(|e| format!("Invalid xUnit XML at position {}: {}", reader.error_position(), e))?;
+    if let Event::Start(element) | Event::Empty(element) = &event {
+      if !root_checked && !matches!(element.local_name().as_ref(), b"assemblies" | b"assembly") {
+        return Err("Not an xUnit v2 report, assemblies element expected".to_string());
+      }
+      root_checked = true;
+    }
+    match event {
+      Event::Start(element) => match element.local_name().as_ref() {
+        b"assembly" => assembly = get_xunit_assembly_name(&element),
+        b"collection" => collection = xml_attribute(&element, "name").unwrap_or_default(),
+        b"test" => current = Some((get_xunit_test(&element, &assembly, &collection), String::new())),
+        b"message" | b"stack-trace" | b"reason" if current.is_some() => text_element = Some(element.local_name().as_ref().to_vec()),
+        _ => {},
+      },
+      Event::Empty(element) => match element.local_name().as_ref() {
+        b"test" => tests.push(get_xunit_test(&element, &assembly, &collection)),
+        b"trait" => {
+          if let Some((test, _)) = &mut current {
+            let name = xml_attribute(&element, "name").unwrap_or_default();
+            let value = xml_attribute(&element, "value").unwrap_or_default();
+            let tag = if name.eq_ignore_ascii_case("category") { value } else { format!("{}:{}", name, value) };
+            if !tag.is_empty() && !test.tags.contains(&tag) {
+              test.tags.push(tag);
+            }
+          }
+        },
+        _ => {},
+      },
+      Event::Text(text) => {
+        let text = text.unescape().map_err(|e| format!("Invalid xUnit XML: {}", e))?;
+        append_xunit_text(&mut current, &text_element, &text);
+      },
+      Event::CData(text) => {
+        let text = text.decode().map_err(|e| format!("Invalid xUnit XML: {}", e))?;
+        append_xunit_text(&mut current, &text_element, &text);
+      },
+      Event::End(element) => {
+        text_element = None;
+        match element.local_name().as_ref() {
+          b"test" => {
+            if let Some((mut test, trace)) = current.take() {
+              if !trace.is_empty() {
+                if !test.message.is_empty() {
+                  test.message.push('\n');
+                }
+                test.message.push_str(&trace);
+              }
+              tests.push(test);
+            }
+          },
+          b"collection" => collection.clear(),
+          b"assembly" => assembly.clear(),
+          _ => {},
+        }
+      },
+      Event::Eof => break,
+      _ => {},
+    }
+  }
+  if !root_checked {
+    return Err("Empty xUnit report".to_string());
+  }
+  Ok(tests)
+}
+
+// File name of the tested assembly, its full path depends on the build machine
+fn get_xunit_assembly_name(element: &BytesStart) -> String {
+  let name = xml_attribute(element, "name").unwrap_or_default();
+  name.rsplit(['/', '\\']).next().unwrap_or_default().to_string()
+}
+
+fn get_xunit_test(element: &BytesStart, assembly: &str, collection: &str) -> IngestedTest {
+  let name = xml_attribute(element, "name").unwrap_or_default();
+  // Theories share their method, the display name tells the rows apart
+  let key = match (xml_attribute(element, "type"), xml_attribute(element, "method")) {
+    (Some(class_name), Some(method)) if !name.contains('(') => format!("{}.{}", class_name, method),
+    _ => name.clone(),
+  };
+  let status = match xml_attribute(element, "result").unwrap_or_default().as_str() {
+    "Pass" => IngestedStatus::Passed,
+    "Fail" => IngestedStatus::Failed,
+    _ => IngestedStatus::Skipped,
+  };
+  IngestedTest {
+    name,
+    key: Some(key),
+    suite: [assembly, collection].iter().filter(|suite| !suite.is_empty()).map(|suite| suite.to_string()).collect(),
+    tags: Vec::new(),
+    status,
+    retries: 0,
+    flaky: false,
+    // Seconds
+    duration_ms: xml_attribute(element, "time").and_then(|time| time.parse::<f64>().ok()).map(|time| (time * 1000.0).round() as u64),
+    message: String::new(),
+    steps: Vec::new(),
+    attachments: Vec::new(),
+  }
+}
+
+fn append_xunit_text(current: &mut Option<(IngestedTest, String)>, text_element: &Option<Vec<u8>>, text: &str) {
+  let ((test, trace), text_element) = match (current, text_element) {
+    (Some(current), Some(text_element)) => (current, text_element),
+    _ => return,
+  };
+  let target = if text_element == b"stack-trace" { trace } else { &mut test.message };
+  target.push_str(text.trim());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const REPORT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<assemblies timestamp="05/14/2024 10:12:03">
+  <assembly name="C:\build\Shop.Tests.dll" environment="64-bit .NET 8.0" total="3" passed="1" failed="1" skipped="1">
+    <collection name="Test collection for Shop.Tests.CartTests" total="3">
+      <test name="Shop.Tests.CartTests.Total(count: 1)" type="Shop.Tests.CartTests" method="Total" time="0.0105" result="Pass">
+        <traits>
+          <trait name="Category" value="smoke" />
+          <trait name="Owner" value="shop" />
+        </traits>
+      </test>
+      <test name="Shop.Tests.CartTests.Total(count: 2)" type="Shop.Tests.CartTests" method="Total" time="0.02" result="Fail">
+        <failure exception-type="Xunit.Sdk.EqualException">
+          <message><![CDATA[Assert.Equal() Failure]]></message>
+          <stack-trace><![CDATA[at Shop.Tests.CartTests.Total(Int32 count)]]></stack-trace>
+        </failure>
+      </test>
+      <test name="Shop.Tests.CartTests.Empty" type="Shop.Tests.CartTests" method="Empty" time="0" result="Skip">
+        <reason><![CDATA[Waiting for the new cart]]></reason>
+      </test>
+    </collection>
+  </assembly>
+</assemblies>"#;
+
+  #[test]
+  fn keeps_theory_rows_apart() {
+    let tests = parse_xunit(REPORT).unwrap();
+    let keys: Vec<&str> = tests.iter().map(|test| test.key.as_deref().unwrap()).collect();
+    assert_eq!(keys, ["Shop.Tests.CartTests.Total(count: 1)", "Shop.Tests.CartTests.Total(count: 2)", "Shop.Tests.CartTests.Empty"]);
+    assert_eq!(tests[0].status, IngestedStatus::Passed);
+    assert_eq!(tests[1].status, IngestedStatus::Failed);
+    assert_eq!(tests[2].status, IngestedStatus::Skipped);
+  }
+
+  #[test]
+  fn parses_suites_traits_and_failures() {
+    let tests = parse_xunit(REPORT).unwrap();
+    assert_eq!(tests[0].suite, ["Shop.Tests.dll", "Test collection for Shop.Tests.CartTests"]);
+    assert_eq!(tests[0].tags, ["smoke", "Owner:shop"]);
+    assert_eq!(tests[0].duration_ms, Some(11));
+    assert_eq!(tests[1].message, "Assert.Equal() Failure\nat Shop.Tests.CartTests.Total(Int32 count)");
+    assert_eq!(tests[2].message, "Waiting for the new cart");
+  }
+
+  #[test]
+  fn rejects_other_documents() {
+    assert!(parse_xunit("<TestRun />").is_err());
+  }
+}
@@ -1,4 +1,5 @@
 mod service;
+mod apitokens;
 mod users;
 mod sessions;
 mod accounts;
@@ -7,12 +8,24 @@ mod testlists;
 mod testchecks;
 mod testreports;
 mod testresults;
+mod testsections;
+mod testrevisions;
+mod testplans;
+mod testenvironments;
+mod testconfigurations;
+mod analytics;
+mod ingestion;
+mod qualitygates;
 
 use std::time::Duration;
 
 use accounts::{endpoints::get_accounts_routes, service::get_accounts_repo};
+use analytics::endpoints::get_analytics_routes;
+use apitokens::{endpoints::get_apitokens_routes, service::get_apitokens_repo};
+use ingestion::endpoints::get_ingestion_routes;
 use projects::endpoints::get_projects_routes;
 use projects::service::get_projects_repo;
+use qualitygates::endpoints::get_qualitygates_routes;
 use testchecks::endpoints::get_testchecks_routes;
 use testchecks::service::get_testchecks_repo;
 use testlists::endpoints::get_testlists_routes;
@@ -27,7 +40,17 @@ use sessions::endpoints::get_sessions_routes;
 use sessions::service::get_sessions_repo;
 use testreports::endpoints::get_testreports_routes;
 use testreports::service::get_testreports_repo;
+use testresults::endpoints::get_testresults_routes;
 use testresults::service::get_testresults_repo;
+use testconfigurations::endpoints::get_testconfigurations_routes;
+use testconfigurations::service::get_testconfigurations_repo;
+use testenvironments::endpoints::get_testenvironments_routes;
+use testenvironments::service::get_testenvironments_repo;
+use testplans::endpoints::get_testplans_routes;
+use testplans::service::get_testplans_repo;
+use testrevisions::service::get_testrevisions_repo;
+use testsections::endpoints::get_testsections_routes;
+use testsections::service::get_testsections_repo;
 use users::endpoints::get_users_routes;
 use users::service::get_users_repo;
 
@@ -40,12 +63,18 @@ async fn rocket() -> _ {
 
   let testlist_repo = get_testlists_repo(client.clone());
   let testcheck_repo = get_testchecks_repo(client.clone());
+  let testsection_repo = get_testsections_repo(client.clone());
+  let testrevision_repo = get_testrevisions_repo(client.clone());
+  let testplan_repo = get_testplans_repo(client.clone());
+  let testenvironment_repo = get_testenvironments_repo(client.clone());
+  let testconfiguration_repo = get_testconfigurations_repo(client.clone());
   let testreport_repo = get_testreports_repo(client.clone());
   let testresult_repo = get_testresults_repo(client.clone());
   let account_repo = get_accounts_repo(client.clone());
   let project_repo = get_projects_repo(client.clone());
   let sessions_repo = get_sessions_repo(client.clone());
   let users_repo = get_users_repo(client.clone());
+  let apitoken_repo = get_apitokens_repo(client.clone());
 
   let _ = users_repo.unique_index("email").await;
   let _ = project_repo.index("account_id").await;
@@ -57,6 +86,20 @@ async fn rocket() -> _ {
   let _ = testcheck_repo.index("project_id").await;
   let _ = testcheck_repo.index("account_id").await;
   let _ = testcheck_repo.index("name").await;
+  let _ = testcheck_repo.index("section_id").await;
+  let _ = testsection_repo.index("testlist_id").await;
+  let _ = testrevision_repo.index("testcheck_id").await;
+  let _ = testplan_repo.index("project_id").await;
+  let _ = testenvironment_repo.index("project_id").await;
+  let _ = testconfiguration_repo.index("project_id").await;
+  let _ = testreport_repo.index("testlist_id").await;
+  let _ = testreport_repo.index("build.commit_sha").await;
+  let _ = testreport_repo.index("build.branch").await;
+  let _ = testresult_repo.index("testreport_id").await;
+  let _ = testresult_repo.index("assignee_id").await;
+  let _ = testresult_repo.index("testcheck_id").await;
+  let _ = apitoken_repo.unique_index("token_hash").await;
+  let _ = apitoken_repo.index("project_id").await;
   let _ = sessions_repo.ttl_index("expires_at", Duration::from_secs(0)).await;
 
   let allowed_origins = AllowedOrigins::some_exact(&[&cfg.allowed_origins]);
@@ -77,9 +120,18 @@ async fn rocket() -> _ {
     .mount("/api/v1/users", get_users_routes())
     .mount("/api/v1/accounts", get_accounts_routes())
     .mount("/api/v1/projects", get_projects_routes())
+    .mount("/api/v1/projects", get_analytics_routes())
+    .mount("/api/v1/projects", get_qualitygates_routes())
     .mount("/api/v1/testlists", get_testlists_routes())
     .mount("/api/v1/testchecks", get_testchecks_routes())
+    .mount("/api/v1/testsections", get_testsections_routes())
     .mount("/api/v1/testreports", get_testreports_routes())
+    .mount("/api/v1/testreports", get_ingestion_routes())
+    .mount("/api/v1/testresults", get_testresults_routes())
+    .mount("/api/v1/testplans", get_testplans_routes())
+    .mount("/api/v1/testenvironments", get_testenvironments_routes())
+    .mount("/api/v1/testconfigurations", get_testconfigurations_routes())
+    .mount("/api/v1/apitokens", get_apitokens_routes())
     .attach(cors)
     .manage(cfg)
     .manage(account_repo)
@@ -88,8 +140,14 @@ async fn rocket() -> _ {
     .manage(users_repo)
     .manage(testlist_repo)
     .manage(testcheck_repo)
+    .manage(testsection_repo)
+    .manage(testrevision_repo)
+    .manage(testplan_repo)
+    .manage(testenvironment_repo)
+    .manage(testconfiguration_repo)
     .manage(testreport_repo)
     .manage(testresult_repo)
+    .manage(apitoken_repo)
     .register("/", catchers![
       catch_bad_request,
       catch_unauthorized,
@@ -0,0 +1,522 @@
+// Archive of a whole project to move it between instances or to restore it: a zip with a manifest,
+// one file per collection of newline-delimited MongoDB Extended JSON, and the attachments of the
+// results stored as plain files.
+
+use std::{collections::{HashMap, HashSet}, io::{Cursor, Read, Write}};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bson::{Bson, SerializerOptions};
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{testchecks::schema::Testcheck, testconfigurations::schema::Testconfiguration, testenvironments::schema::Testenvironment, testlists::schema::Testlist, testplans::schema::Testplan, testreports::schema::Testreport, testresults::schema::Testresult, testrevisions::schema::Testrevision, testsections::schema::Testsection};
+
+use super::schema::{Project, ProjectArchiveEntry, ProjectArchiveManifest};
+
+pub const PROJECT_ARCHIVE_FORMAT: &str = "test-boss-project";
+// Bumped whenever the archived documents change in a way an older import can not read
+pub const PROJECT_ARCHIVE_VERSION: u32 = 1;
+
+const MANIFEST_PATH: &str = "manifest.json";
+const PROJECT_PATH: &str = "project.json";
+const TESTLISTS_PATH: &str = "testlists.json";
+const TESTSECTIONS_PATH: &str = "testsections.json";
+const TESTCHECKS_PATH: &str = "testchecks.json";
+const TESTREVISIONS_PATH: &str = "testrevisions.json";
+const TESTENVIRONMENTS_PATH: &str = "testenvironments.json";
+const TESTCONFIGURATIONS_PATH: &str = "testconfigurations.json";
+const TESTPLANS_PATH: &str = "testplans.json";
+const TESTREPORTS_PATH: &str = "testreports.json";
+const TESTRESULTS_PATH: &str = "testresults.json";
+// Uncompressed size of all the entries, guards against zip bombs
+const MAX_ARCHIVE_CONTENT_SIZE: u64 = 1 << 30;
+
+pub struct ProjectArchive {
+  pub project: Project,
+  pub testlists: Vec<Testlist>,
+  pub testsections: Vec<Testsection>,
+  pub testchecks: Vec<Testcheck>,
+  pub testrevisions: Vec<Testrevision>,
+  pub testenvironments: Vec<Testenvironment>,
+  pub testconfigurations: Vec<Testconfiguration>,
+  pub testplans: Vec<Testplan>,
+  pub testreports: Vec<Testreport>,
+  pub testresults: Vec<Testresult>,
+}
+
+impl ProjectArchive {
+  pub fn count_attachments(&self) -> usize {
+    self.testresults.iter().map(|testresult| testresult.attachments.len()).sum()
+  }
+}
+
+pub fn write_project_archive(archive: &ProjectArchive) -> Result<Vec<u8>, String> {
+  let mut entries: Vec<(ProjectArchiveEntry, Vec<u8>)> = vec![
+    get_documents_entry(PROJECT_PATH, std::slice::from_ref(&archive.project))?,
+    get_documents_entry(TESTLISTS_PATH, &archive.testlists)?,
+    get_documents_entry(TESTSECTIONS_PATH, &archive.testsections)?,
+    get_documents_entry(TESTCHECKS_PATH, &archive.testchecks)?,
+    get_documents_entry(TESTREVISIONS_PATH, &archive.testrevisions)?,
+    get_documents_entry(TESTENVIRONMENTS_PATH, &archive.testenvironments)?,
+    get_documents_entry(TESTCONFIGURATIONS_PATH, &archive.testconfigurations)?,
+    get_documents_entry(TESTPLANS_PATH, &archive.testplans)?,
+    get_documents_entry(TESTREPORTS_PATH, &archive.testreports)?,
+  ];
+
+  // Attachments leave the results as files, unless their data is not base64 and stays inline
+  let mut testresults: Vec<Testresult> = Vec::with_capacity(archive.testresults.len());
+  let mut attachments: Vec<(ProjectArchiveEntry, Vec<u8>)> = Vec::new();
+  for testresult in &archive.testresults {
+    let mut testresult = testresult.clone();
+    for (index, attachment) in testresult.attachments.iter_mut().enumerate() {
+      if let Ok(data) = STANDARD.decode(&attachment.data) {
+        attachments.push(get_entry(&get_attachment_path(testresult.id, index), None, data));
+        attachment.data.clear();
+      }
+    }
+    testresults.push(testresult);
+  }
+  entries.push(get_documents_entry(TESTRESULTS_PATH, &testresults)?);
+  entries.append(&mut attachments);
+
+  let manifest = ProjectArchiveManifest {
+    format: PROJECT_ARCHIVE_FORMAT.to_string(),
+    version: PROJECT_ARCHIVE_VERSION,
+    exported_at: chrono::Utc::now(),
+    project_id: archive.project.id.to_hex(),
+    project_name: archive.project.name.clone(),
+    entries: entries.iter().map(|(entry, _)| entry.clone()).collect(),
+  };
+  let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+
+  let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+  let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated).large_file(true);
+  zip.start_file(MANIFEST_PATH, options).map_err(|e| e.to_string())?;
+  zip.write_all(&manifest).map_err(|e| e.to_string())?;
+  for (entry, data) in entries {
+    zip.start_file(entry.path, options).map_err(|e| e.to_string())?;
+    zip.write_all(&data).map_err(|e| e.to_string())?;
+  }
+  Ok(zip.finish().map_err(|e| e.to_string())?.into_inner())
+}
+
+// Reads and checks a whole archive, the errors are meant for the user
+pub fn read_project_archive(bytes: &[u8]) -> Result<ProjectArchive, String> {
+  let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("Not a project archive: {}", e))?;
+  let manifest = read_entry(&mut zip, MANIFEST_PATH, MAX_ARCHIVE_CONTENT_SIZE)?;
+  let manifest: ProjectArchiveManifest = serde_json::from_slice(&manifest).map_err(|e| format!("Invalid archive manifest: {}", e))?;
+  if manifest.format != PROJECT_ARCHIVE_FORMAT {
+    return Err(format!("Not a project archive: unknown format {}", manifest.format));
+  }
+  if manifest.version != PROJECT_ARCHIVE_VERSION {
+    return Err(format!("Archive version {} is not supported, expected version {}", manifest.version, PROJECT_ARCHIVE_VERSION));
+  }
+  if manifest.entries.iter().map(|entry| entry.size).sum::<u64>() > MAX_ARCHIVE_CONTENT_SIZE {
+    return Err("Archive content is too large".to_string());
+  }
+
+  let mut entries: HashMap<String, (Option<usize>, Vec<u8>)> = HashMap::new();
+  for entry in &manifest.entries {
+    let data = read_entry(&mut zip, &entry.path, entry.size)?;
+    if data.len() as u64 != entry.size || get_sha256(&data) != entry.sha256 {
+      return Err(format!("Archive entry {} is corrupted", entry.path));
+    }
+    entries.insert(entry.path.clone(), (entry.documents, data));
+  }
+
+  let mut projects: Vec<Project> = read_documents(&entries, PROJECT_PATH)?;
+  if projects.len() != 1 {
+    return Err(format!("{} must hold a single project", PROJECT_PATH));
+  }
+  let mut archive = ProjectArchive {
+    project: projects.remove(0),
+    testlists: read_documents(&entries, TESTLISTS_PATH)?,
+    testsections: read_documents(&entries, TESTSECTIONS_PATH)?,
+    testchecks: read_documents(&entries, TESTCHECKS_PATH)?,
+    testrevisions: read_documents(&entries, TESTREVISIONS_PATH)?,
+    testenvironments: read_documents(&entries, TESTENVIRONMENTS_PATH)?,
+    testconfigurations: read_documents(&entries, TESTCONFIGURATIONS_PATH)?,
+    testplans: read_documents(&entries, TESTPLANS_PATH)?,
+    testreports: read_documents(&entries, TESTREPORTS_PATH)?,
+    testresults: read_documents(&entries, TESTRESULTS_PATH)?,
+  };
+  for testresult in &mut archive.testresults {
+    for (index, attachment) in testresult.attachments.iter_mut().enumerate() {
+      if let Some((None, data)) = entries.get(&get_attachment_path(testresult.id, index)) {
+        attachment.data = STANDARD.encode(data);
+      }
+    }
+  }
+  check_project_archive(&archive)?;
+  Ok(archive)
+}
+
+// New ids for every document, the references within the archive follow them. References to
+// documents missing from the archive, e.g. the checks deleted after a report, get new ids too.
+// Users only exist on the source instance: assignees, owners and authors are kept when they are
+// members of the target account, executors and sign-offs are history and kept as they are.
+pub fn remap_project_archive(archive: ProjectArchive, account_id: ObjectId, members: &[ObjectId]) -> ProjectArchive {
+  let mut ids: HashMap<ObjectId, ObjectId> = HashMap::new();
+  // The default ObjectId is a new one
+  let mut id = |id: ObjectId| *ids.entry(id).or_default();
+  let member = |user_id: Option<ObjectId>| user_id.filter(|user_id| members.contains(user_id));
+
+  let mut project = archive.project;
+  project.id = id(project.id);
+  project.account_id = account_id;
+  let project_id = project.id;
+
+  let testlists = archive.testlists.into_iter().map(|mut testlist| {
+    testlist.id = id(testlist.id);
+    testlist.account_id = account_id;
+    testlist.project_id = project_id;
+    testlist
+  }).collect();
+  let testsections = archive.testsections.into_iter().map(|mut testsection| {
+    testsection.id = id(testsection.id);
+    testsection.account_id = account_id;
+    testsection.project_id = project_id;
+    testsection.testlist_id = id(testsection.testlist_id);
+    testsection.parent_id = testsection.parent_id.map(&mut id);
+    testsection
+  }).collect();
+  let testchecks = archive.testchecks.into_iter().map(|mut testcheck| {
+    testcheck.id = id(testcheck.id);
+    testcheck.account_id = account_id;
+    testcheck.project_id = project_id;
+    testcheck.testlist_id = id(testcheck.testlist_id);
+    testcheck.section_id = testcheck.section_id.map(&mut id);
+    testcheck
+  }).collect();
+  let testrevisions = archive.testrevisions.into_iter().map(|mut testrevision| {
+    testrevision.id = id(testrevision.id);
+    testrevision.account_id = account_id;
+    testrevision.testcheck_id = id(testrevision.testcheck_id);
+    testrevision.author_id = member(testrevision.author_id);
+    testrevision
+  }).collect();
+  let testenvironments = archive.testenvironments.into_iter().map(|mut testenvironment| {
+    testenvironment.id = id(testenvironment.id);
+    testenvironment.account_id = account_id;
+    testenvironment.project_id = project_id;
+    testenvironment
+  }).collect();
+  let testconfigurations = archive.testconfigurations.into_iter().map(|mut testconfiguration| {
+    testconfiguration.id = id(testconfiguration.id);
+    testconfiguration.account_id = account_id;
+    testconfiguration.project_id = project_id;
+    testconfiguration.testenvironment_id = testconfiguration.testenvironment_id.map(&mut id);
+    testconfiguration
+  }).collect();
+  let testplans = archive.testplans.into_iter().map(|mut testplan| {
+    testplan.id = id(testplan.id);
+    testplan.account_id = account_id;
+    testplan.project_id = project_id;
+    testplan.owner_id = member(testplan.owner_id);
+    testplan.testreport_ids = testplan.testreport_ids.into_iter().map(&mut id).collect();
+    testplan
+  }).collect();
+  let testreports = archive.testreports.into_iter().map(|mut testreport| {
+    testreport.id = id(testreport.id);
+    testreport.account_id = account_id;
+    testreport.project_id = project_id;
+    testreport.testlist_id = id(testreport.testlist_id);
+    testreport.parent_id = testreport.parent_id.map(&mut id);
+    for section in &mut testreport.sections {
+      section.testsection_id = id(section.testsection_id);
+      section.parent_id = section.parent_id.map(&mut id);
+    }
+    if let Some(testconfiguration) = &mut testreport.testconfiguration {
+      testconfiguration.testconfiguration_id = id(testconfiguration.testconfiguration_id);
+    }
+    testreport
+  }).collect();
+  let testresults = archive.testresults.into_iter().map(|mut testresult| {
+    testresult.id = id(testresult.id);
+    testresult.account_id = account_id;
+    testresult.testreport_id = id(testresult.testreport_id);
+    testresult.testcheck_id = id(testresult.testcheck_id);
+    testresult.section_id = testresult.section_id.map(&mut id);
+    testresult.assignee_id = member(testresult.assignee_id);
+    testresult
+  }).collect();
+
+  ProjectArchive { project, testlists, testsections, testchecks, testrevisions, testenvironments, testconfigurations, testplans, testreports, testresults }
+}
+
+// Users referenced by the documents of the archive
+pub fn get_project_archive_user_ids(archive: &ProjectArchive) -> Vec<ObjectId> {
+  let mut user_ids: Vec<ObjectId> = Vec::new();
+  user_ids.extend(archive.testrevisions.iter().filter_map(|testrevision| testrevision.author_id));
+  user_ids.extend(archive.testplans.iter().filter_map(|testplan| testplan.owner_id));
+  user_ids.extend(archive.testresults.iter().filter_map(|testresult| testresult.assignee_id));
+  user_ids.sort();
+  user_ids.dedup();
+  user_ids
+}
+
+fn get_attachment_path(testresult_id: ObjectId, index: usize) -> String {
+  format!("attachments/{}/{}", testresult_id.to_hex(), index)
+}
+
+fn get_sha256(data: &[u8]) -> String {
+  Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn get_entry(path: &str, documents: Option<usize>, data: Vec<u8>) -> (ProjectArchiveEntry, Vec<u8>) {
+  let entry = ProjectArchiveEntry {
+    path: path.to_string(),
+    documents,
+    size: data.len() as u64,
+    sha256: get_sha256(&data),
+  };
+  (entry, data)
+}
+
+// One relaxed Extended JSON document per line, as mongoexport writes them
+fn get_documents_entry<T: Serialize>(path: &str, documents: &[T]) -> Result<(ProjectArchiveEntry, Vec<u8>), String> {
+  let mut data: Vec<u8> = Vec::new();
+  for document in documents {
+    // Not human readable, as the driver stores it, the ids and dates stay typed
+    let options = SerializerOptions::builder().human_readable(false).build();
+    let document = bson::to_bson_with_options(document, options).map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::to_writer(&mut data, &document.into_relaxed_extjson()).map_err(|e| format!("{}: {}", path, e))?;
+    data.push(b'\n');
+  }
+  Ok(get_entry(path, Some(documents.len()), data))
+}
+
+fn read_entry(zip: &mut ZipArchive<Cursor<&[u8]>>, path: &str, max_size: u64) -> Result<Vec<u8>, String> {
+  let file = zip.by_name(path).map_err(|_| format!("Archive entry {} is missing", path))?;
+  if file.size() > max_size {
+    return Err(format!("Archive entry {} is corrupted", path));
+  }
+  let mut data: Vec<u8> = Vec::new();
+  file.take(max_size).read_to_end(&mut data).map_err(|e| format!("Archive entry {} can not be read: {}", path, e))?;
+  Ok(data)
+}
+
+// A document that does not match the current schema fails the whole import
+fn read_documents<T: DeserializeOwned>(entries: &HashMap<String, (Option<usize>, Vec<u8>)>, path: &str) -> Result<Vec<T>, String> {
+  let (count, data) = match entries.get(path) {
+    Some((Some(count), data)) => (*count, data),
+    _ => return Err(format!("Archive entry {} is missing from the manifest", path)),
+  };
+  let data = std::str::from_utf8(data).map_err(|_| format!("{} is not UTF-8 text", path))?;
+  let mut documents: Vec<T> = Vec::new();
+  for (index, line) in data.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+    let document = serde_json::from_str::<serde_json::Value>(line)
+      .map_err(|e| e.to_string())
+      .and_then(|value| Bson::try_from(value).map_err(|e| e.to_string()))
+      .and_then(|document| bson::from_bson::<T>(document).map_err(|e| e.to_string()))
+      .map_err(|e| format!("{} line {}: {}", path, index + 1, e))?;
+    documents.push(document);
+  }
+  if documents.len() != count {
+    return Err(format!("{} holds {} documents, the manifest lists {}", path, documents.len(), count));
+  }
+  Ok(documents)
+}
+
+// Every document belongs to the archived project and its parent is archived too. Reports outlive
+// their testlist, remapping gives the testlist of an orphaned report a new id.
+fn check_project_archive(archive: &ProjectArchive) -> Result<(), String> {
+  let project_id = archive.project.id;
+  let mut ids: HashSet<ObjectId> = HashSet::from([project_id]);
+  let all_ids = [
+    archive.testlists.iter().map(|testlist| testlist.id).collect::<Vec<ObjectId>>(),
+    archive.testsections.iter().map(|testsection| testsection.id).collect(),
+    archive.testchecks.iter().map(|testcheck| testcheck.id).collect(),
+    archive.testrevisions.iter().map(|testrevision| testrevision.id).collect(),
+    archive.testenvironments.iter().map(|testenvironment| testenvironment.id).collect(),
+    archive.testconfigurations.iter().map(|testconfiguration| testconfiguration.id).collect(),
+    archive.testplans.iter().map(|testplan| testplan.id).collect(),
+    archive.testreports.iter().map(|testreport| testreport.id).collect(),
+    archive.testresults.iter().map(|testresult| testresult.id).collect(),
+  ];
+  for id in all_ids.into_iter().flatten() {
+    if !ids.insert(id) {
+      return Err(format!("Document {} is archived twice", id.to_hex()));
+    }
+  }
+
+  let project_ids = archive.testlists.iter().map(|testlist| (TESTLISTS_PATH, testlist.id, testlist.project_id))
+    .chain(archive.testsections.iter().map(|testsection| (TESTSECTIONS_PATH, testsection.id, testsection.project_id)))
+    .chain(archive.testchecks.iter().map(|testcheck| (TESTCHECKS_PATH, testcheck.id, testcheck.project_id)))
+    .chain(archive.testenvironments.iter().map(|testenvironment| (TESTENVIRONMENTS_PATH, testenvironment.id, testenvironment.project_id)))
+    .chain(archive.testconfigurations.iter().map(|testconfiguration| (TESTCONFIGURATIONS_PATH, testconfiguration.id, testconfiguration.project_id)))
+    .chain(archive.testplans.iter().map(|testplan| (TESTPLANS_PATH, testplan.id, testplan.project_id)))
+    .chain(archive.testreports.iter().map(|testreport| (TESTREPORTS_PATH, testreport.id, testreport.project_id)));
+  for (path, id, document_project_id) in project_ids {
+    if document_project_id != project_id {
+      return Err(format!("{}: document {} belongs to another project", path, id.to_hex()));
+    }
+  }
+
+  let testlist_ids: HashSet<ObjectId> = archive.testlists.iter().map(|testlist| testlist.id).collect();
+  let testcheck_ids: HashSet<ObjectId> = archive.testchecks.iter().map(|testcheck| testcheck.id).collect();
+  let testreport_ids: HashSet<ObjectId> = archive.testreports.iter().map(|testreport| testreport.id).collect();
+  let parents = archive.testsections.iter().map(|testsection| (TESTSECTIONS_PATH, testsection.id, testlist_ids.contains(&testsection.testlist_id)))
+    .chain(archive.testchecks.iter().map(|testcheck| (TESTCHECKS_PATH, testcheck.id, testlist_ids.contains(&testcheck.testlist_id))))
+    .chain(archive.testrevisions.iter().map(|testrevision| (TESTREVISIONS_PATH, testrevision.id, testcheck_ids.contains(&testrevision.testcheck_id))))
+    .chain(archive.testresults.iter().map(|testresult| (TESTRESULTS_PATH, testresult.id, testreport_ids.contains(&testresult.testreport_id))));
+  for (path, id, archived) in parents {
+    if !archived {
+      return Err(format!("{}: the parent of document {} is not archived", path, id.to_hex()));
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use bson::DateTime;
+
+  use super::*;
+  use crate::{testreports::schema::TestreportStatus, testresults::schema::TestresultAttachment};
+
+  fn archive() -> ProjectArchive {
+    let now = DateTime::now();
+    let account_id = ObjectId::new();
+    let project = Project {
+      id: ObjectId::new(),
+      name: "Shop".to_string(),
+      version: "2.1".to_string(),
+      description: String::new(),
+      repository: String::new(),
+      signoff: Default::default(),
+      flaky: Default::default(),
+      quality_gate: Vec::new(),
+      account_id,
+      created_at: now,
+      updated_at: now,
+    };
+    let testlist = Testlist {
+      id: ObjectId::new(),
+      account_id,
+      project_id: project.id,
+      name: "Regression".to_string(),
+      description: String::new(),
+      created_at: now,
+      updated_at: now,
+    };
+    let testreport = |testlist_id: ObjectId, name: &str| Testreport {
+      id: ObjectId::new(),
+      account_id,
+      project_id: project.id,
+      testlist_id,
+      parent_id: None,
+      name: name.to_string(),
+      description: String::new(),
+      execution: String::new(),
+      tags_filter: None,
+      status: TestreportStatus::Completed,
+      signoff: None,
+      history: Vec::new(),
+      executors: None,
+      sections: Vec::new(),
+      testconfiguration: None,
+      build: None,
+      created_at: now,
+      updated_at: now,
+    };
+    // The testlist of the second report was deleted since
+    let testreports = vec![testreport(testlist.id, "Release 2.1"), testreport(ObjectId::new(), "Release 2.0")];
+    let testresults = testreports.iter().map(|testreport| Testresult {
+      id: ObjectId::new(),
+      account_id,
+      testreport_id: testreport.id,
+      testcheck_id: ObjectId::new(),
+      section_id: None,
+      testcheck_revision: 1,
+      name: "Checkout".to_string(),
+      description: String::new(),
+      expected: String::new(),
+      tags: Vec::new(),
+      position: 0,
+      updated: true,
+      pass: false,
+      blocked: false,
+      flacky: false,
+      automated: true,
+      notes: String::new(),
+      url_issue: String::new(),
+      url_result: String::new(),
+      executors: Vec::new(),
+      assignee_id: None,
+      steps: Vec::new(),
+      retries: 0,
+      duration_ms: None,
+      attachments: vec![TestresultAttachment { name: "screenshot".to_string(), content_type: "image/png".to_string(), data: STANDARD.encode(b"png") }],
+      created_at: now,
+      updated_at: now,
+    }).collect();
+    ProjectArchive {
+      project,
+      testlists: vec![testlist],
+      testsections: Vec::new(),
+      testchecks: Vec::new(),
+      testrevisions: Vec::new(),
+      testenvironments: Vec::new(),
+      testconfigurations: Vec::new(),
+      testplans: Vec::new(),
+      testreports,
+      testresults,
+    }
+  }
+
+  // Copy of the zip with the content of an entry replaced, its manifest left untouched
+  fn replace_entry(bytes: &[u8], path: &str, data: &[u8]) -> Vec<u8> {
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for index in 0..zip.len() {
+      let mut file = zip.by_index(index).unwrap();
+      let name = file.name().to_string();
+      let mut content = Vec::new();
+      file.read_to_end(&mut content).unwrap();
+      writer.start_file(name.as_str(), SimpleFileOptions::default()).unwrap();
+      writer.write_all(if name == path { data } else { &content }).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+  }
+
+  #[test]
+  fn round_trip_keeps_orphaned_reports() {
+    let source = archive();
+    let bytes = write_project_archive(&source).unwrap();
+    let read = read_project_archive(&bytes).unwrap();
+    assert_eq!(read.testreports.len(), 2);
+    assert_eq!(read.testresults[1].attachments[0].data, source.testresults[1].attachments[0].data);
+    assert_eq!(read.testreports[1].created_at, source.testreports[1].created_at);
+
+    let account_id = ObjectId::new();
+    let remapped = remap_project_archive(read, account_id, &[]);
+    assert_ne!(remapped.project.id, source.project.id);
+    assert_eq!(remapped.testreports[0].testlist_id, remapped.testlists[0].id);
+    // The missing testlist gets a new id too, unrelated to the archived ones
+    assert_ne!(remapped.testreports[1].testlist_id, source.testreports[1].testlist_id);
+    assert_ne!(remapped.testreports[1].testlist_id, remapped.testlists[0].id);
+    for (testresult, testreport) in remapped.testresults.iter().zip(&remapped.testreports) {
+      assert_eq!(testresult.testreport_id, testreport.id);
+      assert_eq!(testresult.account_id, account_id);
+    }
+  }
+
+  #[test]
+  fn rejects_corrupted_entries() {
+    let bytes = write_project_archive(&archive()).unwrap();
+    let corrupted = replace_entry(&bytes, TESTREPORTS_PATH, b"{}\n");
+    let error = read_project_archive(&corrupted).err().unwrap();
+    assert_eq!(error, format!("Archive entry {} is corrupted", TESTREPORTS_PATH));
+  }
+
+  #[test]
+  fn rejects_results_without_their_report() {
+    let mut source = archive();
+    source.testreports.remove(1);
+    let bytes = write_project_archive(&source).unwrap();
+    assert!(read_project_archive(&bytes).err().unwrap().contains("is not archived"));
+  }
+}
@@ -1,8 +1,9 @@
+use bson::oid::ObjectId;
 use log::{error, warn};
-use rocket::{delete, get, post, put, routes, serde::json::Json, State};
-use crate::{projects::schema::ProjectDto, service::{db::MongoRepo, http_errors::JsonError}, sessions::{guards::authorize_as_admin, jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testlists::schema::{Testlist, TestlistDto}, testreports::schema::Testreport, users::{roles::is_admin, schema::User}};
+use rocket::{delete, get, http::ContentType, post, put, routes, serde::json::Json, State};
+use crate::{apitokens::{endpoints::get_apitoken_by_id, schema::{Apitoken, ApitokenCreatedRes, ApitokenDto, ApitokenRes}}, projects::schema::ProjectDto, testchecks::{endpoints::apply_testchecks_import, gherkin::GherkinFeature, schema::{GherkinImportDto, GherkinImportResult, GherkinImportTestlist, Testcheck, TestcheckImportAction}, service::plan_testchecks_import}, testrevisions::schema::Testrevision, testsections::schema::Testsection, testconfigurations::{endpoints::get_testconfiguration_by_id, schema::{expand_testconfiguration_matrix, Testconfiguration, TestconfigurationDto, TestconfigurationMatrixDto, MAX_MATRIX_CONFIGURATIONS}}, testenvironments::{endpoints::{get_project_testenvironment_id, get_testenvironment_by_id}, schema::{Testenvironment, TestenvironmentDto}}, testresults::{schema::{TestcheckFlakiness, Testresult}, service::get_testchecks_flakiness}, service::{db::MongoRepo, download::Download, http_errors::JsonError}, sessions::{guards::authorize_as_admin, jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testlists::schema::{Testlist, TestlistDto}, testplans::{endpoints::{get_testplan_by_id, get_testplan_owner_id, get_testplan_target_date}, schema::{Testplan, TestplanDto}}, testreports::{schema::{Testreport, TestreportBuildFilter, TestreportMatrix}, service::{check_commit_sha, get_testreport_matrix}}, users::{roles::is_admin, schema::User}};
 
-use super::schema::Project;
+use super::{archive::{write_project_archive, ProjectArchive}, schema::Project, service::check_project_flaky_policy};
 
 #[get("/")]
 async fn get_projects(jwt: Result<JWT, JsonError>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, project_repo: &State<MongoRepo<Project>>) -> Result<Json<Vec<Project>>, JsonError> {
@@ -31,6 +32,9 @@ async fn get_project(jwt: Result<JWT, JsonError>, id: &str, project_repo: &State
 async fn update_project(jwt: Result<JWT, JsonError>, id: &str, data: Json<ProjectDto>, project_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Project>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
   allowed_for_project(jwts, project_repo, id).await?;
+  if let Some(flaky) = &data.flaky {
+    check_project_flaky_policy(flaky).map_err(JsonError::BadRequest)?;
+  }
 
   match project_repo.update(id.to_string(), data.into_inner()).await {
     Ok(updated) => {
@@ -95,12 +99,18 @@ pub async fn get_project_testlists(jwt: Result<JWT, JsonError>, project_id: &str
   }
 }
 
-#[get("/<project_id>/testreports")]
-pub async fn get_project_testreports(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>) -> Result<Json<Vec<Testreport>>, JsonError> {
+// Reports produced by matching CI builds, latest first, every report of the project without filter
+#[get("/<project_id>/testreports?<commit_sha>&<branch>&<tag>&<build_number>&<actor>&<environment>")]
+pub async fn get_project_testreports(jwt: Result<JWT, JsonError>, project_id: &str, commit_sha: Option<&str>, branch: Option<&str>, tag: Option<&str>, build_number: Option<&str>, actor: Option<&str>, environment: Option<&str>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>) -> Result<Json<Vec<Testreport>>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
   allowed_for_project(jwts, projects_repo, project_id).await?;
+  let build_filter = get_build_filter(commit_sha, branch, tag, build_number, actor, environment)?;
 
-  match testreport_repo.get_project_testreports(project_id).await {
+  let testreports = match build_filter {
+    Some(build_filter) => testreport_repo.get_project_build_testreports(project_id, &build_filter, None).await,
+    None => testreport_repo.get_project_testreports(project_id).await,
+  };
+  match testreports {
     Ok(testreports) => Ok(Json(testreports)),
     Err(e) => {
       error!("Error getting testreports: {}", e);
@@ -109,6 +119,28 @@ pub async fn get_project_testreports(jwt: Result<JWT, JsonError>, project_id: &s
   }
 }
 
+// Latest report produced by a matching CI build, e.g. the latest report of branch main
+#[get("/<project_id>/testreports/latest?<commit_sha>&<branch>&<tag>&<build_number>&<actor>&<environment>")]
+pub async fn get_project_latest_testreport(jwt: Result<JWT, JsonError>, project_id: &str, commit_sha: Option<&str>, branch: Option<&str>, tag: Option<&str>, build_number: Option<&str>, actor: Option<&str>, environment: Option<&str>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>) -> Result<Json<Testreport>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+  let build_filter = get_build_filter(commit_sha, branch, tag, build_number, actor, environment)?.unwrap_or_default();
+
+  match testreport_repo.get_project_build_testreports(project_id, &build_filter, Some(1)).await {
+    Ok(testreports) => match testreports.into_iter().next() {
+      Some(testreport) => Ok(Json(testreport)),
+      None => {
+        warn!("Testreport not found for project {} build {:?}", project_id, build_filter);
+        Err(JsonError::NotFound("Testreport not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testreports: {}", e);
+      Err(JsonError::Internal("Error getting testreport".to_string()))
+    },
+  }
+}
+
 #[post("/<project_id>/testlists", format = "json", data = "<testlist>")]
 pub async fn create_project_testlist(jwt: Result<JWT, JsonError>, project_id: &str, testlist: Json<TestlistDto>, projects_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>) -> Result<Json<Testlist>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
@@ -141,13 +173,476 @@ pub async fn create_project_testlist(jwt: Result<JWT, JsonError>, project_id: &s
   }
 }
 
+#[get("/<project_id>/testplans")]
+pub async fn get_project_testplans(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testplan_repo: &State<MongoRepo<Testplan>>) -> Result<Json<Vec<Testplan>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match testplan_repo.get_project_testplans(project_id).await {
+    Ok(testplans) => Ok(Json(testplans)),
+    Err(e) => {
+      error!("Error getting testplans: {}", e);
+      Err(JsonError::Internal("Error getting testplans".to_string()))
+    },
+  }
+}
+
+#[get("/<project_id>/testplans/current")]
+pub async fn get_project_current_testplan(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testplan_repo: &State<MongoRepo<Testplan>>) -> Result<Json<Testplan>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match testplan_repo.get_project_version_testplan(&project).await {
+    Ok(testplan) => match testplan {
+      Some(testplan) => Ok(Json(testplan)),
+      None => {
+        warn!("Testplan not found for project version: {} {}", project_id, project.version);
+        Err(JsonError::NotFound("Testplan not found for project version".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testplan: {}", e);
+      Err(JsonError::Internal("Error getting testplan".to_string()))
+    },
+  }
+}
+
+#[post("/<project_id>/testplans", format = "json", data = "<testplan>")]
+pub async fn create_project_testplan(jwt: Result<JWT, JsonError>, project_id: &str, testplan: Json<TestplanDto>, projects_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testplan_repo: &State<MongoRepo<Testplan>>) -> Result<Json<Testplan>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+  let data = testplan.into_inner();
+  let owner_id = get_testplan_owner_id(users_repo, project.account_id, data.owner_id.as_deref()).await?;
+  let target_date = get_testplan_target_date(data.target_date.as_deref())?;
+
+  match testplan_repo.create_testplan(&project, owner_id, target_date, data).await {
+    Ok(inserted) => {
+      let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
+      get_testplan_by_id(testplan_repo, &id).await.map(Json)
+    },
+    Err(e) => {
+      error!("Error creating testplan: {}", e);
+      Err(JsonError::Internal("Error creating testplan".to_string()))
+    },
+  }
+}
+
+#[get("/<project_id>/testreports/matrix?<testlist_id>")]
+pub async fn get_project_testreports_matrix(jwt: Result<JWT, JsonError>, project_id: &str, testlist_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testlist_repo: &State<MongoRepo<Testlist>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<TestreportMatrix>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match testlist_repo.get_testlist_by_id(testlist_id).await {
+    Ok(Some(testlist)) if testlist.project_id == project.id => (),
+    Ok(_) => return Err(JsonError::NotFound("Testlist not found".to_string())),
+    Err(e) => {
+      error!("Error getting testlist: {}", e);
+      return Err(JsonError::Internal("Error getting testlist".to_string()));
+    },
+  }
+
+  let testreports = match testreport_repo.get_testlist_configured_testreports(testlist_id).await {
+    Ok(testreports) => testreports,
+    Err(e) => {
+      error!("Error getting testreports: {}", e);
+      return Err(JsonError::Internal("Error getting testreports".to_string()));
+    },
+  };
+
+  let mut seen = vec![];
+  let mut reports = vec![];
+  for testreport in testreports {
+    let testconfiguration_id = testreport.testconfiguration.as_ref().map(|testconfiguration| testconfiguration.testconfiguration_id);
+    if seen.contains(&testconfiguration_id) {
+      continue;
+    }
+    seen.push(testconfiguration_id);
+    match testresult_repo.get_testreport_testresults(&testreport.id.to_hex()).await {
+      Ok(testresults) => reports.push((testreport, testresults)),
+      Err(e) => {
+        error!("Error getting testresults: {}", e);
+        return Err(JsonError::Internal("Error getting testresults".to_string()));
+      },
+    }
+  }
+  Ok(Json(get_testreport_matrix(reports)))
+}
+
+// Checks whose outcome flipped at least once over the policy window, the flakiest first
+#[get("/<project_id>/flaky")]
+pub async fn get_project_flaky_testchecks(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<Vec<TestcheckFlakiness>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  let testreport_ids: Vec<ObjectId> = match testreport_repo.get_project_testreports(project_id).await {
+    Ok(testreports) => testreports.iter().map(|testreport| testreport.id).collect(),
+    Err(e) => {
+      error!("Error getting testreports: {}", e);
+      return Err(JsonError::Internal("Error getting testreports".to_string()));
+    },
+  };
+
+  match testresult_repo.get_testreports_executed_testresults(&testreport_ids).await {
+    Ok(testresults) => {
+      let mut flakiness = get_testchecks_flakiness(&testresults, &project.flaky);
+      flakiness.retain(|testcheck| testcheck.flips > 0);
+      Ok(Json(flakiness))
+    },
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+#[get("/<project_id>/testenvironments")]
+pub async fn get_project_testenvironments(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>) -> Result<Json<Vec<Testenvironment>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match testenvironment_repo.get_project_testenvironments(project_id).await {
+    Ok(testenvironments) => Ok(Json(testenvironments)),
+    Err(e) => {
+      error!("Error getting testenvironments: {}", e);
+      Err(JsonError::Internal("Error getting testenvironments".to_string()))
+    },
+  }
+}
+
+#[post("/<project_id>/testenvironments", format = "json", data = "<testenvironment>")]
+pub async fn create_project_testenvironment(jwt: Result<JWT, JsonError>, project_id: &str, testenvironment: Json<TestenvironmentDto>, projects_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>) -> Result<Json<Testenvironment>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match testenvironment_repo.create_testenvironment(&project, testenvironment.into_inner()).await {
+    Ok(inserted) => {
+      let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
+      get_testenvironment_by_id(testenvironment_repo, &id).await.map(Json)
+    },
+    Err(e) => {
+      error!("Error creating testenvironment: {}", e);
+      Err(JsonError::Internal("Error creating testenvironment".to_string()))
+    },
+  }
+}
+
+#[get("/<project_id>/testconfigurations")]
+pub async fn get_project_testconfigurations(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>) -> Result<Json<Vec<Testconfiguration>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match testconfiguration_repo.get_project_testconfigurations(project_id).await {
+    Ok(testconfigurations) => Ok(Json(testconfigurations)),
+    Err(e) => {
+      error!("Error getting testconfigurations: {}", e);
+      Err(JsonError::Internal("Error getting testconfigurations".to_string()))
+    },
+  }
+}
+
+#[post("/<project_id>/testconfigurations", format = "json", data = "<testconfiguration>")]
+pub async fn create_project_testconfiguration(jwt: Result<JWT, JsonError>, project_id: &str, testconfiguration: Json<TestconfigurationDto>, projects_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>) -> Result<Json<Testconfiguration>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+  let data = testconfiguration.into_inner();
+  let testenvironment_id = get_project_testenvironment_id(testenvironment_repo, project.id, data.testenvironment_id.as_deref()).await?;
+
+  match testconfiguration_repo.create_testconfiguration(&project, testenvironment_id, data.name, data.parameters).await {
+    Ok(inserted) => {
+      let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
+      get_testconfiguration_by_id(testconfiguration_repo, &id).await.map(Json)
+    },
+    Err(e) => {
+      error!("Error creating testconfiguration: {}", e);
+      Err(JsonError::Internal("Error creating testconfiguration".to_string()))
+    },
+  }
+}
+
+#[post("/<project_id>/testconfigurations/matrix", format = "json", data = "<matrix>")]
+pub async fn create_project_testconfigurations_matrix(jwt: Result<JWT, JsonError>, project_id: &str, matrix: Json<TestconfigurationMatrixDto>, projects_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>) -> Result<Json<Vec<Testconfiguration>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+  let data = matrix.into_inner();
+  let testenvironment_id = get_project_testenvironment_id(testenvironment_repo, project.id, data.testenvironment_id.as_deref()).await?;
+
+  if data.dimensions.is_empty() || data.dimensions.iter().any(|dimension| dimension.values.is_empty()) {
+    return Err(JsonError::BadRequest("Every matrix dimension needs at least one value".to_string()));
+  }
+  let size = data.dimensions.iter().fold(1usize, |size, dimension| size.saturating_mul(dimension.values.len()));
+  if size > MAX_MATRIX_CONFIGURATIONS {
+    return Err(JsonError::BadRequest(format!("The matrix would generate more than {} testconfigurations", MAX_MATRIX_CONFIGURATIONS)));
+  }
+
+  let mut testconfigurations = vec![];
+  for parameters in expand_testconfiguration_matrix(&data.dimensions) {
+    let name = parameters.iter().map(|parameter| parameter.value.as_str()).collect::<Vec<&str>>().join(" / ");
+    match testconfiguration_repo.create_testconfiguration(&project, testenvironment_id, name, parameters).await {
+      Ok(inserted) => {
+        let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
+        testconfigurations.push(get_testconfiguration_by_id(testconfiguration_repo, &id).await?);
+      },
+      Err(e) => {
+        error!("Error creating testconfiguration: {}", e);
+        return Err(JsonError::Internal("Error creating testconfiguration".to_string()));
+      },
+    }
+  }
+  Ok(Json(testconfigurations))
+}
+
+#[get("/<project_id>/apitokens")]
+pub async fn get_project_apitokens(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, apitoken_repo: &State<MongoRepo<Apitoken>>) -> Result<Json<Vec<ApitokenRes>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match apitoken_repo.get_project_apitokens(project_id).await {
+    Ok(apitokens) => Ok(Json(apitokens.into_iter().map(ApitokenRes::from).collect())),
+    Err(e) => {
+      error!("Error getting apitokens: {}", e);
+      Err(JsonError::Internal("Error getting apitokens".to_string()))
+    },
+  }
+}
+
+#[post("/<project_id>/apitokens", format = "json", data = "<apitoken>")]
+pub async fn create_project_apitoken(jwt: Result<JWT, JsonError>, project_id: &str, apitoken: Json<ApitokenDto>, projects_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, apitoken_repo: &State<MongoRepo<Apitoken>>) -> Result<Json<ApitokenCreatedRes>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let user_id = jwts.user.id;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  match apitoken_repo.create_apitoken(&project, user_id, apitoken.into_inner()).await {
+    Ok((inserted, token)) => {
+      let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
+      let apitoken = get_apitoken_by_id(apitoken_repo, &id).await?;
+      Ok(Json(ApitokenCreatedRes { apitoken: apitoken.into(), token }))
+    },
+    Err(e) => {
+      error!("Error creating apitoken: {}", e);
+      Err(JsonError::Internal("Error creating apitoken".to_string()))
+    },
+  }
+}
+
+// Target testlist, or the name and description of the one to create, with its features
+type GherkinImportTarget = (Option<Testlist>, TestlistDto, Vec<(String, GherkinFeature)>);
+
+// Imports Cucumber feature files, scenarios already imported are matched by feature and scenario name
+#[post("/<project_id>/gherkin", format = "json", data = "<data>")]
+pub async fn import_project_gherkin(jwt: Result<JWT, JsonError>, project_id: &str, data: Json<GherkinImportDto>, projects_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>) -> Result<Json<GherkinImportResult>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let user_id = jwts.user.id;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+  let data = data.into_inner();
+
+  let mut features = Vec::new();
+  for file in &data.files {
+    match GherkinFeature::parse(&file.content) {
+      Ok(feature) => features.push((file.filename.clone(), feature)),
+      Err(e) => return Err(JsonError::BadRequest(format!("{}: {}", file.filename, e))),
+    }
+  }
+  if features.is_empty() {
+    return Err(JsonError::BadRequest("No feature file to import".to_string()));
+  }
+
+  let testlists = match testlist_repo.get_project_testlists(project_id).await {
+    Ok(testlists) => testlists,
+    Err(e) => {
+      error!("Error getting testlists: {}", e);
+      return Err(JsonError::Internal("Error getting testlists".to_string()));
+    },
+  };
+  let mut targets: Vec<GherkinImportTarget> = Vec::new();
+  if data.testlist_per_feature {
+    for (filename, feature) in features {
+      match targets.iter_mut().find(|(_, dto, _)| dto.name == feature.name) {
+        Some((_, _, target_features)) => target_features.push((filename, feature)),
+        None => {
+          let testlist = testlists.iter().find(|testlist| testlist.name == feature.name).cloned();
+          let dto = TestlistDto { name: feature.name.clone(), description: feature.description.clone() };
+          targets.push((testlist, dto, vec![(filename, feature)]));
+        },
+      }
+    }
+  } else {
+    let testlist = match data.testlist_id.as_deref().and_then(|id| testlists.iter().find(|testlist| testlist.id.to_hex() == id)) {
+      Some(testlist) => testlist.clone(),
+      None => return Err(JsonError::BadRequest("A testlist of the project is required".to_string())),
+    };
+    let dto = TestlistDto { name: testlist.name.clone(), description: testlist.description.clone() };
+    targets.push((Some(testlist), dto, features));
+  }
+
+  let mut plans = Vec::new();
+  for (testlist, dto, target_features) in targets {
+    let testchecks = match &testlist {
+      Some(testlist) => match testcheck_repo.get_testlist_testchecks(&testlist.id.to_hex()).await {
+        Ok(testchecks) => testchecks,
+        Err(e) => {
+          error!("Error getting testchecks: {}", e);
+          return Err(JsonError::Internal("Error getting testchecks".to_string()));
+        },
+      },
+      None => Vec::new(),
+    };
+    let imports = target_features.iter()
+      .flat_map(|(_, feature)| feature.testchecks())
+      .map(|imported| (imported.line, imported.testcheck))
+      .collect();
+    let filenames = target_features.into_iter().map(|(filename, _)| filename).collect();
+    plans.push((testlist, dto, filenames, plan_testchecks_import(imports, &testchecks)));
+  }
+
+  let count = |action: TestcheckImportAction| plans.iter()
+    .flat_map(|(_, _, _, plan)| plan.iter())
+    .filter(|(row, _, _)| row.action == action)
+    .count() as u32;
+  let invalid = count(TestcheckImportAction::Invalid);
+  let mut result = GherkinImportResult {
+    dry_run: data.dry_run,
+    imported: !data.dry_run && invalid == 0,
+    created: count(TestcheckImportAction::Create),
+    updated: count(TestcheckImportAction::Update),
+    unchanged: count(TestcheckImportAction::Unchanged),
+    invalid,
+    testlists: Vec::new(),
+  };
+
+  for (testlist, dto, filenames, plan) in plans {
+    let created = testlist.is_none();
+    if !result.imported {
+      result.testlists.push(GherkinImportTestlist {
+        testlist_id: testlist.map(|testlist| testlist.id),
+        name: dto.name,
+        filenames,
+        created,
+        rows: plan.into_iter().map(|(row, _, _)| row).collect(),
+      });
+      continue;
+    }
+    let testlist = match testlist {
+      Some(testlist) => testlist,
+      None => create_gherkin_testlist(testlist_repo, &project, dto).await?,
+    };
+    let rows = apply_testchecks_import(testcheck_repo, testrevision_repo, &testlist, plan, user_id).await?;
+    result.testlists.push(GherkinImportTestlist {
+      testlist_id: Some(testlist.id),
+      name: testlist.name,
+      filenames,
+      created,
+      rows,
+    });
+  }
+  Ok(Json(result))
+}
+
+async fn create_gherkin_testlist(testlist_repo: &State<MongoRepo<Testlist>>, project: &Project, data: TestlistDto) -> Result<Testlist, JsonError> {
+  let id = match testlist_repo.create_testlist(&project.account_id.to_hex(), &project.id.to_hex(), data).await {
+    Ok(inserted) => inserted.inserted_id.as_object_id().unwrap().to_hex(),
+    Err(e) => {
+      error!("Error creating testlist: {}", e);
+      return Err(JsonError::Internal("Error creating testlist".to_string()));
+    },
+  };
+  match testlist_repo.get_testlist_by_id(&id).await {
+    Ok(Some(testlist)) => Ok(testlist),
+    Ok(None) => {
+      warn!("Testlist not found: {}", id);
+      Err(JsonError::NotFound("Testlist not found".to_string()))
+    },
+    Err(e) => {
+      error!("Error getting testlist: {}", e);
+      Err(JsonError::Internal("Error getting testlist".to_string()))
+    },
+  }
+}
+
+#[get("/<project_id>/archive")]
+pub async fn export_project_archive(jwt: Result<JWT, JsonError>, project_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, projects_repo: &State<MongoRepo<Project>>, testlist_repo: &State<MongoRepo<Testlist>>, testsection_repo: &State<MongoRepo<Testsection>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testplan_repo: &State<MongoRepo<Testplan>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let project = allowed_for_project(jwts, projects_repo, project_id).await?;
+
+  let testchecks = archive_result(testcheck_repo.get_project_testchecks(project_id).await, "testchecks")?;
+  let testcheck_ids: Vec<ObjectId> = testchecks.iter().map(|testcheck| testcheck.id).collect();
+  let testreports = archive_result(testreport_repo.get_project_testreports(project_id).await, "testreports")?;
+  let testreport_ids: Vec<ObjectId> = testreports.iter().map(|testreport| testreport.id).collect();
+  let archive = ProjectArchive {
+    testlists: archive_result(testlist_repo.get_project_testlists(project_id).await, "testlists")?,
+    testsections: archive_result(testsection_repo.get_project_testsections(project_id).await, "testsections")?,
+    testrevisions: archive_result(testrevision_repo.get_testchecks_testrevisions(&testcheck_ids).await, "testrevisions")?,
+    testenvironments: archive_result(testenvironment_repo.get_project_testenvironments(project_id).await, "testenvironments")?,
+    testconfigurations: archive_result(testconfiguration_repo.get_project_testconfigurations(project_id).await, "testconfigurations")?,
+    testplans: archive_result(testplan_repo.get_project_testplans(project_id).await, "testplans")?,
+    testresults: archive_result(testresult_repo.get_testreports_testresults(&testreport_ids).await, "testresults")?,
+    project,
+    testchecks,
+    testreports,
+  };
+
+  match write_project_archive(&archive) {
+    Ok(bytes) => Ok(Download::new(ContentType::ZIP, &format!("{}.zip", archive.project.name), bytes)),
+    Err(e) => {
+      error!("Error writing project archive: {}", e);
+      Err(JsonError::Internal("Error exporting project".to_string()))
+    },
+  }
+}
+
+// Inserts the documents of an imported archive, the ids are new so a failed import is undone by
+// deleting them
+pub async fn insert_project_archive(archive: &ProjectArchive, projects_repo: &State<MongoRepo<Project>>, testlist_repo: &State<MongoRepo<Testlist>>, testsection_repo: &State<MongoRepo<Testsection>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testplan_repo: &State<MongoRepo<Testplan>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<(), JsonError> {
+  let result = async {
+    projects_repo.insert_documents(std::slice::from_ref(&archive.project)).await?;
+    testlist_repo.insert_documents(&archive.testlists).await?;
+    testsection_repo.insert_documents(&archive.testsections).await?;
+    testcheck_repo.insert_documents(&archive.testchecks).await?;
+    testrevision_repo.insert_documents(&archive.testrevisions).await?;
+    testenvironment_repo.insert_documents(&archive.testenvironments).await?;
+    testconfiguration_repo.insert_documents(&archive.testconfigurations).await?;
+    testplan_repo.insert_documents(&archive.testplans).await?;
+    testreport_repo.insert_documents(&archive.testreports).await?;
+    testresult_repo.insert_documents(&archive.testresults).await
+  }.await;
+  let e = match result {
+    Ok(()) => return Ok(()),
+    Err(e) => e,
+  };
+  error!("Error importing project archive: {}", e);
+
+  let rollbacks = [
+    projects_repo.delete_by_ids(&[archive.project.id]).await,
+    testlist_repo.delete_by_ids(&archive.testlists.iter().map(|testlist| testlist.id).collect::<Vec<ObjectId>>()).await,
+    testsection_repo.delete_by_ids(&archive.testsections.iter().map(|testsection| testsection.id).collect::<Vec<ObjectId>>()).await,
+    testcheck_repo.delete_by_ids(&archive.testchecks.iter().map(|testcheck| testcheck.id).collect::<Vec<ObjectId>>()).await,
+    testrevision_repo.delete_by_ids(&archive.testrevisions.iter().map(|testrevision| testrevision.id).collect::<Vec<ObjectId>>()).await,
+    testenvironment_repo.delete_by_ids(&archive.testenvironments.iter().map(|testenvironment| testenvironment.id).collect::<Vec<ObjectId>>()).await,
+    testconfiguration_repo.delete_by_ids(&archive.testconfigurations.iter().map(|testconfiguration| testconfiguration.id).collect::<Vec<ObjectId>>()).await,
+    testplan_repo.delete_by_ids(&archive.testplans.iter().map(|testplan| testplan.id).collect::<Vec<ObjectId>>()).await,
+    testreport_repo.delete_by_ids(&archive.testreports.iter().map(|testreport| testreport.id).collect::<Vec<ObjectId>>()).await,
+    testresult_repo.delete_by_ids(&archive.testresults.iter().map(|testresult| testresult.id).collect::<Vec<ObjectId>>()).await,
+  ];
+  for rollback in rollbacks {
+    if let Err(e) = rollback {
+      error!("Error rolling back project archive import: {}", e);
+    }
+  }
+  Err(JsonError::Internal("Error importing project".to_string()))
+}
+
+fn archive_result<T>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>, name: &str) -> Result<T, JsonError> {
+  result.map_err(|e| {
+    error!("Error getting {}: {}", name, e);
+    JsonError::Internal(format!("Error getting {}", name))
+  })
+}
 
 pub fn get_projects_routes() -> Vec<rocket::Route> {
-  routes![get_projects, get_project, update_project, delete_project, get_project_testlists, create_project_testlist, get_project_testreports]
+  routes![get_projects, get_project, update_project, delete_project, get_project_testlists, create_project_testlist, get_project_testreports, get_project_latest_testreport, get_project_testplans, get_project_current_testplan, create_project_testplan, get_project_testreports_matrix, get_project_testenvironments, create_project_testenvironment, get_project_testconfigurations, create_project_testconfiguration, create_project_testconfigurations_matrix, get_project_flaky_testchecks, get_project_apitokens, create_project_apitoken, import_project_gherkin, export_project_archive]
 }
 
 
-async fn allowed_for_project(jwts: JWTSessionAndUser, project_repo: &State<MongoRepo<Project>>, project_id: &str) -> Result<Project, JsonError>  {
+pub async fn allowed_for_project(jwts: JWTSessionAndUser, project_repo: &State<MongoRepo<Project>>, project_id: &str) -> Result<Project, JsonError>  {
   if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
     return Err(JsonError::Forbidden(
       "You are not allowed to retrieve this project".to_string(),
@@ -175,3 +670,23 @@ async fn allowed_for_project(jwts: JWTSessionAndUser, project_repo: &State<Mongo
     },
   }
 }
+
+// None without any build field
+fn get_build_filter(commit_sha: Option<&str>, branch: Option<&str>, tag: Option<&str>, build_number: Option<&str>, actor: Option<&str>, environment: Option<&str>) -> Result<Option<TestreportBuildFilter>, JsonError> {
+  let value = |value: Option<&str>| value.map(str::trim).filter(|value| !value.is_empty()).map(str::to_string);
+  let build_filter = TestreportBuildFilter {
+    commit_sha: value(commit_sha).map(|commit_sha| commit_sha.to_lowercase()),
+    branch: value(branch).map(|branch| branch.trim_start_matches("refs/heads/").to_string()),
+    tag: value(tag).map(|tag| tag.trim_start_matches("refs/tags/").to_string()),
+    build_number: value(build_number),
+    actor: value(actor),
+    environment: value(environment),
+  };
+  if let Some(commit_sha) = &build_filter.commit_sha {
+    check_commit_sha(commit_sha).map_err(JsonError::BadRequest)?;
+  }
+  if build_filter.commit_sha.is_none() && build_filter.branch.is_none() && build_filter.tag.is_none() && build_filter.build_number.is_none() && build_filter.actor.is_none() && build_filter.environment.is_none() {
+    return Ok(None);
+  }
+  Ok(Some(build_filter))
+}
@@ -1,7 +1,8 @@
 use bson::{oid::ObjectId, DateTime};
+use chrono::Utc;
 use rocket::serde::{Deserialize, Serialize};
 
-use crate::service::db::{serialize_datetime, serialize_object_id};
+use crate::{qualitygates::schema::GateRule, service::db::{serialize_datetime, serialize_object_id}};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Project {
@@ -11,6 +12,13 @@ pub struct Project {
   pub version: String,
   pub description: String,
   pub repository: String,
+  #[serde(default)]
+  pub signoff: ProjectSignoffPolicy,
+  #[serde(default)]
+  pub flaky: ProjectFlakyPolicy,
+  // Rules evaluated by the CI quality gate
+  #[serde(default)]
+  pub quality_gate: Vec<GateRule>,
   #[serde(serialize_with = "serialize_object_id")]
   pub account_id: ObjectId,
   #[serde(serialize_with = "serialize_datetime")]
@@ -25,4 +33,80 @@ pub struct ProjectDto {
   pub version: String,
   pub description: String,
   pub repository: String,
+  // Policies left out keep their stored value, or the default on creation
+  #[serde(default)]
+  pub signoff: Option<ProjectSignoffPolicy>,
+  #[serde(default)]
+  pub flaky: Option<ProjectFlakyPolicy>,
+  #[serde(default)]
+  pub quality_gate: Option<Vec<GateRule>>,
+}
+
+// Requirements checked when a testreport of the project is signed off
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProjectSignoffPolicy {
+  // Percentage of passed results over all the report results, 0 disables the check
+  pub min_pass_rate: f64,
+  pub require_manager: bool,
+}
+
+// A check is flaky when its outcome flips too often over its latest executions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ProjectFlakyPolicy {
+  // Number of latest executions considered
+  pub window: u32,
+  // Ratio of pass/fail flips between consecutive executions, from 0 to 1
+  pub threshold: f64,
+  // Executions needed before a check can be flaky, a single pass then fail is a regression
+  pub min_runs: u32,
+  // Flag new results of flaky checks automatically
+  pub auto_flag: bool,
+}
+
+impl Default for ProjectFlakyPolicy {
+  fn default() -> Self {
+    ProjectFlakyPolicy {
+      window: 10,
+      threshold: 0.3,
+      min_runs: 5,
+      auto_flag: false,
+    }
+  }
+}
+
+// First entry of a project archive, describing the other entries
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectArchiveManifest {
+  pub format: String,
+  pub version: u32,
+  pub exported_at: chrono::DateTime<Utc>,
+  pub project_id: String,
+  pub project_name: String,
+  pub entries: Vec<ProjectArchiveEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectArchiveEntry {
+  pub path: String,
+  // Number of documents of the collection entries, None for attachments
+  #[serde(default)]
+  pub documents: Option<usize>,
+  pub size: u64,
+  pub sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectImportResult {
+  pub project: Project,
+  pub testlists: usize,
+  pub testsections: usize,
+  pub testchecks: usize,
+  pub testrevisions: usize,
+  pub testenvironments: usize,
+  pub testconfigurations: usize,
+  pub testplans: usize,
+  pub testreports: usize,
+  pub testresults: usize,
+  pub attachments: usize,
 }
@@ -5,7 +5,7 @@ use mongodb::{
   bson::{self, doc, oid::ObjectId}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
 };
 use crate::service::db::{ self, MongoRepo};
-use super::schema::{Project, ProjectDto};
+use super::schema::{Project, ProjectDto, ProjectFlakyPolicy};
 use rocket::futures::TryStreamExt;
 
 pub fn get_projects_repo(client: Client) -> MongoRepo<Project> {
@@ -42,6 +42,9 @@ impl MongoRepo<Project> {
       version: data.version,
       description: data.description,
       repository: data.repository,
+      signoff: data.signoff.unwrap_or_default(),
+      flaky: data.flaky.unwrap_or_default(),
+      quality_gate: data.quality_gate.unwrap_or_default(),
       created_at: now,
       updated_at: now,
     };
@@ -52,13 +55,24 @@ impl MongoRepo<Project> {
   pub async fn update(&self, id: String, data: ProjectDto) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
     let now = chrono::Utc::now();
     let filter = doc! { "_id": ObjectId::parse_str(&id)? };
-    let upd_doc = doc! { "$set": {
+    let mut set_doc = doc! {
       "name": data.name,
       "version": data.version,
       "description": data.description,
       "repository": data.repository,
       "updated_at": DateTime::from_chrono(now)
-    } };
+    };
+    // Policies are only replaced when sent
+    if let Some(signoff) = data.signoff {
+      set_doc.insert("signoff", bson::to_bson(&signoff)?);
+    }
+    if let Some(flaky) = data.flaky {
+      set_doc.insert("flaky", bson::to_bson(&flaky)?);
+    }
+    if let Some(quality_gate) = data.quality_gate {
+      set_doc.insert("quality_gate", bson::to_bson(&quality_gate)?);
+    }
+    let upd_doc = doc! { "$set": set_doc };
     let result = self.col.update_one(filter, upd_doc, None).await?;
     Ok(result)
   }
@@ -69,3 +83,39 @@ impl MongoRepo<Project> {
     Ok(result)
   }
 }
+
+// Zero window would lift the executions limit, the threshold is a ratio
+pub fn check_project_flaky_policy(policy: &ProjectFlakyPolicy) -> Result<(), String> {
+  if policy.window < 2 {
+    return Err(format!("Invalid flaky window: {}, at least 2 executions are needed", policy.window));
+  }
+  if !(0.0..=1.0).contains(&policy.threshold) {
+    return Err(format!("Invalid flaky threshold: {}, expected a ratio from 0 to 1", policy.threshold));
+  }
+  if policy.min_runs < 2 || policy.min_runs > policy.window {
+    return Err(format!("Invalid flaky minimum runs: {}, expected from 2 to the window", policy.min_runs));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn accepts_default_flaky_policy() {
+    assert!(check_project_flaky_policy(&ProjectFlakyPolicy::default()).is_ok());
+  }
+
+  #[test]
+  fn rejects_invalid_flaky_policies() {
+    let policy = |window: u32, threshold: f64, min_runs: u32| ProjectFlakyPolicy { window, threshold, min_runs, auto_flag: true };
+    assert!(check_project_flaky_policy(&policy(0, 0.3, 0)).is_err());
+    assert!(check_project_flaky_policy(&policy(10, -0.1, 5)).is_err());
+    assert!(check_project_flaky_policy(&policy(10, 1.5, 5)).is_err());
+    assert!(check_project_flaky_policy(&policy(10, f64::NAN, 5)).is_err());
+    assert!(check_project_flaky_policy(&policy(10, 0.3, 1)).is_err());
+    assert!(check_project_flaky_policy(&policy(10, 0.3, 11)).is_err());
+    assert!(check_project_flaky_policy(&policy(2, 1.0, 2)).is_ok());
+  }
+}
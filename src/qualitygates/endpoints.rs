@@ -0,0 +1,63 @@
+use bson::oid::ObjectId;
+use log::error;
+use rocket::{get, routes, serde::json::Json, State};
+use crate::{apitokens::{schema::Apitoken, token::{authorize_project_apitoken, ApiToken}}, projects::{endpoints::allowed_for_project, schema::Project}, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::Session}, testplans::{endpoints::get_testplan_by_id, schema::Testplan}, testreports::{endpoints::get_testreport_by_id, schema::Testreport}, testresults::schema::Testresult, users::schema::User};
+
+use super::{schema::GateVerdict, service::evaluate_quality_gate};
+
+// Callable from CI with an X-Api-Token header, or with a user session
+#[get("/<project_id>/gate?<testreport_id>&<testplan_id>")]
+async fn get_project_gate(api_token: Result<ApiToken, JsonError>, jwt: Result<JWT, JsonError>, project_id: &str, testreport_id: Option<&str>, testplan_id: Option<&str>, apitoken_repo: &State<MongoRepo<Apitoken>>, projects_repo: &State<MongoRepo<Project>>, testplan_repo: &State<MongoRepo<Testplan>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<GateVerdict>, JsonError> {
+  let project = match api_token {
+    Ok(api_token) => {
+      let project = get_project(projects_repo, project_id).await?;
+      authorize_project_apitoken(apitoken_repo, api_token, project.id).await?;
+      project
+    },
+    Err(_) => {
+      let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+      allowed_for_project(jwts, projects_repo, project_id).await?
+    },
+  };
+
+  let testreport_ids: Vec<ObjectId> = match (testreport_id, testplan_id) {
+    (Some(testreport_id), None) => {
+      let testreport = get_testreport_by_id(testreport_repo, testreport_id).await?;
+      if testreport.project_id != project.id {
+        return Err(JsonError::BadRequest("Testreport not found in project".to_string()));
+      }
+      vec![testreport.id]
+    },
+    (None, Some(testplan_id)) => {
+      let testplan = get_testplan_by_id(testplan_repo, testplan_id).await?;
+      if testplan.project_id != project.id {
+        return Err(JsonError::BadRequest("Testplan not found in project".to_string()));
+      }
+      testplan.testreport_ids
+    },
+    _ => return Err(JsonError::BadRequest("Either testreport_id or testplan_id is required".to_string())),
+  };
+
+  match testresult_repo.get_testreports_testresults(&testreport_ids).await {
+    Ok(testresults) => Ok(Json(evaluate_quality_gate(&project.quality_gate, testreport_ids, &testresults))),
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+pub fn get_qualitygates_routes() -> Vec<rocket::Route> {
+  routes![get_project_gate]
+}
+
+async fn get_project(projects_repo: &State<MongoRepo<Project>>, project_id: &str) -> Result<Project, JsonError> {
+  match projects_repo.get_project_by_id(project_id).await {
+    Ok(Some(project)) => Ok(project),
+    Ok(None) => Err(JsonError::NotFound("Project not found".to_string())),
+    Err(e) => {
+      error!("Error getting project: {}", e);
+      Err(JsonError::Internal("Error getting project".to_string()))
+    },
+  }
+}
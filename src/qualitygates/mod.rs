@@ -0,0 +1,3 @@
+pub mod endpoints;
+pub mod schema;
+pub mod service;
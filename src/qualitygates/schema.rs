@@ -0,0 +1,66 @@
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::service::db::serialize_object_ids;
+
+// Rule of the project quality gate, evaluated against executed results
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GateRule {
+  // Percentage of passed results over the executed ones
+  MinPassRate { value: f64 },
+  // No failed or blocked result tagged with the tag
+  NoFailedTag { tag: String },
+  // Every result has been executed
+  AllExecuted,
+  MaxFailed { value: u32 },
+}
+
+impl std::fmt::Display for GateRule {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      GateRule::MinPassRate { value } => write!(f, "pass rate >= {}%", value),
+      GateRule::NoFailedTag { tag } => write!(f, "no failed check tagged {}", tag),
+      GateRule::AllExecuted => write!(f, "all checks executed"),
+      GateRule::MaxFailed { value } => write!(f, "at most {} failed checks", value),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GateStatus {
+  Pass,
+  Fail,
+}
+
+impl std::fmt::Display for GateStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      GateStatus::Pass => write!(f, "pass"),
+      GateStatus::Fail => write!(f, "fail"),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GateViolation {
+  pub rule: GateRule,
+  pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GateVerdict {
+  pub status: GateStatus,
+  // 0 when the gate passes, 1 otherwise, to be used as the CI job exit code
+  pub exit_code: u8,
+  #[serde(serialize_with = "serialize_object_ids")]
+  pub testreport_ids: Vec<ObjectId>,
+  pub total: u32,
+  pub executed: u32,
+  pub passed: u32,
+  pub failed: u32,
+  pub pass_rate: f64,
+  pub rules: Vec<GateRule>,
+  pub violations: Vec<GateViolation>,
+}
@@ -0,0 +1,54 @@
+use mongodb::bson::oid::ObjectId;
+
+use crate::testresults::schema::{Testresult, TestresultStatus};
+
+use super::schema::{GateRule, GateStatus, GateVerdict, GateViolation};
+
+pub fn evaluate_quality_gate(rules: &[GateRule], testreport_ids: Vec<ObjectId>, testresults: &[Testresult]) -> GateVerdict {
+  let total = testresults.len() as u32;
+  let executed = testresults.iter().filter(|testresult| testresult.updated).count() as u32;
+  let passed = testresults.iter().filter(|testresult| testresult.status() == TestresultStatus::Passed).count() as u32;
+  let failed = executed - passed;
+  let pass_rate = if executed > 0 { passed as f64 * 100.0 / executed as f64 } else { 0.0 };
+
+  let violations: Vec<GateViolation> = rules.iter().filter_map(|rule| {
+    let message = match rule {
+      GateRule::MinPassRate { value } if executed == 0 || pass_rate < *value => {
+        format!("Pass rate is {:.2}%, {}% required", pass_rate, value)
+      },
+      GateRule::NoFailedTag { tag } => {
+        let tagged: Vec<&str> = testresults.iter()
+          .filter(|testresult| matches!(testresult.status(), TestresultStatus::Failed | TestresultStatus::Blocked))
+          .filter(|testresult| testresult.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+          .map(|testresult| testresult.name.as_str())
+          .collect();
+        if tagged.is_empty() {
+          return None;
+        }
+        format!("{} failed checks tagged {}: {}", tagged.len(), tag, tagged.join(", "))
+      },
+      GateRule::AllExecuted if executed < total => {
+        format!("{} of {} checks not executed", total - executed, total)
+      },
+      GateRule::MaxFailed { value } if failed > *value => {
+        format!("{} failed checks, at most {} allowed", failed, value)
+      },
+      _ => return None,
+    };
+    Some(GateViolation { rule: rule.clone(), message })
+  }).collect();
+
+  let status = if violations.is_empty() { GateStatus::Pass } else { GateStatus::Fail };
+  GateVerdict {
+    status,
+    exit_code: if status == GateStatus::Pass { 0 } else { 1 },
+    testreport_ids,
+    total,
+    executed,
+    passed,
+    failed,
+    pass_rate,
+    rules: rules.to_vec(),
+    violations,
+  }
+}
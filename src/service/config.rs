@@ -3,6 +3,7 @@ use std::env;
 extern crate dotenv;
 use dotenv::dotenv;
 
+#[allow(dead_code)]
 pub struct Config {
   pub mongodb_uri: String,
   pub jwt_secret: String,
@@ -22,3 +23,11 @@ pub fn get_config() -> Result<Config, Box<dyn Error>> {
   };
   Ok(config)
 }
+
+// Address of the web app, exports link back to it when set
+pub fn get_app_url() -> Option<String> {
+  dotenv().ok();
+  env::var("APP_URL").ok()
+    .map(|app_url| app_url.trim().trim_end_matches('/').to_string())
+    .filter(|app_url| !app_url.is_empty())
+}
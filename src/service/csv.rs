@@ -0,0 +1,61 @@
+// RFC 4180 CSV, fields are quoted only when needed
+
+pub fn csv_row(fields: &[&str]) -> String {
+  let mut row = fields.iter().map(|field| csv_field(field)).collect::<Vec<String>>().join(",");
+  row.push_str("\r\n");
+  row
+}
+
+fn csv_field(field: &str) -> String {
+  if field.contains(',') || field.contains('"') || field.contains('\n') || field.contains('\r') {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
+
+// Records of a RFC 4180 CSV document, blank lines are skipped
+pub fn csv_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+  let mut records = Vec::new();
+  let mut record: Vec<String> = Vec::new();
+  let mut field = String::new();
+  let mut quoted = false;
+  let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+
+  while let Some(c) = chars.next() {
+    if quoted {
+      match c {
+        '"' if chars.peek() == Some(&'"') => {
+          chars.next();
+          field.push('"');
+        },
+        '"' => quoted = false,
+        _ => field.push(c),
+      }
+      continue;
+    }
+    match c {
+      '"' if field.is_empty() => quoted = true,
+      ',' => record.push(std::mem::take(&mut field)),
+      '\r' | '\n' => {
+        if c == '\r' && chars.peek() == Some(&'\n') {
+          chars.next();
+        }
+        record.push(std::mem::take(&mut field));
+        if record.iter().any(|field| !field.is_empty()) {
+          records.push(std::mem::take(&mut record));
+        }
+        record.clear();
+      },
+      _ => field.push(c),
+    }
+  }
+  if quoted {
+    return Err(format!("Unterminated quoted field on record {}", records.len() + 1));
+  }
+  record.push(field);
+  if record.iter().any(|field| !field.is_empty()) {
+    records.push(record);
+  }
+  Ok(records)
+}
@@ -1,8 +1,8 @@
 use std::time::Duration;
 
 use bson::{doc, oid::ObjectId, DateTime};
-use mongodb::{error::Error, options::{ClientOptions, IndexOptions, ServerApi, ServerApiVersion}, results::CreateIndexResult, Client, Collection, Database, IndexModel};
-use rocket::serde::Serializer;
+use mongodb::{error::Error, options::{ClientOptions, IndexOptions, ServerApi, ServerApiVersion}, results::{CreateIndexResult, DeleteResult}, Client, Collection, Database, IndexModel};
+use rocket::serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
 
 pub async fn connect(uri: &str) -> Result<Client, Error> {
   let mut client_options = ClientOptions::parse_async(uri).await?;
@@ -17,6 +17,7 @@ pub async fn connect(uri: &str) -> Result<Client, Error> {
 }
 
 
+#[allow(dead_code)]
 pub struct MongoRepo<T> {
   pub db: Database,
   pub col: Collection<T>,
@@ -40,6 +41,20 @@ impl<T> MongoRepo<T> {
     let index = IndexModel::builder().keys(doc! { prop: 1 }).options(opts).build();
     self.col.create_index(index, None).await
   }
+
+  pub async fn delete_by_ids(&self, ids: &[ObjectId]) -> Result<DeleteResult, Error> {
+    self.col.delete_many(doc! { "_id": { "$in": ids } }, None).await
+  }
+}
+
+impl<T: Serialize> MongoRepo<T> {
+  // Inserts documents kept as they are, ids included
+  pub async fn insert_documents(&self, documents: &[T]) -> Result<(), Error> {
+    if !documents.is_empty() {
+      self.col.insert_many(documents, None).await?;
+    }
+    Ok(())
+  }
 }
 
 pub fn get_mongo_repo<T>(client: Client, dbname: &str, collname: &str) -> MongoRepo<T> {
@@ -58,6 +73,15 @@ where
   return serializer.serialize_some(date)
 }
 
+pub fn serialize_option_datetime<S>(date: &Option<DateTime>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+  match date {
+    Some(ref date) => serialize_datetime(date, serializer),
+    None => serializer.serialize_none()
+  }
+}
 
 pub fn serialize_object_id<S>(object_id: &ObjectId, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -69,12 +93,37 @@ where
   return serializer.serialize_some(object_id)
 }
 
-// pub fn serialize_option_object_id<S>(object_id: &Option<ObjectId>, serializer: S) -> Result<S::Ok, S::Error>
-// where
-//     S: Serializer,
-// {
-//     match object_id {
-//       Some(ref object_id) => serializer.serialize_some(object_id.to_string().as_str()),
-//       None => serializer.serialize_none()
-//     }
-// }
+pub fn serialize_object_ids<S>(object_ids: &[ObjectId], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+  let human_readable = serializer.is_human_readable();
+  let mut seq = serializer.serialize_seq(Some(object_ids.len()))?;
+  for object_id in object_ids {
+    if human_readable {
+      seq.serialize_element(object_id.to_string().as_str())?;
+    } else {
+      seq.serialize_element(object_id)?;
+    }
+  }
+  seq.end()
+}
+
+pub fn serialize_option_object_id<S>(object_id: &Option<ObjectId>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+  match object_id {
+    Some(ref object_id) => serialize_object_id(object_id, serializer),
+    None => serializer.serialize_none()
+  }
+}
+
+// Tells a field sent as null, Some(None), from a missing one, None with #[serde(default)]
+pub fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+  T::deserialize(deserializer).map(Some)
+}
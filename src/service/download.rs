@@ -0,0 +1,38 @@
+use std::io::Cursor;
+use rocket::http::{ContentType, Header};
+use rocket::request::Request;
+use rocket::response::{self, Response, Responder};
+
+// A file sent as an attachment, the browser saves it as `filename`
+pub struct Download {
+  pub content_type: ContentType,
+  pub filename: String,
+  pub body: Vec<u8>,
+}
+
+impl Download {
+  pub fn new(content_type: ContentType, filename: &str, body: Vec<u8>) -> Download {
+    Download {
+      content_type,
+      filename: sanitize_filename(filename),
+      body,
+    }
+  }
+}
+
+impl<'r> Responder<'r, 'static> for Download {
+  fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+    let disposition = format!("attachment; filename=\"{}\"", self.filename);
+    Response::build()
+      .header(self.content_type)
+      .header(Header::new("Content-Disposition", disposition))
+      .sized_body(self.body.len(), Cursor::new(self.body))
+      .ok()
+  }
+}
+
+fn sanitize_filename(filename: &str) -> String {
+  filename.chars()
+    .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+    .collect()
+}
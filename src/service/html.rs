@@ -0,0 +1,5 @@
+// Escapes text so that it renders literally in HTML content and attribute values
+
+pub fn html_text(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
@@ -0,0 +1,43 @@
+// Escapes text so that it renders literally in Markdown, inline HTML included
+
+pub fn markdown_text(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\\', "\\\\").replace('*', "\\*").replace('_', "\\_").replace('`', "\\`").replace('[', "\\[").replace(']', "\\]")
+}
+
+// Table cells cannot contain pipes or line breaks
+pub fn markdown_cell(text: &str) -> String {
+  markdown_text(text).replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+}
+
+// Link destination wrapped in angle brackets, which cannot contain brackets, spaces or line breaks
+pub fn markdown_url(url: &str) -> String {
+  let mut destination = String::from("<");
+  for c in url.chars() {
+    if c == '<' || c == '>' || c.is_whitespace() || c.is_control() {
+      let mut buffer = [0; 4];
+      for byte in c.encode_utf8(&mut buffer).bytes() {
+        destination.push_str(&format!("%{:02X}", byte));
+      }
+    } else {
+      destination.push(c);
+    }
+  }
+  destination.push('>');
+  destination
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn text_escapes_inline_html() {
+    assert_eq!(markdown_text("a </details> <!-- & *b*"), "a &lt;/details&gt; &lt;!-- &amp; \\*b\\*");
+  }
+
+  #[test]
+  fn url_is_wrapped_and_encoded() {
+    assert_eq!(markdown_url("https://ci.example.com/a b?q=<x>)"), "<https://ci.example.com/a%20b?q=%3Cx%3E)>");
+    assert_eq!(markdown_url("https://example.com/\n"), "<https://example.com/%0A>");
+  }
+}
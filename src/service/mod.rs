@@ -1,5 +1,10 @@
 pub mod config;
+pub mod csv;
 pub mod db;
+pub mod download;
+pub mod html;
 pub mod http_errors;
+pub mod markdown;
 pub mod schema;
 pub mod validation;
+pub mod xlsx;
@@ -0,0 +1,76 @@
+// Helpers shared by the Excel workbook exports
+
+use bson::DateTime;
+use rocket::http::ContentType;
+use rust_xlsxwriter::{Color, ExcelDateTime, Format, FormatBorder, Worksheet, XlsxError};
+
+// Longest text a cell holds
+const MAX_CELL_LENGTH: usize = 32767;
+const MAX_SHEET_NAME_LENGTH: usize = 31;
+const MAX_URL_LENGTH: usize = 2079;
+
+pub fn xlsx_content_type() -> ContentType {
+  ContentType::new("application", "vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+}
+
+// Sheet names are unique regardless of case, short, and without []:*?/\
+pub fn xlsx_sheet_name(name: &str, used: &mut Vec<String>) -> String {
+  let name: String = name.chars().map(|c| if "[]:*?/\\".contains(c) { '_' } else { c }).collect();
+  let name = name.trim().trim_matches('\'').to_string();
+  let name = if name.is_empty() || name.eq_ignore_ascii_case("history") { "Sheet".to_string() } else { name };
+  let mut suffix = 1;
+  loop {
+    let end = if suffix == 1 { String::new() } else { format!(" ({})", suffix) };
+    let candidate: String = name.chars().take(MAX_SHEET_NAME_LENGTH - end.chars().count()).collect();
+    let candidate = format!("{}{}", candidate.trim_end(), end);
+    if !used.iter().any(|used| used.to_lowercase() == candidate.to_lowercase()) {
+      used.push(candidate.clone());
+      return candidate;
+    }
+    suffix += 1;
+  }
+}
+
+// Sheet name as written in formulas
+pub fn xlsx_sheet_reference(name: &str) -> String {
+  format!("'{}'", name.replace('\'', "''"))
+}
+
+pub fn xlsx_text(text: &str) -> String {
+  text.chars().take(MAX_CELL_LENGTH).collect()
+}
+
+pub fn xlsx_header_format() -> Format {
+  Format::new().set_bold().set_background_color(Color::RGB(0xEAEEF2)).set_border_bottom(FormatBorder::Thin)
+}
+
+pub fn xlsx_datetime_format() -> Format {
+  Format::new().set_num_format("yyyy-mm-dd hh:mm")
+}
+
+// Header in the first row, kept visible when scrolling and filtering the rows below
+pub fn write_xlsx_table_header(worksheet: &mut Worksheet, columns: &[(&str, f64)], rows: u32) -> Result<(), XlsxError> {
+  let header_format = xlsx_header_format();
+  for (index, (title, width)) in columns.iter().enumerate() {
+    worksheet.write_string_with_format(0, index as u16, *title, &header_format)?;
+    worksheet.set_column_width(index as u16, *width)?;
+  }
+  worksheet.set_freeze_panes(1, 0)?;
+  worksheet.autofilter(0, 0, rows, columns.len() as u16 - 1)?;
+  Ok(())
+}
+
+pub fn write_xlsx_datetime(worksheet: &mut Worksheet, row: u32, col: u16, datetime: DateTime) -> Result<(), XlsxError> {
+  let datetime = ExcelDateTime::from_timestamp(datetime.timestamp_millis() / 1000)?;
+  worksheet.write_datetime_with_format(row, col, &datetime, &xlsx_datetime_format())?;
+  Ok(())
+}
+
+// Web links are clickable, other URLs are written as text
+pub fn write_xlsx_url(worksheet: &mut Worksheet, row: u32, col: u16, url: &str) -> Result<(), XlsxError> {
+  if (url.starts_with("https://") || url.starts_with("http://")) && url.len() <= MAX_URL_LENGTH && worksheet.write_url(row, col, url).is_ok() {
+    return Ok(());
+  }
+  worksheet.write_string(row, col, xlsx_text(url))?;
+  Ok(())
+}
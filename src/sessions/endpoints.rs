@@ -12,12 +12,13 @@ use super::{jwt::{create_jwt, get_jwt_session, get_jwt_session_and_user, JWT}, s
 
 #[post("/login", format = "json", data = "<login>")]
 pub async fn login(login: Json<LoginDto>, users_repo: &State<MongoRepo<User>>, sessions_repos: &State<MongoRepo<Session>>, accounts_repo: &State<MongoRepo<Account>>) -> Result<Json<LoginResponseDto>, JsonError> {
-  let user = users_repo.verify_login(login.into_inner()).await;
-  if user.is_err() {
-    error!("Error verifying login: {}", user.unwrap_err());
-    return Err(JsonError::Internal("Error verifying login".to_string()));
-  }
-  let user = user.unwrap();
+  let user = match users_repo.verify_login(login.into_inner()).await {
+    Ok(user) => user,
+    Err(e) => {
+      error!("Error verifying login: {}", e);
+      return Err(JsonError::Internal("Error verifying login".to_string()));
+    }
+  };
 
   match user {
     Some(user) => {
@@ -3,6 +3,7 @@ use rocket::serde::{Deserialize, Serialize};
 
 use crate::{accounts::schema::LoginAccount, service::db::{serialize_datetime, serialize_object_id}, users::schema::{User, UserRes}};
 
+#[allow(dead_code)]
 pub struct JWTSession {
   pub token: String,
   pub session: Session,
@@ -0,0 +1,217 @@
+// CSV exchange of testchecks with spreadsheets, the export can be imported back as is.
+// Columns: id, key, name, description, expected, tags. Tags are separated by commas or semicolons.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::service::csv::{csv_records, csv_row};
+
+use super::schema::{Testcheck, TestcheckDto};
+
+pub const TESTCHECKS_CSV_FIELDS: [&str; 6] = ["id", "key", "name", "description", "expected", "tags"];
+
+// A record of the imported CSV, fields are trimmed
+#[derive(Debug, Clone, Default)]
+pub struct TestcheckCsvRow {
+  pub row: usize,
+  pub id: String,
+  pub key: String,
+  pub name: String,
+  pub description: String,
+  pub expected: String,
+  pub tags: Vec<String>,
+  pub errors: Vec<String>,
+}
+
+pub fn get_testchecks_csv(testchecks: &[Testcheck]) -> String {
+  let mut csv = csv_row(&TESTCHECKS_CSV_FIELDS);
+  for testcheck in testchecks {
+    let id = testcheck.id.to_hex();
+    let tags = testcheck.tags.join(", ");
+    csv.push_str(&csv_row(&[
+      &id,
+      testcheck.external_key.as_deref().unwrap_or(""),
+      &testcheck.name,
+      &testcheck.description,
+      &testcheck.expected,
+      &tags,
+    ]));
+  }
+  csv
+}
+
+// Fails only when the document or its header cannot be used, row errors are reported on each row
+pub fn parse_testchecks_csv(text: &str, mapping: &HashMap<String, String>) -> Result<Vec<TestcheckCsvRow>, String> {
+  let records = csv_records(text)?;
+  let header = match records.first() {
+    Some(header) => header,
+    None => return Err("CSV is empty".to_string()),
+  };
+
+  for field in mapping.keys() {
+    if !TESTCHECKS_CSV_FIELDS.contains(&field.as_str()) {
+      return Err(format!("Unknown field {}, expected one of {}", field, TESTCHECKS_CSV_FIELDS.join(", ")));
+    }
+  }
+  let mut columns: HashMap<&str, usize> = HashMap::new();
+  for field in TESTCHECKS_CSV_FIELDS {
+    let column = mapping.get(field).map(|column| column.as_str()).unwrap_or(field);
+    match header.iter().position(|name| name.trim().eq_ignore_ascii_case(column.trim())) {
+      Some(index) => {
+        columns.insert(field, index);
+      },
+      None if mapping.contains_key(field) => return Err(format!("Column {} not found", column)),
+      None => {},
+    }
+  }
+  if !columns.contains_key("name") {
+    return Err("A name column is required".to_string());
+  }
+
+  let mut keys: HashSet<String> = HashSet::new();
+  let mut ids: HashSet<String> = HashSet::new();
+  let rows = records.iter().enumerate().skip(1).map(|(index, record)| {
+    let value = |field: &str| columns.get(field)
+      .and_then(|column| record.get(*column))
+      .map(|value| value.trim().to_string())
+      .unwrap_or_default();
+    let mut row = TestcheckCsvRow {
+      row: index + 1,
+      id: value("id"),
+      key: value("key"),
+      name: value("name"),
+      description: value("description"),
+      expected: value("expected"),
+      tags: parse_tags(&value("tags")),
+      errors: Vec::new(),
+    };
+    if record.len() != header.len() {
+      row.errors.push(format!("Expected {} columns, found {}", header.len(), record.len()));
+    }
+    if row.name.is_empty() {
+      row.errors.push("Name is required".to_string());
+    }
+    if !row.id.is_empty() && !ids.insert(row.id.clone()) {
+      row.errors.push(format!("Duplicate id {}", row.id));
+    }
+    if !row.key.is_empty() && !keys.insert(row.key.clone()) {
+      row.errors.push(format!("Duplicate key {}", row.key));
+    }
+    row
+  }).collect();
+  Ok(rows)
+}
+
+fn parse_tags(tags: &str) -> Vec<String> {
+  let mut parsed: Vec<String> = Vec::new();
+  for tag in tags.split([',', ';']).map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+    if !parsed.iter().any(|t| t == tag) {
+      parsed.push(tag.to_string());
+    }
+  }
+  parsed
+}
+
+// Existing check of the testlist updated by the row, matched by id first, then by key
+pub fn find_imported_testcheck<'a>(row: &TestcheckCsvRow, testchecks: &'a [Testcheck]) -> Option<&'a Testcheck> {
+  if !row.id.is_empty() {
+    if let Some(testcheck) = testchecks.iter().find(|testcheck| testcheck.id.to_hex() == row.id) {
+      return Some(testcheck);
+    }
+  }
+  if row.key.is_empty() {
+    return None;
+  }
+  testchecks.iter().find(|testcheck| testcheck.external_key.as_deref() == Some(row.key.as_str()))
+}
+
+// An empty key keeps the key of the updated check
+pub fn get_imported_testcheck_dto(row: &TestcheckCsvRow, testcheck: Option<&Testcheck>) -> TestcheckDto {
+  let external_key = match row.key.is_empty() {
+    true => testcheck.and_then(|testcheck| testcheck.external_key.clone()),
+    false => Some(row.key.clone()),
+  };
+  TestcheckDto {
+    name: row.name.clone(),
+    description: row.description.clone(),
+    expected: row.expected.clone(),
+    tags: row.tags.clone(),
+    section_id: testcheck.and_then(|testcheck| testcheck.section_id).map(|section_id| section_id.to_hex()),
+    external_key,
+    automation_key: testcheck.and_then(|testcheck| testcheck.automation_key.clone()),
+    automation_aliases: testcheck.map(|testcheck| testcheck.automation_aliases.clone()).unwrap_or_default(),
+    mode: testcheck.map(|testcheck| testcheck.mode).unwrap_or_default(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bson::{oid::ObjectId, DateTime};
+
+  use super::*;
+  use crate::testchecks::{schema::TestcheckMode, service::is_testcheck_unchanged};
+
+  fn testcheck(name: &str, description: &str, external_key: Option<&str>, tags: &[&str]) -> Testcheck {
+    let now = DateTime::now();
+    Testcheck {
+      id: ObjectId::new(),
+      testlist_id: ObjectId::new(),
+      section_id: None,
+      project_id: ObjectId::new(),
+      account_id: ObjectId::new(),
+      name: name.to_string(),
+      description: description.to_string(),
+      expected: "Done, \"as expected\"".to_string(),
+      tags: tags.iter().map(|tag| tag.to_string()).collect(),
+      external_key: external_key.map(|key| key.to_string()),
+      automation_key: Some("cart.spec.ts > checkout".to_string()),
+      automation_aliases: Vec::new(),
+      mode: TestcheckMode::Both,
+      position: 0,
+      revision: 1,
+      created_at: now,
+      updated_at: now,
+    }
+  }
+
+  #[test]
+  fn export_imports_back_unchanged() {
+    let testchecks = vec![
+      testcheck("Checkout", "Open the cart,\nthen pay", Some("JIRA-12"), &["smoke", "cart"]),
+      testcheck("Refund; partial", "Refund one item", None, &[]),
+    ];
+    let csv = get_testchecks_csv(&testchecks);
+    let rows = parse_testchecks_csv(&csv, &HashMap::new()).unwrap();
+    assert_eq!(rows.len(), 2);
+    for (row, testcheck) in rows.iter().zip(&testchecks) {
+      assert!(row.errors.is_empty(), "{:?}", row.errors);
+      let found = find_imported_testcheck(row, &testchecks).unwrap();
+      assert_eq!(found.id, testcheck.id);
+      assert!(is_testcheck_unchanged(found, &get_imported_testcheck_dto(row, Some(found))));
+    }
+  }
+
+  #[test]
+  fn matches_rows_by_key_without_id() {
+    let testchecks = vec![testcheck("Checkout", "Pay", Some("JIRA-12"), &[])];
+    let csv = "Summary,Issue key,Labels\nCheckout again,JIRA-12,smoke; ui;smoke\n";
+    let mapping = HashMap::from([("name".to_string(), "Summary".to_string()), ("key".to_string(), "Issue key".to_string()), ("tags".to_string(), "Labels".to_string())]);
+    let rows = parse_testchecks_csv(csv, &mapping).unwrap();
+    assert_eq!(rows[0].tags, ["smoke", "ui"]);
+    assert_eq!(find_imported_testcheck(&rows[0], &testchecks).map(|testcheck| testcheck.id), Some(testchecks[0].id));
+    // An edited name is an update that keeps the automation of the check
+    let dto = get_imported_testcheck_dto(&rows[0], Some(&testchecks[0]));
+    assert_eq!(dto.automation_key, testchecks[0].automation_key);
+    assert!(!is_testcheck_unchanged(&testchecks[0], &dto));
+  }
+
+  #[test]
+  fn reports_row_and_header_errors() {
+    let rows = parse_testchecks_csv("name,key\n,K1\nB,K1\nC\n", &HashMap::new()).unwrap();
+    assert_eq!(rows[0].errors, ["Name is required"]);
+    assert_eq!(rows[1].errors, ["Duplicate key K1"]);
+    assert_eq!(rows[2].errors, ["Expected 2 columns, found 1"]);
+
+    assert!(parse_testchecks_csv("title\nA\n", &HashMap::new()).is_err());
+    assert!(parse_testchecks_csv("name\nA\n", &HashMap::from([("owner".to_string(), "Owner".to_string())])).is_err());
+  }
+}
@@ -1,8 +1,9 @@
+use bson::oid::ObjectId;
 use log::{error, warn};
-use rocket::{delete, get, put, routes, serde::json::Json, State};
-use crate::{testchecks::schema::TestcheckDto, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, users::{roles::is_admin, schema::User}};
+use rocket::{delete, get, post, put, routes, serde::json::Json, State};
+use crate::{testchecks::schema::TestcheckDto, testlists::schema::Testlist, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testrevisions::schema::{diff_testcheck_contents, TestcheckContent, Testrevision, TestrevisionChange}, testsections::{endpoints::check_testlist_testsection, schema::Testsection}, users::{roles::is_admin, schema::User}};
 
-use super::schema::Testcheck;
+use super::{schema::{Testcheck, TestcheckImportAction, TestcheckImportRow, TestcheckUpdateDto}, service::{find_automation_key_conflict, normalize_automation_keys}};
 
 #[get("/")]
 async fn get_testchecks(testcheck_repo: &State<MongoRepo<Testcheck>>) -> Result<Json<Vec<Testcheck>>, JsonError> {
@@ -28,34 +29,17 @@ async fn get_testcheck(jwt: Result<JWT, JsonError>, id: &str, testcheck_repo: &S
 
 
 #[put("/<id>", format = "json", data = "<data>")]
-async fn update_testcheck(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestcheckDto>, testcheck_repo: &State<MongoRepo<Testcheck>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testcheck>, JsonError> {
+async fn update_testcheck(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestcheckUpdateDto>, testcheck_repo: &State<MongoRepo<Testcheck>>, testsection_repo: &State<MongoRepo<Testsection>>, testrevision_repo: &State<MongoRepo<Testrevision>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testcheck>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
-  allowed_for_testcheck(jwts, testcheck_repo, id).await?;
+  let user_id = jwts.user.id;
+  let testcheck = allowed_for_testcheck(jwts, testcheck_repo, id).await?;
+  let mut data = data.into_inner().merge(&testcheck);
+  check_testlist_testsection(testsection_repo, testcheck.testlist_id, data.section_id.as_deref()).await?;
+  check_testcheck_automation_keys(testcheck_repo, testcheck.testlist_id, Some(testcheck.id), &mut data).await?;
 
-  match testcheck_repo.update_testcheck(id.to_string(), data.into_inner()).await {
-    Ok(updated) => {
-      if updated.modified_count == 0 {
-        warn!("Testcheck not found: {}", id);
-        return Err(JsonError::NotFound("Testcheck not found".to_string()));
-      }
-      match testcheck_repo.get_testcheck_by_id(id).await {
-        Ok(testcheck) => match testcheck {
-          Some(testcheck) => Ok(Json(testcheck)),
-          None => {
-            warn!("Testcheck not found: {}", id);
-            Err(JsonError::NotFound("Testcheck not found".to_string()))
-          },
-        },
-        Err(e) => {
-          error!("Error getting testcheck: {}", e);
-          Err(JsonError::Internal("Error getting testcheck".to_string()))
-        },
-      }
-    },
-    Err(e) => {
-      error!("Error updating testcheck: {}", e);
-      Err(JsonError::Internal("Error updating testcheck".to_string()))
-    },
+  match save_testcheck(testcheck_repo, testrevision_repo, testcheck, data, user_id).await {
+    Ok(testcheck) => Ok(Json(testcheck)),
+    Err(e) => Err(e)
   }
 }
 
@@ -82,8 +66,199 @@ async fn delete_testcheck(jwt: Result<JWT, JsonError>, id: &str, testcheck_repo:
   }
 }
 
+#[get("/<testcheck_id>/revisions")]
+async fn get_testcheck_revisions(jwt: Result<JWT, JsonError>, testcheck_id: &str, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Vec<Testrevision>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_testcheck(jwts, testcheck_repo, testcheck_id).await?;
+
+  match testrevision_repo.get_testcheck_testrevisions(testcheck_id).await {
+    Ok(testrevisions) => Ok(Json(testrevisions)),
+    Err(e) => {
+      error!("Error getting testrevisions: {}", e);
+      Err(JsonError::Internal("Error getting testrevisions".to_string()))
+    },
+  }
+}
+
+#[get("/<testcheck_id>/revisions/<revision>")]
+async fn get_testcheck_revision(jwt: Result<JWT, JsonError>, testcheck_id: &str, revision: u32, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testrevision>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_testcheck(jwts, testcheck_repo, testcheck_id).await?;
+
+  match get_testrevision(testrevision_repo, testcheck_id, revision).await {
+    Ok(testrevision) => Ok(Json(testrevision)),
+    Err(e) => Err(e)
+  }
+}
+
+#[get("/<testcheck_id>/revisions/<from>/diff/<to>")]
+async fn diff_testcheck_revisions(jwt: Result<JWT, JsonError>, testcheck_id: &str, from: u32, to: u32, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Vec<TestrevisionChange>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_testcheck(jwts, testcheck_repo, testcheck_id).await?;
+
+  let from = get_testrevision(testrevision_repo, testcheck_id, from).await?;
+  let to = get_testrevision(testrevision_repo, testcheck_id, to).await?;
+  Ok(Json(diff_testcheck_contents(&from.content, &to.content)))
+}
+
+#[post("/<testcheck_id>/revisions/<revision>/restore")]
+async fn restore_testcheck_revision(jwt: Result<JWT, JsonError>, testcheck_id: &str, revision: u32, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testcheck>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let user_id = jwts.user.id;
+  let testcheck = allowed_for_testcheck(jwts, testcheck_repo, testcheck_id).await?;
+
+  let testrevision = get_testrevision(testrevision_repo, testcheck_id, revision).await?;
+  let data = TestcheckDto {
+    name: testrevision.content.name,
+    description: testrevision.content.description,
+    expected: testrevision.content.expected,
+    tags: testrevision.content.tags,
+    section_id: testcheck.section_id.map(|section_id| section_id.to_hex()),
+    external_key: testcheck.external_key.clone(),
+    automation_key: testcheck.automation_key.clone(),
+    automation_aliases: testcheck.automation_aliases.clone(),
+    mode: testcheck.mode,
+  };
+
+  match save_testcheck(testcheck_repo, testrevision_repo, testcheck, data, user_id).await {
+    Ok(testcheck) => Ok(Json(testcheck)),
+    Err(e) => Err(e)
+  }
+}
+
 pub fn get_testchecks_routes() -> Vec<rocket::Route> {
-  routes![get_testchecks, get_testcheck, update_testcheck, delete_testcheck]
+  routes![get_testchecks, get_testcheck, update_testcheck, delete_testcheck, get_testcheck_revisions, get_testcheck_revision, diff_testcheck_revisions, restore_testcheck_revision]
+}
+
+pub async fn create_initial_testrevision(testrevision_repo: &State<MongoRepo<Testrevision>>, testcheck: &Testcheck, author_id: ObjectId) -> Result<(), JsonError> {
+  let changes = diff_testcheck_contents(&TestcheckContent::default(), &testcheck.into());
+  match testrevision_repo.create_testrevision(testcheck, Some(author_id), changes).await {
+    Ok(_) => Ok(()),
+    Err(e) => {
+      error!("Error creating testrevision: {}", e);
+      Err(JsonError::Internal("Error creating testrevision".to_string()))
+    },
+  }
+}
+
+// Automation keys identify a single check of the testlist
+pub async fn check_testcheck_automation_keys(testcheck_repo: &State<MongoRepo<Testcheck>>, testlist_id: ObjectId, testcheck_id: Option<ObjectId>, data: &mut TestcheckDto) -> Result<(), JsonError> {
+  normalize_automation_keys(data);
+  if data.automation_key.is_none() && data.automation_aliases.is_empty() {
+    return Ok(());
+  }
+  let testchecks = match testcheck_repo.get_testlist_testchecks(&testlist_id.to_hex()).await {
+    Ok(testchecks) => testchecks,
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      return Err(JsonError::Internal("Error getting testchecks".to_string()));
+    },
+  };
+  if let Some((key, testcheck)) = find_automation_key_conflict(data.automation_keys(), testcheck_id, &testchecks) {
+    return Err(JsonError::BadRequest(format!("Automation key {} is already used by testcheck {}", key, testcheck.name)));
+  }
+  Ok(())
+}
+
+// Updates the check, recording a new revision when its content changes
+pub async fn save_testcheck(testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, testcheck: Testcheck, data: TestcheckDto, author_id: ObjectId) -> Result<Testcheck, JsonError> {
+  let id = testcheck.id.to_hex();
+  let changes = diff_testcheck_contents(&(&testcheck).into(), &(&data).into());
+
+  let mut revision = testcheck.revision;
+  if revision == 0 {
+    // Checks created before revisions were recorded get a baseline first
+    let baseline = Testcheck { revision: 1, ..testcheck.clone() };
+    let baseline_changes = diff_testcheck_contents(&TestcheckContent::default(), &(&baseline).into());
+    if let Err(e) = testrevision_repo.create_testrevision(&baseline, None, baseline_changes).await {
+      error!("Error creating testrevision: {}", e);
+      return Err(JsonError::Internal("Error creating testrevision".to_string()));
+    }
+    revision = 1;
+  }
+  if !changes.is_empty() {
+    revision += 1;
+  }
+
+  if let Err(e) = testcheck_repo.update_testcheck(id.clone(), data, revision).await {
+    error!("Error updating testcheck: {}", e);
+    return Err(JsonError::Internal("Error updating testcheck".to_string()));
+  }
+
+  let testcheck = match testcheck_repo.get_testcheck_by_id(&id).await {
+    Ok(Some(testcheck)) => testcheck,
+    Ok(None) => {
+      warn!("Testcheck not found: {}", id);
+      return Err(JsonError::NotFound("Testcheck not found".to_string()));
+    },
+    Err(e) => {
+      error!("Error getting testcheck: {}", e);
+      return Err(JsonError::Internal("Error getting testcheck".to_string()));
+    },
+  };
+
+  if !changes.is_empty() {
+    if let Err(e) = testrevision_repo.create_testrevision(&testcheck, Some(author_id), changes).await {
+      error!("Error creating testrevision: {}", e);
+      return Err(JsonError::Internal("Error creating testrevision".to_string()));
+    }
+  }
+  Ok(testcheck)
+}
+
+// Saves the planned creations and updates of an import, other rows are left untouched
+pub async fn apply_testchecks_import(testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>, testlist: &Testlist, imports: Vec<(TestcheckImportRow, TestcheckDto, Option<Testcheck>)>, author_id: ObjectId) -> Result<Vec<TestcheckImportRow>, JsonError> {
+  let account_id = testlist.account_id.to_hex();
+  let project_id = testlist.project_id.to_hex();
+  let testlist_id = testlist.id.to_hex();
+  let mut rows = Vec::new();
+  for (mut row, data, testcheck) in imports {
+    match (row.action, testcheck) {
+      (TestcheckImportAction::Create, _) => {
+        let id = match testcheck_repo.create_testcheck(&account_id, &project_id, &testlist_id, data).await {
+          Ok(inserted) => inserted.inserted_id.as_object_id().unwrap(),
+          Err(e) => {
+            error!("Error creating testcheck: {}", e);
+            return Err(JsonError::Internal("Error creating testcheck".to_string()));
+          },
+        };
+        match testcheck_repo.get_testcheck_by_id(&id.to_hex()).await {
+          Ok(Some(testcheck)) => create_initial_testrevision(testrevision_repo, &testcheck, author_id).await?,
+          Ok(None) => {
+            warn!("Testcheck not found: {}", id);
+            return Err(JsonError::NotFound("Testcheck not found".to_string()));
+          },
+          Err(e) => {
+            error!("Error getting testcheck: {}", e);
+            return Err(JsonError::Internal("Error getting testcheck".to_string()));
+          },
+        }
+        row.testcheck_id = Some(id);
+      },
+      (TestcheckImportAction::Update, Some(testcheck)) => {
+        save_testcheck(testcheck_repo, testrevision_repo, testcheck, data, author_id).await?;
+      },
+      _ => {},
+    }
+    rows.push(row);
+  }
+  Ok(rows)
+}
+
+async fn get_testrevision(testrevision_repo: &State<MongoRepo<Testrevision>>, testcheck_id: &str, revision: u32) -> Result<Testrevision, JsonError> {
+  match testrevision_repo.get_testcheck_testrevision(testcheck_id, revision).await {
+    Ok(testrevision) => match testrevision {
+      Some(testrevision) => Ok(testrevision),
+      None => {
+        warn!("Testrevision not found: {} {}", testcheck_id, revision);
+        Err(JsonError::NotFound("Testrevision not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testrevision: {}", e);
+      Err(JsonError::Internal("Error getting testrevision".to_string()))
+    },
+  }
 }
 
 async fn allowed_for_testcheck(jwts: JWTSessionAndUser, testcheck_repo: &State<MongoRepo<Testcheck>>, testcheck_id: &str) -> Result<Testcheck, JsonError>  {
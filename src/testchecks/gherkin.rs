@@ -0,0 +1,367 @@
+// Cucumber `.feature` files, in English keywords. Each scenario, and each example row of a
+// scenario outline, becomes a testcheck: Given/When steps are its description, Then steps its
+// expected result. Background steps are prepended to every scenario.
+
+use super::schema::{TestcheckDto, TestcheckMode};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GherkinStepKind {
+  Context,
+  Outcome,
+}
+
+#[derive(Debug, Clone)]
+struct GherkinStep {
+  kind: GherkinStepKind,
+  keyword: String,
+  text: String,
+  // Data table rows and doc string lines
+  arguments: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct GherkinExamples {
+  tags: Vec<String>,
+  header: Vec<String>,
+  rows: Vec<(usize, Vec<String>)>,
+}
+
+#[derive(Debug, Clone)]
+struct GherkinScenario {
+  line: usize,
+  name: String,
+  rule: Option<String>,
+  tags: Vec<String>,
+  outline: bool,
+  steps: Vec<GherkinStep>,
+  examples: Vec<GherkinExamples>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GherkinFeature {
+  pub name: String,
+  pub description: String,
+  tags: Vec<String>,
+  scenarios: Vec<GherkinScenario>,
+}
+
+// A testcheck of the feature, its key identifies the scenario across imports
+#[derive(Debug, Clone)]
+pub struct GherkinTestcheck {
+  pub line: usize,
+  pub testcheck: TestcheckDto,
+}
+
+enum Block {
+  None,
+  Background,
+  Scenario,
+  Examples,
+}
+
+impl GherkinFeature {
+  pub fn parse(content: &str) -> Result<GherkinFeature, String> {
+    let mut feature: Option<GherkinFeature> = None;
+    let mut pending_tags: Vec<String> = Vec::new();
+    let mut rule: Option<(String, Vec<String>)> = None;
+    let mut feature_background: Vec<GherkinStep> = Vec::new();
+    let mut rule_background: Vec<GherkinStep> = Vec::new();
+    let mut block = Block::None;
+    // Delimiter and indentation of the open doc string
+    let mut doc_string: Option<(String, usize)> = None;
+
+    for (index, raw) in content.lines().enumerate() {
+      let line_number = index + 1;
+      let line = raw.trim();
+
+      if let Some((delimiter, indent)) = &doc_string {
+        if line == delimiter {
+          doc_string = None;
+        } else if let Some(step) = current_steps(&mut feature, &mut feature_background, &mut rule_background, &rule, &block) {
+          if let Some(step) = step.last_mut() {
+            let unindented = raw.char_indices().find(|(i, c)| *i >= *indent || !c.is_whitespace()).map(|(i, _)| &raw[i..]).unwrap_or("");
+            step.arguments.push(unindented.trim_end().to_string());
+          }
+        }
+        continue;
+      }
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      if line.starts_with('@') {
+        let tags = line.split('#').next().unwrap_or_default();
+        pending_tags.extend(tags.split_whitespace().map(|tag| tag.trim_start_matches('@').to_string()).filter(|tag| !tag.is_empty()));
+        continue;
+      }
+
+      if let Some(name) = keyword_value(line, &["Feature"]) {
+        if feature.is_some() {
+          return Err(format!("Line {}: only one Feature is allowed per file", line_number));
+        }
+        feature = Some(GherkinFeature { name, tags: std::mem::take(&mut pending_tags), ..Default::default() });
+        block = Block::None;
+        continue;
+      }
+      let current = match feature.as_mut() {
+        Some(feature) => feature,
+        None => return Err(format!("Line {}: expected a Feature", line_number)),
+      };
+
+      if let Some(name) = keyword_value(line, &["Rule"]) {
+        rule = Some((name, std::mem::take(&mut pending_tags)));
+        rule_background.clear();
+        block = Block::None;
+      } else if keyword_value(line, &["Background"]).is_some() {
+        block = Block::Background;
+      } else if let Some(name) = keyword_value(line, &["Scenario Outline", "Scenario Template"]) {
+        current.scenarios.push(new_scenario(line_number, name, &rule, std::mem::take(&mut pending_tags), true, &feature_background, &rule_background));
+        block = Block::Scenario;
+      } else if let Some(name) = keyword_value(line, &["Scenario", "Example"]) {
+        current.scenarios.push(new_scenario(line_number, name, &rule, std::mem::take(&mut pending_tags), false, &feature_background, &rule_background));
+        block = Block::Scenario;
+      } else if keyword_value(line, &["Examples", "Scenarios"]).is_some() {
+        match current.scenarios.last_mut() {
+          Some(scenario) if scenario.outline => {
+            scenario.examples.push(GherkinExamples { tags: std::mem::take(&mut pending_tags), ..Default::default() });
+            block = Block::Examples;
+          },
+          _ => return Err(format!("Line {}: Examples outside of a Scenario Outline", line_number)),
+        }
+      } else if line.starts_with('|') {
+        let cells = table_cells(line);
+        if let Block::Examples = block {
+          let examples = current.scenarios.last_mut().and_then(|scenario| scenario.examples.last_mut()).unwrap();
+          if examples.header.is_empty() {
+            examples.header = cells;
+          } else if cells.len() != examples.header.len() {
+            return Err(format!("Line {}: expected {} cells, found {}", line_number, examples.header.len(), cells.len()));
+          } else {
+            examples.rows.push((line_number, cells));
+          }
+        } else {
+          match current_steps(&mut feature, &mut feature_background, &mut rule_background, &rule, &block).and_then(|steps| steps.last_mut()) {
+            Some(step) => step.arguments.push(format!("| {} |", cells.join(" | "))),
+            None => return Err(format!("Line {}: table outside of a step", line_number)),
+          }
+        }
+      } else if line.starts_with("\"\"\"") || line.starts_with("```") {
+        doc_string = Some((line[..3].to_string(), raw.len() - raw.trim_start().len()));
+      } else if let Some((keyword, text)) = step_keyword(line) {
+        let steps = match current_steps(&mut feature, &mut feature_background, &mut rule_background, &rule, &block) {
+          Some(steps) => steps,
+          None => return Err(format!("Line {}: step outside of a scenario", line_number)),
+        };
+        let kind = match keyword {
+          "Then" => GherkinStepKind::Outcome,
+          "And" | "But" | "*" => steps.last().map(|step| step.kind).unwrap_or(GherkinStepKind::Context),
+          _ => GherkinStepKind::Context,
+        };
+        steps.push(GherkinStep { kind, keyword: keyword.to_string(), text: text.to_string(), arguments: Vec::new() });
+      } else if let (Block::None, Some(feature)) = (&block, feature.as_mut()) {
+        if rule.is_none() && feature.scenarios.is_empty() {
+          if !feature.description.is_empty() {
+            feature.description.push('\n');
+          }
+          feature.description.push_str(line);
+        }
+      }
+    }
+
+    if doc_string.is_some() {
+      return Err("Unterminated doc string".to_string());
+    }
+    feature.ok_or_else(|| "No Feature found".to_string())
+  }
+
+  pub fn testchecks(&self) -> Vec<GherkinTestcheck> {
+    let mut testchecks = Vec::new();
+    for scenario in &self.scenarios {
+      let mut tags = self.tags.clone();
+      tags.extend(scenario.tags.iter().cloned());
+      if !scenario.outline {
+        testchecks.push(self.testcheck(scenario, scenario.line, scenario.name.clone(), &tags, &[]));
+        continue;
+      }
+      for examples in &scenario.examples {
+        let mut example_tags = tags.clone();
+        example_tags.extend(examples.tags.iter().cloned());
+        for (line, row) in &examples.rows {
+          let values: Vec<(&str, &str)> = examples.header.iter().map(|h| h.as_str()).zip(row.iter().map(|v| v.as_str())).collect();
+          let parameters = values.iter().map(|(name, value)| format!("{}: {}", name, value)).collect::<Vec<String>>().join(", ");
+          let name = format!("{} ({})", substitute(&scenario.name, &values), parameters);
+          testchecks.push(self.testcheck(scenario, *line, name, &example_tags, &values));
+        }
+      }
+    }
+    testchecks
+  }
+
+  fn testcheck(&self, scenario: &GherkinScenario, line: usize, name: String, tags: &[String], values: &[(&str, &str)]) -> GherkinTestcheck {
+    let steps_text = |kind: GherkinStepKind| scenario.steps.iter()
+      .filter(|step| step.kind == kind)
+      .map(|step| {
+        let mut text = format!("{} {}", step.keyword, substitute(&step.text, values));
+        for argument in &step.arguments {
+          text.push('\n');
+          text.push_str(&substitute(argument, values));
+        }
+        text
+      })
+      .collect::<Vec<String>>()
+      .join("\n");
+
+    let mut unique_tags: Vec<String> = Vec::new();
+    for tag in tags {
+      if !unique_tags.contains(tag) {
+        unique_tags.push(tag.clone());
+      }
+    }
+    let key = match &scenario.rule {
+      Some(rule) => format!("{} > {} > {}", self.name, rule, name),
+      None => format!("{} > {}", self.name, name),
+    };
+    GherkinTestcheck {
+      line,
+      testcheck: TestcheckDto {
+        name,
+        description: steps_text(GherkinStepKind::Context),
+        expected: steps_text(GherkinStepKind::Outcome),
+        tags: unique_tags,
+        section_id: None,
+        external_key: Some(key),
+        automation_key: None,
+        automation_aliases: Vec::new(),
+        mode: TestcheckMode::Manual,
+      },
+    }
+  }
+}
+
+fn new_scenario(line: usize, name: String, rule: &Option<(String, Vec<String>)>, tags: Vec<String>, outline: bool, feature_background: &[GherkinStep], rule_background: &[GherkinStep]) -> GherkinScenario {
+  let mut scenario_tags = rule.as_ref().map(|(_, tags)| tags.clone()).unwrap_or_default();
+  scenario_tags.extend(tags);
+  GherkinScenario {
+    line,
+    name,
+    rule: rule.as_ref().map(|(name, _)| name.clone()),
+    tags: scenario_tags,
+    outline,
+    steps: feature_background.iter().chain(rule_background.iter()).cloned().collect(),
+    examples: Vec::new(),
+  }
+}
+
+// Steps receiving the current line, those of the background or of the latest scenario
+fn current_steps<'a>(feature: &'a mut Option<GherkinFeature>, feature_background: &'a mut Vec<GherkinStep>, rule_background: &'a mut Vec<GherkinStep>, rule: &Option<(String, Vec<String>)>, block: &Block) -> Option<&'a mut Vec<GherkinStep>> {
+  match block {
+    Block::Background if rule.is_some() => Some(rule_background),
+    Block::Background => Some(feature_background),
+    Block::Scenario => feature.as_mut().and_then(|feature| feature.scenarios.last_mut()).map(|scenario| &mut scenario.steps),
+    _ => None,
+  }
+}
+
+fn keyword_value(line: &str, keywords: &[&str]) -> Option<String> {
+  keywords.iter().find_map(|keyword| {
+    line.strip_prefix(keyword)
+      .and_then(|rest| rest.trim_start().strip_prefix(':'))
+      .map(|value| value.trim().to_string())
+  })
+}
+
+fn step_keyword(line: &str) -> Option<(&str, &str)> {
+  ["Given", "When", "Then", "And", "But", "*"].iter().find_map(|keyword| {
+    line.strip_prefix(keyword)
+      .filter(|rest| rest.starts_with(' '))
+      .map(|rest| (*keyword, rest.trim()))
+  })
+}
+
+fn table_cells(line: &str) -> Vec<String> {
+  let inner = line.trim().trim_start_matches('|');
+  let inner = inner.strip_suffix('|').unwrap_or(inner);
+  inner.split('|').map(|cell| cell.trim().replace("\\n", "\n")).collect()
+}
+
+fn substitute(text: &str, values: &[(&str, &str)]) -> String {
+  let mut text = text.to_string();
+  for (name, value) in values {
+    text = text.replace(&format!("<{}>", name), value);
+  }
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FEATURE: &str = r#"@web
+Feature: Shopping cart
+  Customers collect products before paying.
+
+  Background:
+    Given a signed in customer
+
+  @smoke
+  Scenario: Add a product
+    When they add "Tea" to the cart
+    Then the cart shows 1 product
+    And the total is updated
+
+  Rule: Discounts
+
+    Background:
+      Given a discount code "SPRING"
+
+    Scenario Outline: Apply a discount on <count> products
+      When they add <count> products
+        """
+        Products of the spring catalog
+        """
+      Then the total is <total>
+
+      @regression
+      Examples:
+        | count | total |
+        | 1     | 9.00  |
+        | 3     | 27.00 |
+"#;
+
+  #[test]
+  fn parses_scenarios_with_background() {
+    let feature = GherkinFeature::parse(FEATURE).unwrap();
+    assert_eq!(feature.name, "Shopping cart");
+    assert_eq!(feature.description, "Customers collect products before paying.");
+
+    let testchecks = feature.testchecks();
+    assert_eq!(testchecks.len(), 3);
+    let testcheck = &testchecks[0].testcheck;
+    assert_eq!(testchecks[0].line, 9);
+    assert_eq!(testcheck.name, "Add a product");
+    assert_eq!(testcheck.description, "Given a signed in customer\nWhen they add \"Tea\" to the cart");
+    assert_eq!(testcheck.expected, "Then the cart shows 1 product\nAnd the total is updated");
+    assert_eq!(testcheck.tags, ["web", "smoke"]);
+    assert_eq!(testcheck.external_key.as_deref(), Some("Shopping cart > Add a product"));
+  }
+
+  #[test]
+  fn expands_outline_examples_under_their_rule() {
+    let testchecks = GherkinFeature::parse(FEATURE).unwrap().testchecks();
+    let testcheck = &testchecks[2].testcheck;
+    assert_eq!(testchecks[2].line, 30);
+    assert_eq!(testcheck.name, "Apply a discount on 3 products (count: 3, total: 27.00)");
+    assert_eq!(testcheck.description, "Given a signed in customer\nGiven a discount code \"SPRING\"\nWhen they add 3 products\nProducts of the spring catalog");
+    assert_eq!(testcheck.expected, "Then the total is 27.00");
+    assert_eq!(testcheck.tags, ["web", "regression"]);
+    assert_eq!(testcheck.external_key.as_deref(), Some("Shopping cart > Discounts > Apply a discount on 3 products (count: 3, total: 27.00)"));
+  }
+
+  #[test]
+  fn rejects_malformed_features() {
+    assert!(GherkinFeature::parse("Scenario: Orphan\n  Given nothing").is_err());
+    assert!(GherkinFeature::parse("Feature: A\nFeature: B").is_err());
+    assert!(GherkinFeature::parse("Feature: A\n  Scenario: B\n    Examples:\n").is_err());
+    assert!(GherkinFeature::parse("Feature: A\n  Scenario Outline: B\n    Examples:\n      | a | b |\n      | 1 |\n").is_err());
+    assert!(GherkinFeature::parse("Feature: A\n  Scenario: B\n    Given a\n      \"\"\"\n      never closed\n").is_err());
+  }
+}
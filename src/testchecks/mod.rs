@@ -1,3 +1,6 @@
+pub mod csv;
 pub mod endpoints;
+pub mod gherkin;
 pub mod schema;
 pub mod service;
+pub mod tags;
@@ -1,8 +1,10 @@
 use bson::DateTime;
 use mongodb::bson::oid::ObjectId;
+use std::collections::HashMap;
+
 use rocket::serde::{Deserialize, Serialize};
 
-use crate::service::db::{serialize_datetime, serialize_object_id};
+use crate::service::db::{deserialize_some, serialize_datetime, serialize_object_id, serialize_option_object_id};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Testcheck {
@@ -10,6 +12,9 @@ pub struct Testcheck {
   pub id: ObjectId,
   #[serde(serialize_with = "serialize_object_id")]
   pub testlist_id: ObjectId,
+  // None for checks at the root of the testlist
+  #[serde(default, serialize_with = "serialize_option_object_id")]
+  pub section_id: Option<ObjectId>,
   #[serde(serialize_with = "serialize_object_id")]
   pub project_id: ObjectId,
   #[serde(serialize_with = "serialize_object_id")]
@@ -18,7 +23,21 @@ pub struct Testcheck {
   pub description: String,
   pub expected: String,
   pub tags: Vec<String>,
+  // Identifier of the check in an external tool, used to upsert on import
+  #[serde(default)]
+  pub external_key: Option<String>,
+  // Identifier of the automated test, matched by the ingestion of automated results
+  #[serde(default)]
+  pub automation_key: Option<String>,
+  // Former identifiers of the automated test, kept matching after a rename
+  #[serde(default)]
+  pub automation_aliases: Vec<String>,
+  #[serde(default)]
+  pub mode: TestcheckMode,
   pub position: u16,
+  // 0 until the first revision is recorded
+  #[serde(default)]
+  pub revision: u32,
   #[serde(serialize_with = "serialize_datetime")]
   pub created_at: DateTime,
   #[serde(serialize_with = "serialize_datetime")]
@@ -31,4 +50,173 @@ pub struct TestcheckDto {
   pub description: String,
   pub expected: String,
   pub tags: Vec<String>,
+  #[serde(default)]
+  pub section_id: Option<String>,
+  #[serde(default)]
+  pub external_key: Option<String>,
+  #[serde(default)]
+  pub automation_key: Option<String>,
+  #[serde(default)]
+  pub automation_aliases: Vec<String>,
+  #[serde(default)]
+  pub mode: TestcheckMode,
+}
+
+// Edit of a testcheck, fields left out keep their stored value
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestcheckUpdateDto {
+  pub name: String,
+  pub description: String,
+  pub expected: String,
+  pub tags: Vec<String>,
+  // null moves the check to the root of the testlist
+  #[serde(default, deserialize_with = "deserialize_some")]
+  pub section_id: Option<Option<String>>,
+  #[serde(default, deserialize_with = "deserialize_some")]
+  pub external_key: Option<Option<String>>,
+  #[serde(default, deserialize_with = "deserialize_some")]
+  pub automation_key: Option<Option<String>>,
+  #[serde(default)]
+  pub automation_aliases: Option<Vec<String>>,
+  #[serde(default)]
+  pub mode: Option<TestcheckMode>,
+}
+
+impl TestcheckUpdateDto {
+  pub fn merge(self, testcheck: &Testcheck) -> TestcheckDto {
+    TestcheckDto {
+      name: self.name,
+      description: self.description,
+      expected: self.expected,
+      tags: self.tags,
+      section_id: self.section_id.unwrap_or_else(|| testcheck.section_id.map(|section_id| section_id.to_hex())),
+      external_key: self.external_key.unwrap_or_else(|| testcheck.external_key.clone()),
+      automation_key: self.automation_key.unwrap_or_else(|| testcheck.automation_key.clone()),
+      automation_aliases: self.automation_aliases.unwrap_or_else(|| testcheck.automation_aliases.clone()),
+      mode: self.mode.unwrap_or(testcheck.mode),
+    }
+  }
+}
+
+impl Testcheck {
+  // Automation key first, then its aliases
+  pub fn automation_keys(&self) -> impl Iterator<Item = &String> {
+    self.automation_key.iter().chain(self.automation_aliases.iter())
+  }
+}
+
+impl TestcheckDto {
+  pub fn automation_keys(&self) -> impl Iterator<Item = &String> {
+    self.automation_key.iter().chain(self.automation_aliases.iter())
+  }
+}
+
+// How the check is run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TestcheckMode {
+  #[default]
+  Manual,
+  Automated,
+  Both,
+}
+
+impl std::fmt::Display for TestcheckMode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TestcheckMode::Manual => write!(f, "manual"),
+      TestcheckMode::Automated => write!(f, "automated"),
+      TestcheckMode::Both => write!(f, "both"),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestchecksTransferDto {
+  pub testchecks_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestchecksImportDto {
+  pub csv: String,
+  // Validates the rows without saving anything
+  #[serde(default)]
+  pub dry_run: bool,
+  // Field name to CSV column header, columns named after the fields are used otherwise
+  #[serde(default)]
+  pub mapping: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestcheckImportAction {
+  Create,
+  Update,
+  Unchanged,
+  Invalid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestcheckImportRow {
+  // CSV record number, the header being 1, or line of the imported scenario
+  pub row: usize,
+  pub action: TestcheckImportAction,
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub testcheck_id: Option<ObjectId>,
+  pub name: String,
+  pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestchecksImportResult {
+  pub dry_run: bool,
+  // False when any row is invalid, nothing is saved then
+  pub imported: bool,
+  pub created: u32,
+  pub updated: u32,
+  pub unchanged: u32,
+  pub invalid: u32,
+  pub rows: Vec<TestcheckImportRow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GherkinFileDto {
+  pub filename: String,
+  pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GherkinImportDto {
+  pub files: Vec<GherkinFileDto>,
+  // Testlist receiving every scenario, unless each feature gets its own testlist
+  #[serde(default)]
+  pub testlist_id: Option<String>,
+  // Features are imported into the project testlist of the same name, created when missing
+  #[serde(default)]
+  pub testlist_per_feature: bool,
+  #[serde(default)]
+  pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GherkinImportTestlist {
+  // None for a testlist still to be created by a dry run
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub testlist_id: Option<ObjectId>,
+  pub name: String,
+  pub filenames: Vec<String>,
+  pub created: bool,
+  pub rows: Vec<TestcheckImportRow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GherkinImportResult {
+  pub dry_run: bool,
+  // False when any scenario is invalid, nothing is saved then
+  pub imported: bool,
+  pub created: u32,
+  pub updated: u32,
+  pub unchanged: u32,
+  pub invalid: u32,
+  pub testlists: Vec<GherkinImportTestlist>,
 }
@@ -1,11 +1,11 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error};
 
 use bson::DateTime;
 use mongodb::{
   bson::{self, doc, oid::ObjectId}, options::{FindOneOptions, FindOptions}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
 };
-use crate::service::db::{ self, MongoRepo};
-use super::schema::{Testcheck, TestcheckDto};
+use crate::{service::db::{ self, MongoRepo}, testlists::schema::Testlist};
+use super::schema::{Testcheck, TestcheckDto, TestcheckImportAction, TestcheckImportRow};
 use rocket::futures::TryStreamExt;
 
 pub fn get_testchecks_repo(client: Client) -> MongoRepo<Testcheck> {
@@ -27,6 +27,13 @@ impl MongoRepo<Testcheck> {
     Ok(testchecks)
   }
 
+  pub async fn get_project_testchecks(&self, project_id: &str) -> Result<Vec<Testcheck>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "project_id": ObjectId::parse_str(project_id)? };
+    let cursor = self.col.find(filter, None).await?;
+    let testchecks: Vec<Testcheck> = cursor.try_collect().await?;
+    Ok(testchecks)
+  }
+
   pub async fn get_testcheck_by_id(&self, id: &str) -> Result<Option<Testcheck>, Box<dyn Error + Send + Sync>> {
     let oid = ObjectId::parse_str(id)?;
     let filter = doc! { "_id": oid };
@@ -34,8 +41,23 @@ impl MongoRepo<Testcheck> {
     Ok(result)
   }
 
-  async fn get_next_position(&self, testlist_id: &str) -> Result<u16, Box<dyn Error + Send + Sync>> {
-    let filter = doc! { "testlist_id": ObjectId::parse_str(testlist_id)? };
+  pub async fn get_testchecks_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Testcheck>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": { "$in": ids } };
+    let cursor = self.col.find(filter, None).await?;
+    let testchecks: Vec<Testcheck> = cursor.try_collect().await?;
+    Ok(testchecks)
+  }
+
+  pub async fn get_testsection_testchecks(&self, section_id: &str) -> Result<Vec<Testcheck>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "section_id": ObjectId::parse_str(section_id)? };
+    let options = FindOptions::builder().sort(doc! { "position": 1 }).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testchecks: Vec<Testcheck> = cursor.try_collect().await?;
+    Ok(testchecks)
+  }
+
+  pub async fn get_next_position(&self, testlist_id: &str, section_id: Option<ObjectId>) -> Result<u16, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testlist_id": ObjectId::parse_str(testlist_id)?, "section_id": section_id };
     let options = FindOneOptions::builder()
       .sort(doc! { "position": -1 })
       .build();
@@ -47,18 +69,25 @@ impl MongoRepo<Testcheck> {
   }
 
   pub async fn create_testcheck(&self, account_id: &str, project_id: &str, testlist_id: &str, data: TestcheckDto) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
-    let position = self.get_next_position(testlist_id).await?;
+    let section_id = data.section_id.as_deref().map(ObjectId::parse_str).transpose()?;
+    let position = self.get_next_position(testlist_id, section_id).await?;
     let now = DateTime::from_chrono(chrono::Utc::now());
     let new_doc = Testcheck {
       id: ObjectId::new(),
       account_id: ObjectId::parse_str(account_id)?,
       project_id: ObjectId::parse_str(project_id)?,
       testlist_id: ObjectId::parse_str(testlist_id)?,
+      section_id,
       name: data.name,
       description: data.description,
       expected: data.expected,
       tags: data.tags,
+      external_key: data.external_key,
+      automation_key: data.automation_key,
+      automation_aliases: data.automation_aliases,
+      mode: data.mode,
       position,
+      revision: 1,
       created_at: now,
       updated_at: now,
     };
@@ -66,16 +95,31 @@ impl MongoRepo<Testcheck> {
     Ok(result)
   }
 
-  pub async fn update_testcheck(&self, id: String, data: TestcheckDto) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+  pub async fn update_testcheck(&self, id: String, data: TestcheckDto, revision: u32) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
     let now = chrono::Utc::now();
+    let section_id = data.section_id.as_deref().map(ObjectId::parse_str).transpose()?;
     let filter = doc! { "_id": ObjectId::parse_str(&id)? };
-    let upd_doc = doc! { "$set": {
+    let mut set_doc = doc! {
       "name": data.name,
       "description": data.description,
       "expected": data.expected,
       "tags": data.tags,
+      "external_key": data.external_key,
+      "automation_key": data.automation_key,
+      "automation_aliases": data.automation_aliases,
+      "mode": bson::to_bson(&data.mode)?,
+      "section_id": section_id,
+      "revision": revision,
       "updated_at": DateTime::from_chrono(now)
-    } };
+    };
+    // Checks moved to another section are appended at its end
+    if let Some(testcheck) = self.get_testcheck_by_id(&id).await? {
+      if testcheck.section_id != section_id {
+        let position = self.get_next_position(&testcheck.testlist_id.to_hex(), section_id).await?;
+        set_doc.insert("position", position as i32);
+      }
+    }
+    let upd_doc = doc! { "$set": set_doc };
     let result = self.col.update_one(filter, upd_doc, None).await?;
     Ok(result)
   }
@@ -93,10 +137,10 @@ impl MongoRepo<Testcheck> {
   }
 
   pub async fn update_testlist_testchecks_positions(&self, testlist_id: &str, testchecks_ids: Vec<String>) -> Result<i32, Box<dyn Error + Send + Sync>> {
-    let mut position = 1;
     let mut updates = 0;
-    for id in testchecks_ids {
-      let filter = doc! { "_id": ObjectId::parse_str(&id)?, "testlist_id": ObjectId::parse_str(testlist_id)? };
+    for (index, id) in testchecks_ids.iter().enumerate() {
+      let position = index as i32 + 1;
+      let filter = doc! { "_id": ObjectId::parse_str(id)?, "testlist_id": ObjectId::parse_str(testlist_id)? };
       let upd_doc = doc! { "$set": {
         "position": position
       } };
@@ -104,8 +148,299 @@ impl MongoRepo<Testcheck> {
       if result.modified_count > 0 {
         updates += 1;
       }
-      position += 1;
     }
     Ok(updates)
   }
+
+  // Listed checks first in the given order, then the other checks of the section, numbered from 1
+  pub async fn update_testsection_testchecks_positions(&self, section_id: ObjectId, testchecks_ids: &[ObjectId]) -> Result<i32, Box<dyn Error + Send + Sync>> {
+    let mut testchecks = self.get_testsection_testchecks(&section_id.to_hex()).await?;
+    testchecks.sort_by_key(|testcheck| testchecks_ids.iter().position(|id| *id == testcheck.id).unwrap_or(testchecks_ids.len()));
+    let mut updates = 0;
+    for (index, testcheck) in testchecks.iter().enumerate() {
+      let position = index as u16 + 1;
+      if testcheck.position == position {
+        continue;
+      }
+      let filter = doc! { "_id": testcheck.id, "section_id": section_id };
+      let upd_doc = doc! { "$set": {
+        "position": position as i32
+      } };
+      let result = self.col.update_one(filter, upd_doc, None).await?;
+      if result.modified_count > 0 {
+        updates += 1;
+      }
+    }
+    Ok(updates)
+  }
+
+  pub async fn move_testcheck(&self, testcheck: &Testcheck, testlist: &Testlist, position: u16) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let now = chrono::Utc::now();
+    let filter = doc! { "_id": testcheck.id };
+    let upd_doc = doc! { "$set": {
+      "testlist_id": testlist.id,
+      "section_id": None::<ObjectId>,
+      "project_id": testlist.project_id,
+      "account_id": testlist.account_id,
+      "position": position as i32,
+      "updated_at": DateTime::from_chrono(now)
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn copy_testcheck(&self, testcheck: Testcheck, testlist: &Testlist, position: u16) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let new_doc = Testcheck {
+      id: ObjectId::new(),
+      account_id: testlist.account_id,
+      project_id: testlist.project_id,
+      testlist_id: testlist.id,
+      section_id: None,
+      // Automated results keep matching the original check
+      automation_key: None,
+      automation_aliases: Vec::new(),
+      position,
+      revision: 1,
+      created_at: now,
+      updated_at: now,
+      ..testcheck
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn reparent_testchecks(&self, section_id: ObjectId, new_section_id: Option<ObjectId>) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "section_id": section_id };
+    let upd_doc = doc! { "$set": {
+      "section_id": new_section_id
+    } };
+    let result = self.col.update_many(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  // Positions are numbered from 1 within each section of the testlist
+  pub async fn renumber_testlist_testchecks(&self, testlist_id: &str) -> Result<i32, Box<dyn Error + Send + Sync>> {
+    let testchecks = self.get_testlist_testchecks(testlist_id).await?;
+    let mut positions: HashMap<Option<ObjectId>, u16> = HashMap::new();
+    let mut updates = 0;
+    for testcheck in testchecks {
+      let position = positions.entry(testcheck.section_id).or_insert(0);
+      *position += 1;
+      if testcheck.position == *position {
+        continue;
+      }
+      let filter = doc! { "_id": testcheck.id };
+      let upd_doc = doc! { "$set": {
+        "position": *position as i32
+      } };
+      let result = self.col.update_one(filter, upd_doc, None).await?;
+      if result.modified_count > 0 {
+        updates += 1;
+      }
+    }
+    Ok(updates)
+  }
+}
+
+// Matches the imported checks with the testlist ones by external key, a key imported twice is invalid
+pub fn plan_testchecks_import(imports: Vec<(usize, TestcheckDto)>, testchecks: &[Testcheck]) -> Vec<(TestcheckImportRow, TestcheckDto, Option<Testcheck>)> {
+  let mut keys: Vec<String> = Vec::new();
+  imports.into_iter().map(|(row, data)| {
+    let mut errors = Vec::new();
+    if data.name.trim().is_empty() {
+      errors.push("Name is required".to_string());
+    }
+    let testcheck = match &data.external_key {
+      Some(key) if keys.contains(key) => {
+        errors.push(format!("Duplicate key {}", key));
+        None
+      },
+      Some(key) => {
+        keys.push(key.clone());
+        testchecks.iter().find(|testcheck| testcheck.external_key.as_ref() == Some(key))
+      },
+      None => None,
+    };
+    // Updated checks stay in their section and keep their automated test identity
+    let data = match testcheck {
+      Some(testcheck) => TestcheckDto {
+        section_id: testcheck.section_id.map(|section_id| section_id.to_hex()),
+        automation_key: testcheck.automation_key.clone(),
+        automation_aliases: testcheck.automation_aliases.clone(),
+        mode: testcheck.mode,
+        ..data
+      },
+      None => TestcheckDto { section_id: None, ..data },
+    };
+    let action = match testcheck {
+      _ if !errors.is_empty() => TestcheckImportAction::Invalid,
+      Some(testcheck) if is_testcheck_unchanged(testcheck, &data) => TestcheckImportAction::Unchanged,
+      Some(_) => TestcheckImportAction::Update,
+      None => TestcheckImportAction::Create,
+    };
+    let result = TestcheckImportRow {
+      row,
+      action,
+      testcheck_id: testcheck.map(|testcheck| testcheck.id),
+      name: data.name.clone(),
+      errors,
+    };
+    (result, data, testcheck.cloned())
+  }).collect()
+}
+
+pub fn is_testcheck_unchanged(testcheck: &Testcheck, data: &TestcheckDto) -> bool {
+  testcheck.name == data.name
+    && testcheck.description == data.description
+    && testcheck.expected == data.expected
+    && testcheck.tags == data.tags
+    && testcheck.external_key == data.external_key
+    && testcheck.automation_key == data.automation_key
+    && testcheck.automation_aliases == data.automation_aliases
+    && testcheck.mode == data.mode
+}
+
+// Trims the automation keys, empty ones and aliases repeating a previous key are dropped
+pub fn normalize_automation_keys(data: &mut TestcheckDto) {
+  data.automation_key = data.automation_key.as_deref().map(str::trim).filter(|key| !key.is_empty()).map(str::to_string);
+  let mut aliases: Vec<String> = Vec::new();
+  for alias in &data.automation_aliases {
+    let alias = alias.trim();
+    if !alias.is_empty() && data.automation_key.as_deref() != Some(alias) && !aliases.iter().any(|previous| previous == alias) {
+      aliases.push(alias.to_string());
+    }
+  }
+  data.automation_aliases = aliases;
+}
+
+// Another check of the testlist already identified by one of the automation keys
+pub fn find_automation_key_conflict<'a>(mut keys: impl Iterator<Item = &'a String>, testcheck_id: Option<ObjectId>, testchecks: &'a [Testcheck]) -> Option<(&'a String, &'a Testcheck)> {
+  keys.find_map(|key| {
+    testchecks.iter()
+      .filter(|testcheck| Some(testcheck.id) != testcheck_id)
+      .find(|testcheck| testcheck.automation_keys().any(|other| other == key))
+      .map(|testcheck| (key, testcheck))
+  })
+}
+
+// Selected ids without duplicates, in the order of the selection
+pub fn parse_testchecks_ids(ids: &[String]) -> Result<Vec<ObjectId>, String> {
+  let mut oids: Vec<ObjectId> = vec![];
+  for id in ids {
+    let oid = ObjectId::parse_str(id).map_err(|_| format!("Invalid testcheck id: {}", id))?;
+    if !oids.contains(&oid) {
+      oids.push(oid);
+    }
+  }
+  if oids.is_empty() {
+    return Err("No testchecks selected".to_string());
+  }
+  Ok(oids)
+}
+
+// Moved checks keep their automation keys, which must stay unique in the destination testlist
+pub fn find_transfer_automation_key_conflict(testchecks: &[Testcheck], testlist_id: ObjectId, destination_testchecks: &[Testcheck]) -> Option<String> {
+  let mut destination_testchecks = destination_testchecks.to_vec();
+  for testcheck in testchecks {
+    if testcheck.testlist_id == testlist_id {
+      continue;
+    }
+    if let Some((key, other)) = find_automation_key_conflict(testcheck.automation_keys(), Some(testcheck.id), &destination_testchecks) {
+      return Some(format!("Automation key {} of testcheck {} is already used by testcheck {}", key, testcheck.name, other.name));
+    }
+    destination_testchecks.push(testcheck.clone());
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testchecks::schema::TestcheckMode;
+
+  fn testcheck() -> Testcheck {
+    let now = DateTime::now();
+    Testcheck {
+      id: ObjectId::new(),
+      testlist_id: ObjectId::new(),
+      section_id: Some(ObjectId::new()),
+      project_id: ObjectId::new(),
+      account_id: ObjectId::new(),
+      name: "Login".to_string(),
+      description: "Open the login page".to_string(),
+      expected: "The user is logged in".to_string(),
+      tags: vec!["smoke".to_string()],
+      external_key: Some("auth > Login".to_string()),
+      automation_key: Some("auth.spec.ts > login".to_string()),
+      automation_aliases: vec!["login".to_string()],
+      mode: TestcheckMode::Both,
+      position: 0,
+      revision: 1,
+      created_at: now,
+      updated_at: now,
+    }
+  }
+
+  fn imported(testcheck: &Testcheck) -> TestcheckDto {
+    TestcheckDto {
+      name: testcheck.name.clone(),
+      description: testcheck.description.clone(),
+      expected: testcheck.expected.clone(),
+      tags: testcheck.tags.clone(),
+      section_id: None,
+      external_key: testcheck.external_key.clone(),
+      automation_key: None,
+      automation_aliases: Vec::new(),
+      mode: TestcheckMode::Manual,
+    }
+  }
+
+  #[test]
+  fn reimported_automated_check_is_unchanged() {
+    let testcheck = testcheck();
+    let plan = plan_testchecks_import(vec![(1, imported(&testcheck))], std::slice::from_ref(&testcheck));
+    let (row, data, _) = &plan[0];
+    assert_eq!(row.action, TestcheckImportAction::Unchanged);
+    assert_eq!(data.automation_key, testcheck.automation_key);
+    assert_eq!(data.section_id, testcheck.section_id.map(|section_id| section_id.to_hex()));
+  }
+
+  #[test]
+  fn reimported_edited_check_is_updated() {
+    let testcheck = testcheck();
+    let data = TestcheckDto { expected: "The dashboard opens".to_string(), ..imported(&testcheck) };
+    let plan = plan_testchecks_import(vec![(1, data)], &[testcheck]);
+    assert_eq!(plan[0].0.action, TestcheckImportAction::Update);
+  }
+
+  #[test]
+  fn parses_testchecks_ids() {
+    let id = ObjectId::new();
+    assert_eq!(parse_testchecks_ids(&[id.to_hex(), id.to_hex()]), Ok(vec![id]));
+    assert!(parse_testchecks_ids(&[]).is_err());
+    assert_eq!(parse_testchecks_ids(&[id.to_hex(), "nope".to_string()]), Err("Invalid testcheck id: nope".to_string()));
+  }
+
+  #[test]
+  fn moved_checks_keep_unique_automation_keys() {
+    let destination = testcheck();
+    let moved = Testcheck { id: ObjectId::new(), name: "Logout".to_string(), automation_key: Some("auth.spec.ts > logout".to_string()), automation_aliases: Vec::new(), ..testcheck() };
+    assert_eq!(find_transfer_automation_key_conflict(std::slice::from_ref(&moved), destination.testlist_id, std::slice::from_ref(&destination)), None);
+
+    // An alias of the destination check
+    let moved = Testcheck { automation_aliases: vec!["login".to_string()], ..moved };
+    assert_eq!(
+      find_transfer_automation_key_conflict(std::slice::from_ref(&moved), destination.testlist_id, std::slice::from_ref(&destination)),
+      Some("Automation key login of testcheck Logout is already used by testcheck Login".to_string()),
+    );
+
+    // Two moved checks sharing a key
+    let other = Testcheck { id: ObjectId::new(), name: "Logout again".to_string(), automation_key: None, ..moved.clone() };
+    assert!(find_transfer_automation_key_conflict(&[moved.clone(), other], destination.testlist_id, &[]).is_some());
+
+    // Checks already in the destination stay where they are
+    let moved = Testcheck { testlist_id: destination.testlist_id, ..moved };
+    assert_eq!(find_transfer_automation_key_conflict(&[moved], destination.testlist_id, &[destination]), None);
+  }
 }
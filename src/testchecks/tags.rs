@@ -0,0 +1,205 @@
+// Boolean expressions over testcheck tags, e.g. `smoke AND NOT (slow OR flaky)`.
+// Operators are case insensitive, NOT binds tighter than AND, which binds tighter than OR.
+// Tags are matched case insensitively.
+
+// Bounds keeping the recursive parsing, matching and dropping of user input off the stack limit
+const MAX_TAGS_EXPRESSION_LENGTH: usize = 1024;
+const MAX_TAGS_EXPRESSION_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagsExpression {
+  Tag(String),
+  Not(Box<TagsExpression>),
+  And(Box<TagsExpression>, Box<TagsExpression>),
+  Or(Box<TagsExpression>, Box<TagsExpression>),
+}
+
+impl TagsExpression {
+  pub fn parse(expression: &str) -> Result<TagsExpression, String> {
+    if expression.len() > MAX_TAGS_EXPRESSION_LENGTH {
+      return Err(format!("Tags expression longer than {} characters", MAX_TAGS_EXPRESSION_LENGTH));
+    }
+    let tokens = tokenize(expression);
+    if tokens.is_empty() {
+      return Err("Empty tags expression".to_string());
+    }
+    let mut parser = Parser { tokens, index: 0, depth: 0 };
+    let parsed = parser.parse_or()?;
+    match parser.peek() {
+      Some(token) => Err(format!("Unexpected '{}' in tags expression", token)),
+      None => Ok(parsed),
+    }
+  }
+
+  pub fn matches(&self, tags: &[String]) -> bool {
+    match self {
+      TagsExpression::Tag(tag) => tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+      TagsExpression::Not(expression) => !expression.matches(tags),
+      TagsExpression::And(left, right) => left.matches(tags) && right.matches(tags),
+      TagsExpression::Or(left, right) => left.matches(tags) || right.matches(tags),
+    }
+  }
+}
+
+fn tokenize(expression: &str) -> Vec<String> {
+  let mut tokens = vec![];
+  let mut current = String::new();
+  for c in expression.chars() {
+    if c == '(' || c == ')' || c.is_whitespace() {
+      if !current.is_empty() {
+        tokens.push(std::mem::take(&mut current));
+      }
+      if !c.is_whitespace() {
+        tokens.push(c.to_string());
+      }
+    } else {
+      current.push(c);
+    }
+  }
+  if !current.is_empty() {
+    tokens.push(current);
+  }
+  tokens
+}
+
+struct Parser {
+  tokens: Vec<String>,
+  index: usize,
+  // Nesting of NOT and parentheses
+  depth: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&str> {
+    self.tokens.get(self.index).map(|token| token.as_str())
+  }
+
+  fn next_is(&self, keyword: &str) -> bool {
+    self.peek().is_some_and(|token| token.eq_ignore_ascii_case(keyword))
+  }
+
+  fn enter(&mut self) -> Result<(), String> {
+    self.depth += 1;
+    if self.depth > MAX_TAGS_EXPRESSION_DEPTH {
+      return Err(format!("Tags expression nested deeper than {} levels", MAX_TAGS_EXPRESSION_DEPTH));
+    }
+    Ok(())
+  }
+
+  fn parse_or(&mut self) -> Result<TagsExpression, String> {
+    let mut left = self.parse_and()?;
+    while self.next_is("OR") {
+      self.index += 1;
+      let right = self.parse_and()?;
+      left = TagsExpression::Or(Box::new(left), Box::new(right));
+    }
+    Ok(left)
+  }
+
+  fn parse_and(&mut self) -> Result<TagsExpression, String> {
+    let mut left = self.parse_not()?;
+    while self.next_is("AND") {
+      self.index += 1;
+      let right = self.parse_not()?;
+      left = TagsExpression::And(Box::new(left), Box::new(right));
+    }
+    Ok(left)
+  }
+
+  fn parse_not(&mut self) -> Result<TagsExpression, String> {
+    if self.next_is("NOT") {
+      self.index += 1;
+      self.enter()?;
+      let expression = self.parse_not()?;
+      self.depth -= 1;
+      return Ok(TagsExpression::Not(Box::new(expression)));
+    }
+    self.parse_primary()
+  }
+
+  fn parse_primary(&mut self) -> Result<TagsExpression, String> {
+    let token = match self.peek() {
+      Some(token) => token.to_string(),
+      None => return Err("Unexpected end of tags expression".to_string()),
+    };
+    self.index += 1;
+    match token.as_str() {
+      "(" => {
+        self.enter()?;
+        let expression = self.parse_or()?;
+        if self.peek() != Some(")") {
+          return Err("Missing ')' in tags expression".to_string());
+        }
+        self.index += 1;
+        self.depth -= 1;
+        Ok(expression)
+      },
+      ")" => Err("Unexpected ')' in tags expression".to_string()),
+      _ if ["AND", "OR", "NOT"].iter().any(|keyword| token.eq_ignore_ascii_case(keyword)) => {
+        Err(format!("Expected a tag before '{}' in tags expression", token))
+      },
+      _ => Ok(TagsExpression::Tag(token)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+  }
+
+  fn tag(tag: &str) -> Box<TagsExpression> {
+    Box::new(TagsExpression::Tag(tag.to_string()))
+  }
+
+  #[test]
+  fn not_binds_tighter_than_and_tighter_than_or() {
+    let expression = TagsExpression::parse("a OR NOT b AND c").unwrap();
+    let expected = TagsExpression::Or(tag("a"), Box::new(TagsExpression::And(Box::new(TagsExpression::Not(tag("b"))), tag("c"))));
+    assert_eq!(expression, expected);
+  }
+
+  #[test]
+  fn parentheses_override_precedence() {
+    let expression = TagsExpression::parse("(a or b) and not (slow)").unwrap();
+    let expected = TagsExpression::And(Box::new(TagsExpression::Or(tag("a"), tag("b"))), Box::new(TagsExpression::Not(tag("slow"))));
+    assert_eq!(expression, expected);
+  }
+
+  #[test]
+  fn matches_tags_case_insensitively() {
+    let expression = TagsExpression::parse("smoke AND NOT (slow OR flaky)").unwrap();
+    assert!(expression.matches(&tags(&["Smoke", "fast"])));
+    assert!(!expression.matches(&tags(&["smoke", "FLAKY"])));
+    assert!(!expression.matches(&tags(&["slow"])));
+  }
+
+  #[test]
+  fn rejects_malformed_expressions() {
+    for expression in ["", "  ", "a AND", "AND a", "(a", "a)", "a b", "NOT", "()"] {
+      assert!(TagsExpression::parse(expression).is_err(), "{:?} should be rejected", expression);
+    }
+  }
+
+  #[test]
+  fn rejects_expressions_nested_too_deep() {
+    let nots = "NOT ".repeat(MAX_TAGS_EXPRESSION_DEPTH);
+    assert!(TagsExpression::parse(&format!("{}a", nots)).is_ok());
+    assert!(TagsExpression::parse(&format!("NOT {}a", nots)).is_err());
+
+    let parentheses = format!("{}a{}", "(".repeat(MAX_TAGS_EXPRESSION_DEPTH + 1), ")".repeat(MAX_TAGS_EXPRESSION_DEPTH + 1));
+    assert!(TagsExpression::parse(&parentheses).is_err());
+    // Siblings do not add up
+    let siblings = vec!["(a)"; MAX_TAGS_EXPRESSION_DEPTH + 1].join(" OR ");
+    assert!(TagsExpression::parse(&siblings).is_ok());
+  }
+
+  #[test]
+  fn rejects_expressions_too_long() {
+    let expression = vec!["a"; MAX_TAGS_EXPRESSION_LENGTH].join(" OR ");
+    assert!(TagsExpression::parse(&expression).is_err());
+  }
+}
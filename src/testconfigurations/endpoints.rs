@@ -0,0 +1,91 @@
+use log::{error, warn};
+use rocket::{delete, get, put, routes, serde::json::Json, State};
+use crate::{service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testenvironments::{endpoints::get_project_testenvironment_id, schema::Testenvironment}, users::{roles::is_admin, schema::User}};
+
+use super::schema::{Testconfiguration, TestconfigurationDto};
+
+#[get("/<id>")]
+async fn get_testconfiguration(jwt: Result<JWT, JsonError>, id: &str, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testconfiguration>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  match allowed_for_testconfiguration(jwts, testconfiguration_repo, id).await {
+    Ok(testconfiguration) => Ok(Json(testconfiguration)),
+    Err(e) => Err(e)
+  }
+}
+
+#[put("/<id>", format = "json", data = "<data>")]
+async fn update_testconfiguration(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestconfigurationDto>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testconfiguration>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testconfiguration = allowed_for_testconfiguration(jwts, testconfiguration_repo, id).await?;
+  let data = data.into_inner();
+  let testenvironment_id = get_project_testenvironment_id(testenvironment_repo, testconfiguration.project_id, data.testenvironment_id.as_deref()).await?;
+
+  match testconfiguration_repo.update_testconfiguration(id.to_string(), testenvironment_id, data.name, data.parameters).await {
+    Ok(updated) => {
+      if updated.matched_count == 0 {
+        warn!("Testconfiguration not found: {}", id);
+        return Err(JsonError::NotFound("Testconfiguration not found".to_string()));
+      }
+      get_testconfiguration_by_id(testconfiguration_repo, id).await.map(Json)
+    },
+    Err(e) => {
+      error!("Error updating testconfiguration: {}", e);
+      Err(JsonError::Internal("Error updating testconfiguration".to_string()))
+    },
+  }
+}
+
+#[delete("/<id>")]
+async fn delete_testconfiguration(jwt: Result<JWT, JsonError>, id: &str, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testconfiguration>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testconfiguration = allowed_for_testconfiguration(jwts, testconfiguration_repo, id).await?;
+
+  match testconfiguration_repo.delete_testconfiguration(id.to_string()).await {
+    Ok(deleted) => {
+      if deleted.deleted_count == 0 {
+        warn!("Testconfiguration not found: {}", id);
+        return Err(JsonError::NotFound("Testconfiguration not found".to_string()));
+      }
+      Ok(Json(testconfiguration))
+    },
+    Err(e) => {
+      error!("Error deleting testconfiguration: {}", e);
+      Err(JsonError::Internal("Error deleting testconfiguration".to_string()))
+    },
+  }
+}
+
+pub fn get_testconfigurations_routes() -> Vec<rocket::Route> {
+  routes![get_testconfiguration, update_testconfiguration, delete_testconfiguration]
+}
+
+pub async fn get_testconfiguration_by_id(testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, id: &str) -> Result<Testconfiguration, JsonError> {
+  match testconfiguration_repo.get_testconfiguration_by_id(id).await {
+    Ok(testconfiguration) => match testconfiguration {
+      Some(testconfiguration) => Ok(testconfiguration),
+      None => {
+        warn!("Testconfiguration not found: {}", id);
+        Err(JsonError::NotFound("Testconfiguration not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testconfiguration: {}", e);
+      Err(JsonError::Internal("Error getting testconfiguration".to_string()))
+    },
+  }
+}
+
+async fn allowed_for_testconfiguration(jwts: JWTSessionAndUser, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testconfiguration_id: &str) -> Result<Testconfiguration, JsonError>  {
+  if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
+    return Err(JsonError::Forbidden(
+      "You are not allowed to retrieve this testconfiguration".to_string(),
+    ));
+  }
+  let testconfiguration = get_testconfiguration_by_id(testconfiguration_repo, testconfiguration_id).await?;
+  if let Some(accounts) = &jwts.user.accounts {
+    if !accounts.iter().any(|account| account.account_id == testconfiguration.account_id) && !is_admin(&jwts.user) {
+      return Err(JsonError::Forbidden("You are not allowed to retrieve this testconfiguration".to_string()));
+    }
+  }
+  Ok(testconfiguration)
+}
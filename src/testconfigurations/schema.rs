@@ -0,0 +1,68 @@
+use bson::DateTime;
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::service::db::{serialize_datetime, serialize_object_id, serialize_option_object_id};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Testconfiguration {
+  #[serde(rename = "_id", serialize_with = "serialize_object_id")]
+  pub id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub account_id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub project_id: ObjectId,
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub testenvironment_id: Option<ObjectId>,
+  pub name: String,
+  // e.g. browser: firefox, os: linux, locale: it-IT
+  pub parameters: Vec<TestconfigurationParameter>,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub created_at: DateTime,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TestconfigurationParameter {
+  pub name: String,
+  pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestconfigurationDto {
+  pub name: String,
+  pub testenvironment_id: Option<String>,
+  pub parameters: Vec<TestconfigurationParameter>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestconfigurationDimension {
+  pub name: String,
+  pub values: Vec<String>,
+}
+
+// Generates one configuration for each combination of the dimensions values
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestconfigurationMatrixDto {
+  pub testenvironment_id: Option<String>,
+  pub dimensions: Vec<TestconfigurationDimension>,
+}
+
+pub const MAX_MATRIX_CONFIGURATIONS: usize = 100;
+
+pub fn expand_testconfiguration_matrix(dimensions: &[TestconfigurationDimension]) -> Vec<Vec<TestconfigurationParameter>> {
+  let mut combinations: Vec<Vec<TestconfigurationParameter>> = vec![vec![]];
+  for dimension in dimensions {
+    let mut expanded = vec![];
+    for combination in &combinations {
+      for value in &dimension.values {
+        let mut parameters = combination.clone();
+        parameters.push(TestconfigurationParameter { name: dimension.name.clone(), value: value.clone() });
+        expanded.push(parameters);
+      }
+    }
+    combinations = expanded;
+  }
+  combinations
+}
@@ -0,0 +1,71 @@
+use std::error::Error;
+
+use bson::DateTime;
+use mongodb::{
+  bson::{self, doc, oid::ObjectId}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
+};
+use crate::{projects::schema::Project, service::db::{ self, MongoRepo}};
+use super::schema::{Testconfiguration, TestconfigurationParameter};
+use rocket::futures::TryStreamExt;
+
+pub fn get_testconfigurations_repo(client: Client) -> MongoRepo<Testconfiguration> {
+  return db::get_mongo_repo(client, "test_boss", "testconfigurations");
+}
+
+impl MongoRepo<Testconfiguration> {
+  pub async fn get_project_testconfigurations(&self, project_id: &str) -> Result<Vec<Testconfiguration>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "project_id": ObjectId::parse_str(project_id)? };
+    let cursor = self.col.find(filter, None).await?;
+    let testconfigurations: Vec<Testconfiguration> = cursor.try_collect().await?;
+    Ok(testconfigurations)
+  }
+
+  pub async fn get_testconfigurations_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Testconfiguration>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": { "$in": ids } };
+    let cursor = self.col.find(filter, None).await?;
+    let testconfigurations: Vec<Testconfiguration> = cursor.try_collect().await?;
+    Ok(testconfigurations)
+  }
+
+  pub async fn get_testconfiguration_by_id(&self, id: &str) -> Result<Option<Testconfiguration>, Box<dyn Error + Send + Sync>> {
+    let oid = ObjectId::parse_str(id)?;
+    let filter = doc! { "_id": oid };
+    let result = self.col.find_one(filter, None).await?;
+    Ok(result)
+  }
+
+  pub async fn create_testconfiguration(&self, project: &Project, testenvironment_id: Option<ObjectId>, name: String, parameters: Vec<TestconfigurationParameter>) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let new_doc = Testconfiguration {
+      id: ObjectId::new(),
+      account_id: project.account_id,
+      project_id: project.id,
+      testenvironment_id,
+      name,
+      parameters,
+      created_at: now,
+      updated_at: now,
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testconfiguration(&self, id: String, testenvironment_id: Option<ObjectId>, name: String, parameters: Vec<TestconfigurationParameter>) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let now = chrono::Utc::now();
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let upd_doc = doc! { "$set": {
+      "name": name,
+      "testenvironment_id": testenvironment_id,
+      "parameters": bson::to_bson(&parameters)?,
+      "updated_at": DateTime::from_chrono(now)
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn delete_testconfiguration(&self, id: String) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let result = self.col.delete_one(filter, None).await?;
+    Ok(result)
+  }
+}
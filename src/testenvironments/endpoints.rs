@@ -0,0 +1,105 @@
+use bson::oid::ObjectId;
+use log::{error, warn};
+use rocket::{delete, get, put, routes, serde::json::Json, State};
+use crate::{service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, users::{roles::is_admin, schema::User}};
+
+use super::schema::{Testenvironment, TestenvironmentDto};
+
+#[get("/<id>")]
+async fn get_testenvironment(jwt: Result<JWT, JsonError>, id: &str, testenvironment_repo: &State<MongoRepo<Testenvironment>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testenvironment>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  match allowed_for_testenvironment(jwts, testenvironment_repo, id).await {
+    Ok(testenvironment) => Ok(Json(testenvironment)),
+    Err(e) => Err(e)
+  }
+}
+
+#[put("/<id>", format = "json", data = "<data>")]
+async fn update_testenvironment(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestenvironmentDto>, testenvironment_repo: &State<MongoRepo<Testenvironment>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testenvironment>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_testenvironment(jwts, testenvironment_repo, id).await?;
+
+  match testenvironment_repo.update_testenvironment(id.to_string(), data.into_inner()).await {
+    Ok(updated) => {
+      if updated.matched_count == 0 {
+        warn!("Testenvironment not found: {}", id);
+        return Err(JsonError::NotFound("Testenvironment not found".to_string()));
+      }
+      get_testenvironment_by_id(testenvironment_repo, id).await.map(Json)
+    },
+    Err(e) => {
+      error!("Error updating testenvironment: {}", e);
+      Err(JsonError::Internal("Error updating testenvironment".to_string()))
+    },
+  }
+}
+
+#[delete("/<id>")]
+async fn delete_testenvironment(jwt: Result<JWT, JsonError>, id: &str, testenvironment_repo: &State<MongoRepo<Testenvironment>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testenvironment>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testenvironment = allowed_for_testenvironment(jwts, testenvironment_repo, id).await?;
+
+  match testenvironment_repo.delete_testenvironment(id.to_string()).await {
+    Ok(deleted) => {
+      if deleted.deleted_count == 0 {
+        warn!("Testenvironment not found: {}", id);
+        return Err(JsonError::NotFound("Testenvironment not found".to_string()));
+      }
+      Ok(Json(testenvironment))
+    },
+    Err(e) => {
+      error!("Error deleting testenvironment: {}", e);
+      Err(JsonError::Internal("Error deleting testenvironment".to_string()))
+    },
+  }
+}
+
+pub fn get_testenvironments_routes() -> Vec<rocket::Route> {
+  routes![get_testenvironment, update_testenvironment, delete_testenvironment]
+}
+
+pub async fn get_testenvironment_by_id(testenvironment_repo: &State<MongoRepo<Testenvironment>>, id: &str) -> Result<Testenvironment, JsonError> {
+  match testenvironment_repo.get_testenvironment_by_id(id).await {
+    Ok(testenvironment) => match testenvironment {
+      Some(testenvironment) => Ok(testenvironment),
+      None => {
+        warn!("Testenvironment not found: {}", id);
+        Err(JsonError::NotFound("Testenvironment not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testenvironment: {}", e);
+      Err(JsonError::Internal("Error getting testenvironment".to_string()))
+    },
+  }
+}
+
+pub async fn get_project_testenvironment_id(testenvironment_repo: &State<MongoRepo<Testenvironment>>, project_id: ObjectId, testenvironment_id: Option<&str>) -> Result<Option<ObjectId>, JsonError> {
+  let testenvironment_id = match testenvironment_id {
+    Some(testenvironment_id) => testenvironment_id,
+    None => return Ok(None),
+  };
+  match testenvironment_repo.get_testenvironment_by_id(testenvironment_id).await {
+    Ok(Some(testenvironment)) if testenvironment.project_id == project_id => Ok(Some(testenvironment.id)),
+    Ok(_) => Err(JsonError::BadRequest("Testenvironment not found in project".to_string())),
+    Err(e) => {
+      error!("Error getting testenvironment: {}", e);
+      Err(JsonError::Internal("Error getting testenvironment".to_string()))
+    },
+  }
+}
+
+async fn allowed_for_testenvironment(jwts: JWTSessionAndUser, testenvironment_repo: &State<MongoRepo<Testenvironment>>, testenvironment_id: &str) -> Result<Testenvironment, JsonError>  {
+  if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
+    return Err(JsonError::Forbidden(
+      "You are not allowed to retrieve this testenvironment".to_string(),
+    ));
+  }
+  let testenvironment = get_testenvironment_by_id(testenvironment_repo, testenvironment_id).await?;
+  if let Some(accounts) = &jwts.user.accounts {
+    if !accounts.iter().any(|account| account.account_id == testenvironment.account_id) && !is_admin(&jwts.user) {
+      return Err(JsonError::Forbidden("You are not allowed to retrieve this testenvironment".to_string()));
+    }
+  }
+  Ok(testenvironment)
+}
@@ -0,0 +1,64 @@
+use std::error::Error;
+
+use bson::DateTime;
+use mongodb::{
+  bson::{self, doc, oid::ObjectId}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
+};
+use crate::{projects::schema::Project, service::db::{ self, MongoRepo}};
+use super::schema::{Testenvironment, TestenvironmentDto};
+use rocket::futures::TryStreamExt;
+
+pub fn get_testenvironments_repo(client: Client) -> MongoRepo<Testenvironment> {
+  return db::get_mongo_repo(client, "test_boss", "testenvironments");
+}
+
+impl MongoRepo<Testenvironment> {
+  pub async fn get_project_testenvironments(&self, project_id: &str) -> Result<Vec<Testenvironment>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "project_id": ObjectId::parse_str(project_id)? };
+    let cursor = self.col.find(filter, None).await?;
+    let testenvironments: Vec<Testenvironment> = cursor.try_collect().await?;
+    Ok(testenvironments)
+  }
+
+  pub async fn get_testenvironment_by_id(&self, id: &str) -> Result<Option<Testenvironment>, Box<dyn Error + Send + Sync>> {
+    let oid = ObjectId::parse_str(id)?;
+    let filter = doc! { "_id": oid };
+    let result = self.col.find_one(filter, None).await?;
+    Ok(result)
+  }
+
+  pub async fn create_testenvironment(&self, project: &Project, data: TestenvironmentDto) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let new_doc = Testenvironment {
+      id: ObjectId::new(),
+      account_id: project.account_id,
+      project_id: project.id,
+      name: data.name,
+      description: data.description,
+      url: data.url,
+      created_at: now,
+      updated_at: now,
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testenvironment(&self, id: String, data: TestenvironmentDto) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let now = chrono::Utc::now();
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let upd_doc = doc! { "$set": {
+      "name": data.name,
+      "description": data.description,
+      "url": data.url,
+      "updated_at": DateTime::from_chrono(now)
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn delete_testenvironment(&self, id: String) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let result = self.col.delete_one(filter, None).await?;
+    Ok(result)
+  }
+}
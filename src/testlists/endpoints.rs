@@ -1,8 +1,9 @@
+use bson::oid::ObjectId;
 use log::{error, warn};
-use rocket::{delete, get, post, put, routes, serde::json::Json, State};
-use crate::{service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testchecks::schema::{Testcheck, TestcheckDto}, testlists::schema::TestlistDto, testreports::schema::{Testreport, TestreportDto}, testresults::schema::Testresult, users::{roles::is_admin, schema::User}};
+use rocket::{delete, get, http::ContentType, post, put, routes, serde::json::Json, State};
+use crate::{service::{db::MongoRepo, download::Download, http_errors::JsonError, xlsx::xlsx_content_type}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testchecks::{csv::{find_imported_testcheck, get_imported_testcheck_dto, get_testchecks_csv, parse_testchecks_csv}, endpoints::{apply_testchecks_import, check_testcheck_automation_keys, create_initial_testrevision}, schema::{Testcheck, TestcheckDto, TestcheckImportAction, TestcheckImportRow, TestchecksImportDto, TestchecksImportResult, TestchecksTransferDto}, service::{find_transfer_automation_key_conflict, is_testcheck_unchanged, parse_testchecks_ids}, tags::TagsExpression}, testlists::schema::TestlistDto, testconfigurations::schema::Testconfiguration, testenvironments::schema::Testenvironment, testreports::{export::TestreportGroupBy, schema::{Testreport, TestreportBuild, TestreportConfiguration, TestreportDto, TestreportMatrixDto}, service::normalize_testreport_build}, testresults::schema::Testresult, testrevisions::schema::Testrevision, testsections::{endpoints::{check_testlist_testsection, get_parent_testsection_id}, schema::{Testsection, TestsectionDto}}, users::{roles::is_admin, schema::User}};
 
-use super::schema::Testlist;
+use super::{schema::Testlist, xlsx::get_testlist_xlsx};
 
 #[get("/")]
 async fn get_testlists(testlist_repo: &State<MongoRepo<Testlist>>) -> Result<Json<Vec<Testlist>>, JsonError> {
@@ -60,7 +61,7 @@ async fn update_testlist(jwt: Result<JWT, JsonError>, id: &str, data: Json<Testl
 }
 
 #[delete("/<id>")]
-async fn delete_testlist(jwt: Result<JWT, JsonError>, id: &str, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testlist>, JsonError> {
+async fn delete_testlist(jwt: Result<JWT, JsonError>, id: &str, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testsection_repo: &State<MongoRepo<Testsection>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testlist>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
   let testlist = allowed_for_testlist(jwts, testlist_repo, id).await?;
 
@@ -69,6 +70,11 @@ async fn delete_testlist(jwt: Result<JWT, JsonError>, id: &str, testlist_repo: &
     return Err(JsonError::Internal("Error deleting testlist testchecks".to_string()));
   }
 
+  if testsection_repo.delete_testlist_testsections(id).await.is_err() {
+    error!("Error deleting testlist testsections: {}", id);
+    return Err(JsonError::Internal("Error deleting testlist testsections".to_string()));
+  }
+
   match testlist_repo.delete_testlist(id.to_string()).await {
     Ok(deleted) => {
       if deleted.deleted_count == 0 {
@@ -100,10 +106,13 @@ pub async fn get_testlist_testchecks(jwt: Result<JWT, JsonError>, testlist_id: &
 }
 
 #[post("/<testlist_id>/testchecks", format = "json", data = "<data>")]
-pub async fn create_testlist_testcheck(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestcheckDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>) -> Result<Json<Testcheck>, JsonError> {
+pub async fn create_testlist_testcheck(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestcheckDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testsection_repo: &State<MongoRepo<Testsection>>, testrevision_repo: &State<MongoRepo<Testrevision>>) -> Result<Json<Testcheck>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let user_id = jwts.user.id;
   let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
-  let data = data.into_inner();
+  let mut data = data.into_inner();
+  check_testlist_testsection(testsection_repo, testlist.id, data.section_id.as_deref()).await?;
+  check_testcheck_automation_keys(testcheck_repo, testlist.id, None, &mut data).await?;
   let account_id = testlist.account_id.to_hex();
   let project_id = testlist.project_id.to_hex();
 
@@ -113,7 +122,10 @@ pub async fn create_testlist_testcheck(jwt: Result<JWT, JsonError>, testlist_id:
       let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
       match testcheck_repo.get_testcheck_by_id(id.as_str()).await {
         Ok(testcheck) => match testcheck {
-          Some(testcheck) => Ok(Json(testcheck)),
+          Some(testcheck) => {
+            create_initial_testrevision(testrevision_repo, &testcheck, user_id).await?;
+            Ok(Json(testcheck))
+          },
           None => {
             warn!("Testlist not found: {}", id);
             Err(JsonError::NotFound("Testlist not found".to_string()))
@@ -150,61 +162,370 @@ pub async fn update_testchecks_positions(jwt: Result<JWT, JsonError>, testlist_i
   }
 }
 
+#[post("/<testlist_id>/testchecks/move", format = "json", data = "<data>")]
+pub async fn move_testchecks(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestchecksTransferDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>) -> Result<Json<Vec<Testcheck>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+  let testchecks = get_transfer_testchecks(testcheck_repo, &testlist, data.into_inner()).await?;
 
-#[post("/<testlist_id>/testreports", format = "json", data = "<data>")]
-pub async fn create_testreport(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestreportDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<Testreport>, JsonError> {
+  let destination_testchecks = match testcheck_repo.get_testlist_testchecks(testlist_id).await {
+    Ok(testchecks) => testchecks,
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      return Err(JsonError::Internal("Error getting testchecks".to_string()));
+    },
+  };
+  if let Some(conflict) = find_transfer_automation_key_conflict(&testchecks, testlist.id, &destination_testchecks) {
+    return Err(JsonError::BadRequest(conflict));
+  }
+
+  let mut position = get_next_transfer_position(testcheck_repo, testlist_id).await?;
+  let mut source_testlist_ids: Vec<String> = vec![];
+  for testcheck in testchecks {
+    if testcheck.testlist_id == testlist.id {
+      continue;
+    }
+    let source_testlist_id = testcheck.testlist_id.to_hex();
+    if !source_testlist_ids.contains(&source_testlist_id) {
+      source_testlist_ids.push(source_testlist_id);
+    }
+    if let Err(e) = testcheck_repo.move_testcheck(&testcheck, &testlist, position).await {
+      error!("Error moving testcheck {}: {}", testcheck.id.to_hex(), e);
+      return Err(JsonError::Internal("Error moving testcheck".to_string()));
+    }
+    position += 1;
+  }
+
+  for source_testlist_id in source_testlist_ids {
+    if let Err(e) = testcheck_repo.renumber_testlist_testchecks(&source_testlist_id).await {
+      error!("Error updating testchecks positions: {}", e);
+      return Err(JsonError::Internal("Error updating testchecks positions".to_string()));
+    }
+  }
+
+  match testcheck_repo.get_testlist_testchecks(testlist_id).await {
+    Ok(testchecks) => Ok(Json(testchecks)),
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      Err(JsonError::Internal("Error getting testchecks".to_string()))
+    },
+  }
+}
+
+#[post("/<testlist_id>/testchecks/copy", format = "json", data = "<data>")]
+pub async fn copy_testchecks(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestchecksTransferDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>) -> Result<Json<Vec<Testcheck>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let user_id = jwts.user.id;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+  let testchecks = get_transfer_testchecks(testcheck_repo, &testlist, data.into_inner()).await?;
+
+  let mut position = get_next_transfer_position(testcheck_repo, testlist_id).await?;
+  for testcheck in testchecks {
+    let testcheck_id = testcheck.id.to_hex();
+    let copy_id = match testcheck_repo.copy_testcheck(testcheck, &testlist, position).await {
+      Ok(inserted) => inserted.inserted_id.as_object_id().unwrap().to_hex(),
+      Err(e) => {
+        error!("Error copying testcheck {}: {}", testcheck_id, e);
+        return Err(JsonError::Internal("Error copying testcheck".to_string()));
+      },
+    };
+    match testcheck_repo.get_testcheck_by_id(&copy_id).await {
+      Ok(Some(copy)) => create_initial_testrevision(testrevision_repo, &copy, user_id).await?,
+      Ok(None) => {
+        warn!("Testcheck not found: {}", copy_id);
+        return Err(JsonError::NotFound("Testcheck not found".to_string()));
+      },
+      Err(e) => {
+        error!("Error getting testcheck: {}", e);
+        return Err(JsonError::Internal("Error getting testcheck".to_string()));
+      },
+    }
+    position += 1;
+  }
+
+  match testcheck_repo.get_testlist_testchecks(testlist_id).await {
+    Ok(testchecks) => Ok(Json(testchecks)),
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      Err(JsonError::Internal("Error getting testchecks".to_string()))
+    },
+  }
+}
+#[get("/<testlist_id>/testchecks/csv")]
+pub async fn export_testchecks_csv(jwt: Result<JWT, JsonError>, testlist_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+
+  match testcheck_repo.get_testlist_testchecks(testlist_id).await {
+    Ok(testchecks) => {
+      let filename = format!("{}.csv", testlist.name);
+      Ok(Download::new(ContentType::CSV, &filename, get_testchecks_csv(&testchecks).into_bytes()))
+    },
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      Err(JsonError::Internal("Error getting testchecks".to_string()))
+    },
+  }
+}
+
+// Workbook with a summary sheet and a sheet of checks per section or tag
+#[get("/<testlist_id>/xlsx?<group_by>")]
+pub async fn export_testlist_xlsx(jwt: Result<JWT, JsonError>, testlist_id: &str, group_by: Option<&str>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testsection_repo: &State<MongoRepo<Testsection>>) -> Result<Download, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
-  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+  let group_by = TestreportGroupBy::parse(group_by).map_err(JsonError::BadRequest)?;
+  let testchecks = match testcheck_repo.get_testlist_testchecks(testlist_id).await {
+    Ok(testchecks) => testchecks,
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      return Err(JsonError::Internal("Error getting testchecks".to_string()));
+    },
+  };
+  let testsections = match testsection_repo.get_testlist_testsections(testlist_id).await {
+    Ok(testsections) => testsections,
+    Err(e) => {
+      error!("Error getting testsections: {}", e);
+      return Err(JsonError::Internal("Error getting testsections".to_string()));
+    },
+  };
+
+  match get_testlist_xlsx(&testlist, &testsections, &testchecks, group_by) {
+    Ok(xlsx) => Ok(Download::new(xlsx_content_type(), &format!("{}.xlsx", testlist.name), xlsx)),
+    Err(e) => {
+      error!("Error writing testlist workbook: {}", e);
+      Err(JsonError::Internal("Error writing testlist workbook".to_string()))
+    },
+  }
+}
+
+// Creates or updates the checks of the CSV rows, nothing is saved when a row is invalid
+#[post("/<testlist_id>/testchecks/import", format = "json", data = "<data>")]
+pub async fn import_testchecks_csv(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestchecksImportDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testrevision_repo: &State<MongoRepo<Testrevision>>) -> Result<Json<TestchecksImportResult>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let user_id = jwts.user.id;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
   let data = data.into_inner();
-  let account_id = testlist.account_id.to_hex();
-  let project_id = testlist.project_id.to_hex();
+  let rows = parse_testchecks_csv(&data.csv, &data.mapping).map_err(JsonError::BadRequest)?;
 
-  let testchecks = testcheck_repo.get_testlist_testchecks(testlist_id).await;
-  if(testchecks.is_err()) {
-    error!("Error getting testchecks: {}", testlist_id);
-    return Err(JsonError::Internal("Error getting testchecks".to_string()));
+  let testchecks = match testcheck_repo.get_testlist_testchecks(testlist_id).await {
+    Ok(testchecks) => testchecks,
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      return Err(JsonError::Internal("Error getting testchecks".to_string()));
+    },
+  };
+
+  let mut imports = Vec::new();
+  for mut row in rows {
+    let testcheck = find_imported_testcheck(&row, &testchecks);
+    if testcheck.is_none() && !row.id.is_empty() {
+      row.errors.push(format!("Testcheck {} not found in testlist", row.id));
+    }
+    let dto = get_imported_testcheck_dto(&row, testcheck);
+    let action = match testcheck {
+      _ if !row.errors.is_empty() => TestcheckImportAction::Invalid,
+      Some(testcheck) if is_testcheck_unchanged(testcheck, &dto) => TestcheckImportAction::Unchanged,
+      Some(_) => TestcheckImportAction::Update,
+      None => TestcheckImportAction::Create,
+    };
+    let result = TestcheckImportRow {
+      row: row.row,
+      action,
+      testcheck_id: testcheck.map(|testcheck| testcheck.id),
+      name: row.name,
+      errors: row.errors,
+    };
+    imports.push((result, dto, testcheck.cloned()));
+  }
+
+  let count = |action: TestcheckImportAction| imports.iter().filter(|(result, _, _)| result.action == action).count() as u32;
+  let invalid = count(TestcheckImportAction::Invalid);
+  let mut result = TestchecksImportResult {
+    dry_run: data.dry_run,
+    imported: !data.dry_run && invalid == 0,
+    created: count(TestcheckImportAction::Create),
+    updated: count(TestcheckImportAction::Update),
+    unchanged: count(TestcheckImportAction::Unchanged),
+    invalid,
+    rows: Vec::new(),
+  };
+  if !result.imported {
+    result.rows = imports.into_iter().map(|(result, _, _)| result).collect();
+    return Ok(Json(result));
   }
-  let testchecks = testchecks.unwrap();
 
-  let res = testreport_repo.create_testreport(&account_id, &project_id, testlist, data).await;
+  result.rows = apply_testchecks_import(testcheck_repo, testrevision_repo, &testlist, imports, user_id).await?;
+  Ok(Json(result))
+}
+
+#[get("/<testlist_id>/testsections")]
+pub async fn get_testlist_testsections(jwt: Result<JWT, JsonError>, testlist_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testsection_repo: &State<MongoRepo<Testsection>>) -> Result<Json<Vec<Testsection>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+
+  match testsection_repo.get_testlist_testsections(testlist_id).await {
+    Ok(testsections) => Ok(Json(testsections)),
+    Err(e) => {
+      error!("Error getting testsections: {}", e);
+      Err(JsonError::Internal("Error getting testsections".to_string()))
+    },
+  }
+}
+
+#[post("/<testlist_id>/testsections", format = "json", data = "<data>")]
+pub async fn create_testlist_testsection(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestsectionDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testsection_repo: &State<MongoRepo<Testsection>>) -> Result<Json<Testsection>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+  let data = data.into_inner();
+  let parent_id = get_parent_testsection_id(testsection_repo, testlist.id, data.parent_id.as_deref(), None).await?;
+
+  let res = testsection_repo.create_testsection(&testlist, parent_id, data).await;
   match res {
     Ok(inserted) => {
       let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
-      match testreport_repo.get_testreport_by_id(id.as_str()).await {
-        Ok(testreport) => match testreport {
-          Some(testreport) => {
-            for testcheck in testchecks {
-              let testcheck_id = testcheck.id.to_hex();
-              let testresult = testresult_repo.create_testresult(&id, testcheck).await;
-              if(testresult.is_err()) {
-                let _ = testresult_repo.delete_testreport_testresults(id.as_str()).await;
-                let _ = testresult_repo.delete_testresult(id.as_str()).await;
-                error!("Error creating testresult for testcheck: {}", testcheck_id);
-                return Err(JsonError::Internal("Error creating testresult".to_string()));
-              }
-            }
-            Ok(Json(testreport))
-          },
+      match testsection_repo.get_testsection_by_id(id.as_str()).await {
+        Ok(testsection) => match testsection {
+          Some(testsection) => Ok(Json(testsection)),
           None => {
-            warn!("Testreport not found: {}", id);
-            Err(JsonError::NotFound("Testreport not found".to_string()))
+            warn!("Testsection not found: {}", id);
+            Err(JsonError::NotFound("Testsection not found".to_string()))
           },
         },
         Err(e) => {
-          error!("Error getting testreport: {}", e);
-          Err(JsonError::Internal("Error getting testreport".to_string()))
+          error!("Error getting testsection: {}", e);
+          Err(JsonError::Internal("Error getting testsection".to_string()))
         },
       }
     },
     Err(e) => {
-      error!("Error creating testreport: {}", e);
-      Err(JsonError::Internal("Error creating testreport".to_string()))
+      error!("Error creating testsection: {}", e);
+      Err(JsonError::Internal("Error creating testsection".to_string()))
+    },
+  }
+}
+
+#[put("/<testlist_id>/testsections", format = "json", data = "<data>")]
+pub async fn update_testsections_positions(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<Vec<String>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testsection_repo: &State<MongoRepo<Testsection>>) -> Result<Json<i32>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let _ = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+  let data = data.into_inner();
+
+  let res = testsection_repo.update_testsections_positions(testlist_id, data).await;
+  match res {
+    Ok(updated) => Ok(Json(updated)),
+    Err(e) => {
+      error!("Error updating testsections positions: {}", e);
+      Err(JsonError::Internal("Error updating testsections positions".to_string()))
+    },
+  }
+}
+
+#[post("/<testlist_id>/testreports", format = "json", data = "<data>")]
+pub async fn create_testreport(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestreportDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testsection_repo: &State<MongoRepo<Testsection>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<Testreport>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+
+  create_testlist_testreport(testcheck_repo, testsection_repo, testreport_repo, testresult_repo, testlist, None, data.into_inner()).await.map(Json)
+}
+
+#[post("/<testlist_id>/testreports/matrix", format = "json", data = "<data>")]
+pub async fn create_testreports_matrix(jwt: Result<JWT, JsonError>, testlist_id: &str, data: Json<TestreportMatrixDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testlist_repo: &State<MongoRepo<Testlist>>, testcheck_repo: &State<MongoRepo<Testcheck>>, testsection_repo: &State<MongoRepo<Testsection>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, testconfiguration_repo: &State<MongoRepo<Testconfiguration>>, testenvironment_repo: &State<MongoRepo<Testenvironment>>) -> Result<Json<Vec<Testreport>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testlist = allowed_for_testlist(jwts, testlist_repo, testlist_id).await?;
+  let data = data.into_inner();
+
+  let mut testconfiguration_ids: Vec<ObjectId> = vec![];
+  for id in &data.testconfiguration_ids {
+    match ObjectId::parse_str(id) {
+      Ok(oid) => {
+        if !testconfiguration_ids.contains(&oid) {
+          testconfiguration_ids.push(oid);
+        }
+      },
+      Err(_) => return Err(JsonError::BadRequest(format!("Invalid testconfiguration id: {}", id))),
+    }
+  }
+  if testconfiguration_ids.is_empty() {
+    return Err(JsonError::BadRequest("No testconfigurations selected".to_string()));
+  }
+
+  let testconfigurations = match testconfiguration_repo.get_testconfigurations_by_ids(&testconfiguration_ids).await {
+    Ok(testconfigurations) => testconfigurations,
+    Err(e) => {
+      error!("Error getting testconfigurations: {}", e);
+      return Err(JsonError::Internal("Error getting testconfigurations".to_string()));
+    },
+  };
+  if testconfigurations.len() != testconfiguration_ids.len() {
+    return Err(JsonError::NotFound("Testconfiguration not found".to_string()));
+  }
+  if testconfigurations.iter().any(|testconfiguration| testconfiguration.project_id != testlist.project_id) {
+    return Err(JsonError::BadRequest("Testconfigurations must belong to the testlist project".to_string()));
+  }
+  let testenvironments = match testenvironment_repo.get_project_testenvironments(&testlist.project_id.to_hex()).await {
+    Ok(testenvironments) => testenvironments,
+    Err(e) => {
+      error!("Error getting testenvironments: {}", e);
+      return Err(JsonError::Internal("Error getting testenvironments".to_string()));
     },
+  };
+
+  // Every configuration is checked before the first report is created
+  if let Some(tags_filter) = data.tags_filter.as_deref().map(str::trim).filter(|tags_filter| !tags_filter.is_empty()) {
+    TagsExpression::parse(tags_filter).map_err(JsonError::BadRequest)?;
   }
+  let mut dtos = vec![];
+  for id in testconfiguration_ids {
+    let testconfiguration = testconfigurations.iter().find(|testconfiguration| testconfiguration.id == id).unwrap();
+    let environment = testconfiguration.testenvironment_id
+      .and_then(|testenvironment_id| testenvironments.iter().find(|testenvironment| testenvironment.id == testenvironment_id))
+      .map(|testenvironment| testenvironment.name.clone());
+    let snapshot = TestreportConfiguration {
+      testconfiguration_id: testconfiguration.id,
+      name: testconfiguration.name.clone(),
+      environment,
+      parameters: testconfiguration.parameters.clone(),
+    };
+    let build = data.build.clone().map(|build| TestreportBuild {
+      environment: build.environment.or_else(|| snapshot.environment.clone()),
+      ..build
+    });
+    let build = build.map(normalize_testreport_build).transpose().map_err(JsonError::BadRequest)?;
+    let dto = TestreportDto {
+      name: "".to_string(),
+      description: "".to_string(),
+      execution: data.execution.clone(),
+      tags_filter: data.tags_filter.clone(),
+      status: None,
+      justification: "".to_string(),
+      build,
+    };
+    dtos.push((snapshot, dto));
+  }
+
+  let mut testreports: Vec<Testreport> = vec![];
+  for (snapshot, dto) in dtos {
+    match create_testlist_testreport(testcheck_repo, testsection_repo, testreport_repo, testresult_repo, testlist.clone(), Some(snapshot), dto).await {
+      Ok(testreport) => testreports.push(testreport),
+      Err(e) => {
+        // No half built matrix, the reports already created are deleted with their results
+        for testreport in &testreports {
+          if let Err(e) = testresult_repo.delete_testreport_testresults(&testreport.id.to_hex()).await {
+            error!("Error rolling back testreports matrix: {}", e);
+          }
+        }
+        if let Err(e) = testreport_repo.delete_by_ids(&testreports.iter().map(|testreport| testreport.id).collect::<Vec<ObjectId>>()).await {
+          error!("Error rolling back testreports matrix: {}", e);
+        }
+        return Err(e);
+      },
+    }
+  }
+  Ok(Json(testreports))
 }
 
 pub fn get_testlists_routes() -> Vec<rocket::Route> {
-  routes![get_testlists, get_testlist, update_testlist, delete_testlist, get_testlist_testchecks, create_testlist_testcheck, update_testchecks_positions, create_testreport]
+  routes![get_testlists, get_testlist, update_testlist, delete_testlist, get_testlist_testchecks, create_testlist_testcheck, update_testchecks_positions, move_testchecks, copy_testchecks, export_testchecks_csv, export_testlist_xlsx, import_testchecks_csv, get_testlist_testsections, create_testlist_testsection, update_testsections_positions, create_testreport, create_testreports_matrix]
 }
 
 async fn allowed_for_testlist(jwts: JWTSessionAndUser, testlist_repo: &State<MongoRepo<Testlist>>, testlist_id: &str) -> Result<Testlist, JsonError>  {
@@ -235,3 +556,109 @@ async fn allowed_for_testlist(jwts: JWTSessionAndUser, testlist_repo: &State<Mon
     },
   }
 }
+
+async fn get_transfer_testchecks(testcheck_repo: &State<MongoRepo<Testcheck>>, testlist: &Testlist, data: TestchecksTransferDto) -> Result<Vec<Testcheck>, JsonError> {
+  let ids = parse_testchecks_ids(&data.testchecks_ids).map_err(JsonError::BadRequest)?;
+
+  let mut testchecks = match testcheck_repo.get_testchecks_by_ids(&ids).await {
+    Ok(testchecks) => testchecks,
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      return Err(JsonError::Internal("Error getting testchecks".to_string()));
+    },
+  };
+  if testchecks.len() != ids.len() {
+    warn!("Testchecks not found: {:?}", ids);
+    return Err(JsonError::NotFound("Testcheck not found".to_string()));
+  }
+  if testchecks.iter().any(|testcheck| testcheck.account_id != testlist.account_id) {
+    return Err(JsonError::Forbidden("Testchecks cannot be moved across accounts".to_string()));
+  }
+
+  testchecks.sort_by_key(|testcheck| ids.iter().position(|id| *id == testcheck.id));
+  Ok(testchecks)
+}
+
+async fn get_next_transfer_position(testcheck_repo: &State<MongoRepo<Testcheck>>, testlist_id: &str) -> Result<u16, JsonError> {
+  if let Err(e) = testcheck_repo.renumber_testlist_testchecks(testlist_id).await {
+    error!("Error updating testchecks positions: {}", e);
+    return Err(JsonError::Internal("Error updating testchecks positions".to_string()));
+  }
+  match testcheck_repo.get_next_position(testlist_id, None).await {
+    Ok(position) => Ok(position),
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      Err(JsonError::Internal("Error getting testchecks".to_string()))
+    },
+  }
+}
+
+async fn create_testlist_testreport(testcheck_repo: &State<MongoRepo<Testcheck>>, testsection_repo: &State<MongoRepo<Testsection>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, testlist: Testlist, testconfiguration: Option<TestreportConfiguration>, mut data: TestreportDto) -> Result<Testreport, JsonError> {
+  let testlist_id = testlist.id.to_hex();
+  let account_id = testlist.account_id.to_hex();
+  let project_id = testlist.project_id.to_hex();
+
+  data.tags_filter = data.tags_filter.map(|tags_filter| tags_filter.trim().to_string()).filter(|tags_filter| !tags_filter.is_empty());
+  data.build = data.build.map(normalize_testreport_build).transpose().map_err(JsonError::BadRequest)?;
+  let tags_expression = match data.tags_filter.as_deref() {
+    Some(tags_filter) => Some(TagsExpression::parse(tags_filter).map_err(JsonError::BadRequest)?),
+    None => None,
+  };
+
+  let testchecks = testcheck_repo.get_testlist_testchecks(&testlist_id).await;
+  if testchecks.is_err() {
+    error!("Error getting testchecks: {}", testlist_id);
+    return Err(JsonError::Internal("Error getting testchecks".to_string()));
+  }
+  let mut testchecks = testchecks.unwrap();
+  if let Some(tags_expression) = tags_expression {
+    testchecks.retain(|testcheck| tags_expression.matches(&testcheck.tags));
+    if testchecks.is_empty() {
+      return Err(JsonError::BadRequest("No testchecks match the tags filter".to_string()));
+    }
+  }
+
+  let testsections = match testsection_repo.get_testlist_testsections(&testlist_id).await {
+    Ok(testsections) => testsections,
+    Err(e) => {
+      error!("Error getting testsections: {}", e);
+      return Err(JsonError::Internal("Error getting testsections".to_string()));
+    },
+  };
+
+  let res = testreport_repo.create_testreport(&account_id, &project_id, testlist, testsections, testconfiguration, data).await;
+  match res {
+    Ok(inserted) => {
+      let id = inserted.inserted_id.as_object_id().unwrap().to_hex();
+      match testreport_repo.get_testreport_by_id(id.as_str()).await {
+        Ok(testreport) => match testreport {
+          Some(testreport) => {
+            for testcheck in testchecks {
+              let testcheck_id = testcheck.id.to_hex();
+              let testresult = testresult_repo.create_testresult(&id, testcheck).await;
+              if testresult.is_err() {
+                let _ = testresult_repo.delete_testreport_testresults(id.as_str()).await;
+                let _ = testreport_repo.delete_testreport(id.clone()).await;
+                error!("Error creating testresult for testcheck: {}", testcheck_id);
+                return Err(JsonError::Internal("Error creating testresult".to_string()));
+              }
+            }
+            Ok(testreport)
+          },
+          None => {
+            warn!("Testreport not found: {}", id);
+            Err(JsonError::NotFound("Testreport not found".to_string()))
+          },
+        },
+        Err(e) => {
+          error!("Error getting testreport: {}", e);
+          Err(JsonError::Internal("Error getting testreport".to_string()))
+        },
+      }
+    },
+    Err(e) => {
+      error!("Error creating testreport: {}", e);
+      Err(JsonError::Internal("Error creating testreport".to_string()))
+    },
+  }
+}
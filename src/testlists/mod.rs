@@ -1,3 +1,4 @@
 pub mod endpoints;
 pub mod schema;
 pub mod service;
+pub mod xlsx;
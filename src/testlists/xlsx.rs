@@ -0,0 +1,203 @@
+// Excel workbook of the checks of a testlist: a summary sheet counting the checks by mode with
+// formulas over one sheet per section or tag.
+
+use mongodb::bson::oid::ObjectId;
+use rust_xlsxwriter::{Color, ConditionalFormatCell, ConditionalFormatCellRule, Format, Formula, Workbook, Worksheet, XlsxError};
+
+use crate::{service::xlsx::{write_xlsx_datetime, write_xlsx_table_header, xlsx_header_format, xlsx_sheet_name, xlsx_sheet_reference, xlsx_text}, testchecks::schema::{Testcheck, TestcheckMode}, testreports::{export::{get_section_paths, TestreportGroupBy}, schema::TestreportSection}, testsections::schema::Testsection};
+
+use super::schema::Testlist;
+
+const SUMMARY_COLUMNS: [(&str, f64); 7] = [
+  ("Group", 40.0), ("Checks", 10.0), ("Manual", 10.0), ("Automated", 11.0), ("Both", 10.0), ("With automation key", 20.0), ("Automated share", 16.0),
+];
+const TESTCHECKS_COLUMNS: [(&str, f64); 11] = [
+  ("Name", 40.0), ("Section", 24.0), ("Tags", 20.0), ("Mode", 12.0), ("Description", 50.0), ("Expected", 40.0),
+  ("Automation key", 30.0), ("Automation aliases", 30.0), ("External key", 20.0), ("Revision", 10.0), ("Updated", 17.0),
+];
+
+struct TestchecksGroup<'a> {
+  name: String,
+  testchecks: Vec<&'a Testcheck>,
+}
+
+pub fn get_testlist_xlsx(testlist: &Testlist, testsections: &[Testsection], testchecks: &[Testcheck], group_by: TestreportGroupBy) -> Result<Vec<u8>, XlsxError> {
+  let sections: Vec<TestreportSection> = testsections.iter().map(|testsection| TestreportSection {
+    testsection_id: testsection.id,
+    parent_id: testsection.parent_id,
+    name: testsection.name.clone(),
+    description: testsection.description.clone(),
+    position: testsection.position,
+  }).collect();
+  let section_paths = get_section_paths(&sections);
+  let groups = group_testchecks(testchecks, &section_paths, group_by);
+
+  let mut used = vec!["Summary".to_string()];
+  let sheet_names: Vec<String> = groups.iter().map(|group| xlsx_sheet_name(&group.name, &mut used)).collect();
+
+  let mut workbook = Workbook::new();
+  let summary = workbook.add_worksheet();
+  summary.set_name("Summary")?;
+  write_summary(summary, testlist, testchecks, &groups, &sheet_names, group_by)?;
+
+  for (group, sheet_name) in groups.iter().zip(&sheet_names) {
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet_name)?;
+    write_testchecks(worksheet, &group.testchecks, &section_paths)?;
+  }
+  workbook.save_to_buffer()
+}
+
+// Same groups as the report exports, checks in testlist order
+fn group_testchecks<'a>(testchecks: &'a [Testcheck], section_paths: &[(ObjectId, String)], group_by: TestreportGroupBy) -> Vec<TestchecksGroup<'a>> {
+  let mut groups: Vec<TestchecksGroup> = Vec::new();
+  match group_by {
+    TestreportGroupBy::Section => {
+      for (section_id, path) in section_paths {
+        groups.push(TestchecksGroup { name: path.clone(), testchecks: testchecks.iter().filter(|testcheck| testcheck.section_id == Some(*section_id)).collect() });
+      }
+      groups.push(TestchecksGroup {
+        name: "No section".to_string(),
+        testchecks: testchecks.iter()
+          .filter(|testcheck| testcheck.section_id.is_none_or(|section_id| !section_paths.iter().any(|(id, _)| *id == section_id)))
+          .collect(),
+      });
+    },
+    TestreportGroupBy::Tag => {
+      let mut tags: Vec<&String> = testchecks.iter().flat_map(|testcheck| testcheck.tags.iter()).collect();
+      tags.sort();
+      tags.dedup();
+      for tag in tags {
+        groups.push(TestchecksGroup { name: tag.clone(), testchecks: testchecks.iter().filter(|testcheck| testcheck.tags.contains(tag)).collect() });
+      }
+      groups.push(TestchecksGroup { name: "Untagged".to_string(), testchecks: testchecks.iter().filter(|testcheck| testcheck.tags.is_empty()).collect() });
+    },
+  }
+  groups.retain(|group| !group.testchecks.is_empty());
+  for group in &mut groups {
+    group.testchecks.sort_by_key(|testcheck| (testcheck.position, testcheck.name.clone()));
+  }
+  groups
+}
+
+fn get_mode_label(mode: TestcheckMode) -> &'static str {
+  match mode {
+    TestcheckMode::Manual => "Manual",
+    TestcheckMode::Automated => "Automated",
+    TestcheckMode::Both => "Both",
+  }
+}
+
+// Manual, automated and both, then the checks with an automation key
+fn count_testchecks<'a>(testchecks: impl Iterator<Item = &'a Testcheck>) -> [u32; 5] {
+  let mut counts = [0; 5];
+  for testcheck in testchecks {
+    counts[0] += 1;
+    match testcheck.mode {
+      TestcheckMode::Manual => counts[1] += 1,
+      TestcheckMode::Automated => counts[2] += 1,
+      TestcheckMode::Both => counts[3] += 1,
+    }
+    if testcheck.automation_key.is_some() {
+      counts[4] += 1;
+    }
+  }
+  counts
+}
+
+fn get_automated_share(counts: &[u32; 5]) -> f64 {
+  if counts[0] == 0 { 0.0 } else { (counts[2] + counts[3]) as f64 / counts[0] as f64 }
+}
+
+fn write_summary(worksheet: &mut Worksheet, testlist: &Testlist, testchecks: &[Testcheck], groups: &[TestchecksGroup], sheet_names: &[String], group_by: TestreportGroupBy) -> Result<(), XlsxError> {
+  let percent_format = Format::new().set_num_format("0.0%");
+  let total_format = Format::new().set_bold();
+  let total_percent_format = Format::new().set_bold().set_num_format("0.0%");
+
+  for (index, (group, sheet_name)) in groups.iter().zip(sheet_names).enumerate() {
+    let row = index as u32 + 1;
+    let last_row = group.testchecks.len() + 1;
+    let names = format!("{}!$A$2:$A${}", xlsx_sheet_reference(sheet_name), last_row);
+    let modes = format!("{}!$D$2:$D${}", xlsx_sheet_reference(sheet_name), last_row);
+    let keys = format!("{}!$G$2:$G${}", xlsx_sheet_reference(sheet_name), last_row);
+    let counts = count_testchecks(group.testchecks.iter().copied());
+    worksheet.write_url_with_text(row, 0, format!("internal:{}!A1", xlsx_sheet_reference(sheet_name)).as_str(), xlsx_text(&group.name))?;
+    let formulas = [
+      format!("=COUNTA({})", names),
+      format!("=COUNTIF({},\"{}\")", modes, get_mode_label(TestcheckMode::Manual)),
+      format!("=COUNTIF({},\"{}\")", modes, get_mode_label(TestcheckMode::Automated)),
+      format!("=COUNTIF({},\"{}\")", modes, get_mode_label(TestcheckMode::Both)),
+      format!("=COUNTA({})", keys),
+    ];
+    for (col, (formula, result)) in formulas.into_iter().zip(counts).enumerate() {
+      worksheet.write_formula(row, col as u16 + 1, Formula::new(formula).set_result(result.to_string()))?;
+    }
+    let share = Formula::new(format!("=IF(B{0}=0,0,(D{0}+E{0})/B{0})", row + 1)).set_result(get_automated_share(&counts).to_string());
+    worksheet.write_formula_with_format(row, 6, share, &percent_format)?;
+  }
+
+  let total_row = groups.len() as u32 + 1;
+  let counts = count_testchecks(testchecks.iter());
+  worksheet.write_string_with_format(total_row, 0, "Total", &total_format)?;
+  for (index, total) in counts.into_iter().enumerate() {
+    let col = index as u16 + 1;
+    match group_by {
+      TestreportGroupBy::Section => {
+        let column = (b'B' + index as u8) as char;
+        let formula = Formula::new(format!("=SUM({0}2:{0}{1})", column, total_row)).set_result(total.to_string());
+        worksheet.write_formula_with_format(total_row, col, formula, &total_format)?;
+      },
+      // A check is counted in each of its tags, the testlist totals are not the sum of the sheets
+      TestreportGroupBy::Tag => {
+        worksheet.write_number_with_format(total_row, col, total, &total_format)?;
+      },
+    }
+  }
+  let share = Formula::new(format!("=IF(B{0}=0,0,(D{0}+E{0})/B{0})", total_row + 1)).set_result(get_automated_share(&counts).to_string());
+  worksheet.write_formula_with_format(total_row, 6, share, &total_percent_format)?;
+  write_xlsx_table_header(worksheet, &SUMMARY_COLUMNS, groups.len() as u32)?;
+
+  let header_format = xlsx_header_format();
+  let row = total_row + 2;
+  worksheet.write_string_with_format(row, 0, "Testlist", &header_format)?;
+  worksheet.write_string(row, 1, xlsx_text(&testlist.name))?;
+  if !testlist.description.is_empty() {
+    worksheet.write_string_with_format(row + 1, 0, "Description", &header_format)?;
+    worksheet.write_string(row + 1, 1, xlsx_text(&testlist.description))?;
+  }
+  Ok(())
+}
+
+fn write_testchecks(worksheet: &mut Worksheet, testchecks: &[&Testcheck], section_paths: &[(ObjectId, String)]) -> Result<(), XlsxError> {
+  let wrap_format = Format::new().set_text_wrap();
+  for (index, testcheck) in testchecks.iter().enumerate() {
+    let row = index as u32 + 1;
+    let section = testcheck.section_id.and_then(|section_id| section_paths.iter().find(|(id, _)| *id == section_id)).map(|(_, path)| path.as_str()).unwrap_or("");
+    worksheet.write_string(row, 0, xlsx_text(&testcheck.name))?;
+    worksheet.write_string(row, 1, xlsx_text(section))?;
+    worksheet.write_string(row, 2, xlsx_text(&testcheck.tags.join(", ")))?;
+    worksheet.write_string(row, 3, get_mode_label(testcheck.mode))?;
+    worksheet.write_string_with_format(row, 4, xlsx_text(&testcheck.description), &wrap_format)?;
+    worksheet.write_string_with_format(row, 5, xlsx_text(&testcheck.expected), &wrap_format)?;
+    // Left empty without a key so that the summary counts the filled cells
+    if let Some(automation_key) = &testcheck.automation_key {
+      worksheet.write_string(row, 6, xlsx_text(automation_key))?;
+    }
+    worksheet.write_string(row, 7, xlsx_text(&testcheck.automation_aliases.join(", ")))?;
+    worksheet.write_string(row, 8, xlsx_text(testcheck.external_key.as_deref().unwrap_or("")))?;
+    worksheet.write_number(row, 9, testcheck.revision)?;
+    write_xlsx_datetime(worksheet, row, 10, testcheck.updated_at)?;
+  }
+
+  let last_row = (testchecks.len() as u32).max(1);
+  let mode_formats = [
+    (TestcheckMode::Automated, 0x006100, 0xC6EFCE),
+    (TestcheckMode::Both, 0x1F4E79, 0xDDEBF7),
+  ];
+  for (mode, font_color, background_color) in mode_formats {
+    let format = Format::new().set_font_color(Color::RGB(font_color)).set_background_color(Color::RGB(background_color));
+    let conditional_format = ConditionalFormatCell::new().set_rule(ConditionalFormatCellRule::EqualTo(get_mode_label(mode))).set_format(format);
+    worksheet.add_conditional_format(1, 3, last_row, 3, &conditional_format)?;
+  }
+  write_xlsx_table_header(worksheet, &TESTCHECKS_COLUMNS, testchecks.len() as u32)
+}
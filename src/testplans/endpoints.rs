@@ -0,0 +1,214 @@
+use bson::{oid::ObjectId, DateTime};
+use log::{error, warn};
+use rocket::{delete, get, put, routes, serde::json::Json, State};
+use crate::{service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testreports::{schema::{Testreport, TestreportProgress}, service::get_testreport_progress}, testresults::schema::Testresult, users::{roles::is_admin, schema::User}};
+
+use super::{schema::{Testplan, TestplanDto, TestplanProgress}, service::get_testplan_progress};
+
+#[get("/<id>")]
+async fn get_testplan(jwt: Result<JWT, JsonError>, id: &str, testplan_repo: &State<MongoRepo<Testplan>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testplan>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  match allowed_for_testplan(jwts, testplan_repo, id).await {
+    Ok(testplan) => Ok(Json(testplan)),
+    Err(e) => Err(e)
+  }
+}
+
+#[put("/<id>", format = "json", data = "<data>")]
+async fn update_testplan(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestplanDto>, testplan_repo: &State<MongoRepo<Testplan>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testplan>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testplan = allowed_for_testplan(jwts, testplan_repo, id).await?;
+  let mut data = data.into_inner();
+  if data.version.is_empty() {
+    data.version = testplan.version;
+  }
+  let owner_id = get_testplan_owner_id(users_repo, testplan.account_id, data.owner_id.as_deref()).await?;
+  let target_date = get_testplan_target_date(data.target_date.as_deref())?;
+
+  match testplan_repo.update_testplan(id.to_string(), owner_id, target_date, data).await {
+    Ok(updated) => {
+      if updated.matched_count == 0 {
+        warn!("Testplan not found: {}", id);
+        return Err(JsonError::NotFound("Testplan not found".to_string()));
+      }
+      get_testplan_by_id(testplan_repo, id).await.map(Json)
+    },
+    Err(e) => {
+      error!("Error updating testplan: {}", e);
+      Err(JsonError::Internal("Error updating testplan".to_string()))
+    },
+  }
+}
+
+#[delete("/<id>")]
+async fn delete_testplan(jwt: Result<JWT, JsonError>, id: &str, testplan_repo: &State<MongoRepo<Testplan>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testplan>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testplan = allowed_for_testplan(jwts, testplan_repo, id).await?;
+
+  match testplan_repo.delete_testplan(id.to_string()).await {
+    Ok(deleted) => {
+      if deleted.deleted_count == 0 {
+        warn!("Testplan not found: {}", id);
+        return Err(JsonError::NotFound("Testplan not found".to_string()));
+      }
+      Ok(Json(testplan))
+    },
+    Err(e) => {
+      error!("Error deleting testplan: {}", e);
+      Err(JsonError::Internal("Error deleting testplan".to_string()))
+    },
+  }
+}
+
+#[get("/<testplan_id>/testreports")]
+async fn get_testplan_testreports(jwt: Result<JWT, JsonError>, testplan_id: &str, testplan_repo: &State<MongoRepo<Testplan>>, testreport_repo: &State<MongoRepo<Testreport>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Vec<Testreport>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testplan = allowed_for_testplan(jwts, testplan_repo, testplan_id).await?;
+
+  match testreport_repo.get_testreports_by_ids(&testplan.testreport_ids).await {
+    Ok(testreports) => Ok(Json(testreports)),
+    Err(e) => {
+      error!("Error getting testreports: {}", e);
+      Err(JsonError::Internal("Error getting testreports".to_string()))
+    },
+  }
+}
+
+#[put("/<testplan_id>/testreports", format = "json", data = "<data>")]
+async fn update_testplan_testreports(jwt: Result<JWT, JsonError>, testplan_id: &str, data: Json<Vec<String>>, testplan_repo: &State<MongoRepo<Testplan>>, testreport_repo: &State<MongoRepo<Testreport>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testplan>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testplan = allowed_for_testplan(jwts, testplan_repo, testplan_id).await?;
+
+  let mut testreport_ids: Vec<ObjectId> = vec![];
+  for id in data.into_inner() {
+    match ObjectId::parse_str(&id) {
+      Ok(oid) => {
+        if !testreport_ids.contains(&oid) {
+          testreport_ids.push(oid);
+        }
+      },
+      Err(_) => return Err(JsonError::BadRequest(format!("Invalid testreport id: {}", id))),
+    }
+  }
+
+  match testreport_repo.get_testreports_by_ids(&testreport_ids).await {
+    Ok(testreports) => {
+      if testreports.len() != testreport_ids.len() {
+        return Err(JsonError::NotFound("Testreport not found".to_string()));
+      }
+      if testreports.iter().any(|testreport| testreport.project_id != testplan.project_id) {
+        return Err(JsonError::BadRequest("Testreports must belong to the testplan project".to_string()));
+      }
+    },
+    Err(e) => {
+      error!("Error getting testreports: {}", e);
+      return Err(JsonError::Internal("Error getting testreports".to_string()));
+    },
+  }
+
+  match testplan_repo.update_testplan_testreports(testplan_id, testreport_ids).await {
+    Ok(_) => get_testplan_by_id(testplan_repo, testplan_id).await.map(Json),
+    Err(e) => {
+      error!("Error updating testplan testreports: {}", e);
+      Err(JsonError::Internal("Error updating testplan testreports".to_string()))
+    },
+  }
+}
+
+#[get("/<testplan_id>/progress")]
+async fn get_testplan_progress_summary(jwt: Result<JWT, JsonError>, testplan_id: &str, testplan_repo: &State<MongoRepo<Testplan>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<TestplanProgress>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testplan = allowed_for_testplan(jwts, testplan_repo, testplan_id).await?;
+
+  let testreports = get_testreports_progress(testreport_repo, testresult_repo, &testplan.testreport_ids).await?;
+  Ok(Json(get_testplan_progress(&testplan, testreports)))
+}
+
+pub fn get_testplans_routes() -> Vec<rocket::Route> {
+  routes![get_testplan, update_testplan, delete_testplan, get_testplan_testreports, update_testplan_testreports, get_testplan_progress_summary]
+}
+
+pub async fn get_testreports_progress(testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, testreport_ids: &[ObjectId]) -> Result<Vec<TestreportProgress>, JsonError> {
+  let testreports = match testreport_repo.get_testreports_by_ids(testreport_ids).await {
+    Ok(testreports) => testreports,
+    Err(e) => {
+      error!("Error getting testreports: {}", e);
+      return Err(JsonError::Internal("Error getting testreports".to_string()));
+    },
+  };
+
+  let mut progress = vec![];
+  for testreport in testreports {
+    match testresult_repo.get_testreport_testresults(&testreport.id.to_hex()).await {
+      Ok(testresults) => progress.push(get_testreport_progress(&testreport, &testresults)),
+      Err(e) => {
+        error!("Error getting testresults: {}", e);
+        return Err(JsonError::Internal("Error getting testresults".to_string()));
+      },
+    }
+  }
+  Ok(progress)
+}
+
+// The owner must be a member of the testplan account
+pub async fn get_testplan_owner_id(users_repo: &State<MongoRepo<User>>, account_id: ObjectId, owner_id: Option<&str>) -> Result<Option<ObjectId>, JsonError> {
+  let owner_id = match owner_id {
+    Some(owner_id) => owner_id,
+    None => return Ok(None),
+  };
+  match users_repo.get_user_by_id(owner_id).await {
+    Ok(Some(user)) => {
+      let is_member = user.accounts.as_ref().is_some_and(|accounts| accounts.iter().any(|account| account.account_id == account_id));
+      if !is_member {
+        return Err(JsonError::BadRequest("Testplan owner must be a member of the account".to_string()));
+      }
+      Ok(Some(user.id))
+    },
+    Ok(None) => Err(JsonError::BadRequest("Testplan owner not found".to_string())),
+    Err(e) => {
+      error!("Error getting user: {}", e);
+      Err(JsonError::Internal("Error getting user".to_string()))
+    },
+  }
+}
+
+pub fn get_testplan_target_date(target_date: Option<&str>) -> Result<Option<DateTime>, JsonError> {
+  match target_date {
+    Some(target_date) => match chrono::DateTime::parse_from_rfc3339(target_date) {
+      Ok(date) => Ok(Some(DateTime::from_chrono(date.with_timezone(&chrono::Utc)))),
+      Err(_) => Err(JsonError::BadRequest("Invalid testplan target date".to_string())),
+    },
+    None => Ok(None),
+  }
+}
+
+pub async fn get_testplan_by_id(testplan_repo: &State<MongoRepo<Testplan>>, id: &str) -> Result<Testplan, JsonError> {
+  match testplan_repo.get_testplan_by_id(id).await {
+    Ok(testplan) => match testplan {
+      Some(testplan) => Ok(testplan),
+      None => {
+        warn!("Testplan not found: {}", id);
+        Err(JsonError::NotFound("Testplan not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testplan: {}", e);
+      Err(JsonError::Internal("Error getting testplan".to_string()))
+    },
+  }
+}
+
+async fn allowed_for_testplan(jwts: JWTSessionAndUser, testplan_repo: &State<MongoRepo<Testplan>>, testplan_id: &str) -> Result<Testplan, JsonError>  {
+  if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
+    return Err(JsonError::Forbidden(
+      "You are not allowed to retrieve this testplan".to_string(),
+    ));
+  }
+  let testplan = get_testplan_by_id(testplan_repo, testplan_id).await?;
+  if let Some(accounts) = &jwts.user.accounts {
+    if !accounts.iter().any(|account| account.account_id == testplan.account_id) && !is_admin(&jwts.user) {
+      return Err(JsonError::Forbidden("You are not allowed to retrieve this testplan".to_string()));
+    }
+  }
+  Ok(testplan)
+}
@@ -0,0 +1,78 @@
+use bson::DateTime;
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::{service::db::{serialize_datetime, serialize_object_id, serialize_object_ids, serialize_option_datetime, serialize_option_object_id}, testreports::schema::TestreportProgress};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestplanStatus {
+  Planned,
+  InProgress,
+  Completed,
+  Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestplanReadiness {
+  Ready,
+  InProgress,
+  AtRisk,
+  NotReady,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Testplan {
+  #[serde(rename = "_id", serialize_with = "serialize_object_id")]
+  pub id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub account_id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub project_id: ObjectId,
+  pub name: String,
+  pub description: String,
+  // Matches the Project.version the plan is meant to release
+  pub version: String,
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub owner_id: Option<ObjectId>,
+  #[serde(serialize_with = "serialize_option_datetime")]
+  pub target_date: Option<DateTime>,
+  pub status: TestplanStatus,
+  #[serde(serialize_with = "serialize_object_ids")]
+  pub testreport_ids: Vec<ObjectId>,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub created_at: DateTime,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestplanDto {
+  pub name: String,
+  pub description: String,
+  // Defaults to the project version when empty
+  #[serde(default)]
+  pub version: String,
+  pub owner_id: Option<String>,
+  // RFC 3339 date
+  pub target_date: Option<String>,
+  pub status: TestplanStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestplanProgress {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testplan_id: ObjectId,
+  pub total: u32,
+  pub executed: u32,
+  pub passed: u32,
+  pub failed: u32,
+  pub blocked: u32,
+  // Percentage of executed results
+  pub progress: f64,
+  // Percentage of passed results over the executed ones
+  pub pass_rate: f64,
+  pub readiness: TestplanReadiness,
+  pub testreports: Vec<TestreportProgress>,
+}
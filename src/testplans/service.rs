@@ -0,0 +1,126 @@
+use std::error::Error;
+
+use bson::DateTime;
+use mongodb::{
+  bson::{self, doc, oid::ObjectId}, options::{FindOneOptions, FindOptions}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
+};
+use crate::{projects::schema::Project, service::db::{ self, MongoRepo}, testreports::schema::TestreportProgress};
+use super::schema::{Testplan, TestplanDto, TestplanProgress, TestplanReadiness};
+use rocket::futures::TryStreamExt;
+
+pub fn get_testplans_repo(client: Client) -> MongoRepo<Testplan> {
+  return db::get_mongo_repo(client, "test_boss", "testplans");
+}
+
+impl MongoRepo<Testplan> {
+  pub async fn get_project_testplans(&self, project_id: &str) -> Result<Vec<Testplan>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "project_id": ObjectId::parse_str(project_id)? };
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testplans: Vec<Testplan> = cursor.try_collect().await?;
+    Ok(testplans)
+  }
+
+  pub async fn get_project_version_testplan(&self, project: &Project) -> Result<Option<Testplan>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "project_id": project.id, "version": &project.version };
+    let options = FindOneOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let result = self.col.find_one(filter, options).await?;
+    Ok(result)
+  }
+
+  pub async fn get_testplan_by_id(&self, id: &str) -> Result<Option<Testplan>, Box<dyn Error + Send + Sync>> {
+    let oid = ObjectId::parse_str(id)?;
+    let filter = doc! { "_id": oid };
+    let result = self.col.find_one(filter, None).await?;
+    Ok(result)
+  }
+
+  pub async fn create_testplan(&self, project: &Project, owner_id: Option<ObjectId>, target_date: Option<DateTime>, data: TestplanDto) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let version = if data.version.is_empty() { project.version.clone() } else { data.version };
+    let new_doc = Testplan {
+      id: ObjectId::new(),
+      account_id: project.account_id,
+      project_id: project.id,
+      name: data.name,
+      description: data.description,
+      version,
+      owner_id,
+      target_date,
+      status: data.status,
+      testreport_ids: vec![],
+      created_at: now,
+      updated_at: now,
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testplan(&self, id: String, owner_id: Option<ObjectId>, target_date: Option<DateTime>, data: TestplanDto) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let now = chrono::Utc::now();
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let upd_doc = doc! { "$set": {
+      "name": data.name,
+      "description": data.description,
+      "version": data.version,
+      "owner_id": owner_id,
+      "target_date": target_date,
+      "status": bson::to_bson(&data.status)?,
+      "updated_at": DateTime::from_chrono(now)
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testplan_testreports(&self, id: &str, testreport_ids: Vec<ObjectId>) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let now = chrono::Utc::now();
+    let filter = doc! { "_id": ObjectId::parse_str(id)? };
+    let upd_doc = doc! { "$set": {
+      "testreport_ids": testreport_ids,
+      "updated_at": DateTime::from_chrono(now)
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn delete_testplan(&self, id: String) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let result = self.col.delete_one(filter, None).await?;
+    Ok(result)
+  }
+}
+
+pub fn get_testplan_progress(testplan: &Testplan, testreports: Vec<TestreportProgress>) -> TestplanProgress {
+  let total: u32 = testreports.iter().map(|testreport| testreport.total).sum();
+  let executed: u32 = testreports.iter().map(|testreport| testreport.executed).sum();
+  let passed: u32 = testreports.iter().map(|testreport| testreport.passed).sum();
+  let failed: u32 = testreports.iter().map(|testreport| testreport.failed).sum();
+  let blocked: u32 = testreports.iter().map(|testreport| testreport.blocked).sum();
+
+  let progress = if total > 0 { executed as f64 * 100.0 / total as f64 } else { 0.0 };
+  let pass_rate = if executed > 0 { passed as f64 * 100.0 / executed as f64 } else { 0.0 };
+
+  let overdue = testplan.target_date.is_some_and(|target_date| target_date < DateTime::now());
+  let readiness = if failed > 0 || blocked > 0 {
+    TestplanReadiness::NotReady
+  } else if total > 0 && executed == total {
+    TestplanReadiness::Ready
+  } else if overdue {
+    TestplanReadiness::AtRisk
+  } else {
+    TestplanReadiness::InProgress
+  };
+
+  TestplanProgress {
+    testplan_id: testplan.id,
+    total,
+    executed,
+    passed,
+    failed,
+    blocked,
+    progress,
+    pass_rate,
+    readiness,
+    testreports,
+  }
+}
@@ -0,0 +1,141 @@
+use crate::{service::{csv::csv_row, markdown::{markdown_cell, markdown_text}}, testresults::schema::{Testresult, TestresultStatus}};
+
+use super::{schema::{Testreport, TestreportChange, TestreportComparison, TestreportComparisonEntry}, service::get_testreport_progress};
+
+pub fn compare_testreports(base: &Testreport, base_testresults: &[Testresult], target: &Testreport, target_testresults: &[Testresult]) -> TestreportComparison {
+  let mut entries = vec![];
+
+  let mut sorted: Vec<&Testresult> = target_testresults.iter().collect();
+  sorted.sort_by_key(|testresult| testresult.position);
+  for testresult in sorted {
+    let target_status = testresult.status();
+    let entry = match base_testresults.iter().find(|base_testresult| base_testresult.testcheck_id == testresult.testcheck_id) {
+      Some(base_testresult) => {
+        let base_status = base_testresult.status();
+        TestreportComparisonEntry {
+          testcheck_id: testresult.testcheck_id,
+          name: testresult.name.clone(),
+          change: get_change(base_status, target_status),
+          base_status: Some(base_status),
+          target_status: Some(target_status),
+          notes_changed: base_testresult.notes != testresult.notes,
+          base_notes: base_testresult.notes.clone(),
+          target_notes: testresult.notes.clone(),
+        }
+      },
+      None => TestreportComparisonEntry {
+        testcheck_id: testresult.testcheck_id,
+        name: testresult.name.clone(),
+        change: TestreportChange::Added,
+        base_status: None,
+        target_status: Some(target_status),
+        notes_changed: false,
+        base_notes: "".to_string(),
+        target_notes: testresult.notes.clone(),
+      },
+    };
+    if entry.change != TestreportChange::Unchanged || entry.notes_changed {
+      entries.push(entry);
+    }
+  }
+
+  let mut removed: Vec<&Testresult> = base_testresults.iter()
+    .filter(|base_testresult| !target_testresults.iter().any(|testresult| testresult.testcheck_id == base_testresult.testcheck_id))
+    .collect();
+  removed.sort_by_key(|testresult| testresult.position);
+  for testresult in removed {
+    entries.push(TestreportComparisonEntry {
+      testcheck_id: testresult.testcheck_id,
+      name: testresult.name.clone(),
+      change: TestreportChange::Removed,
+      base_status: Some(testresult.status()),
+      target_status: None,
+      notes_changed: false,
+      base_notes: testresult.notes.clone(),
+      target_notes: "".to_string(),
+    });
+  }
+
+  let count = |change: TestreportChange| entries.iter().filter(|entry| entry.change == change).count() as u32;
+  TestreportComparison {
+    base: get_testreport_progress(base, base_testresults),
+    target: get_testreport_progress(target, target_testresults),
+    newly_failing: count(TestreportChange::NewlyFailing),
+    newly_passing: count(TestreportChange::NewlyPassing),
+    still_failing: count(TestreportChange::StillFailing),
+    added: count(TestreportChange::Added),
+    removed: count(TestreportChange::Removed),
+    notes_changed: entries.iter().filter(|entry| entry.notes_changed).count() as u32,
+    entries,
+  }
+}
+
+// Blocked results count as failing, not executed ones keep the previous outcome
+fn get_change(base: TestresultStatus, target: TestresultStatus) -> TestreportChange {
+  let is_failing = |status: TestresultStatus| status == TestresultStatus::Failed || status == TestresultStatus::Blocked;
+  match (is_failing(base), target) {
+    (true, TestresultStatus::Failed | TestresultStatus::Blocked) => TestreportChange::StillFailing,
+    (false, TestresultStatus::Failed | TestresultStatus::Blocked) => TestreportChange::NewlyFailing,
+    (true, TestresultStatus::Passed) => TestreportChange::NewlyPassing,
+    _ => TestreportChange::Unchanged,
+  }
+}
+
+fn format_status(status: Option<TestresultStatus>) -> String {
+  status.map(|status| status.to_string()).unwrap_or_default()
+}
+
+pub fn get_testreport_comparison_csv(comparison: &TestreportComparison) -> String {
+  let mut csv = csv_row(&["change", "testcheck_id", "name", "base_status", "target_status", "notes_changed", "base_notes", "target_notes"]);
+  for entry in &comparison.entries {
+    csv.push_str(&csv_row(&[
+      &entry.change.to_string(),
+      &entry.testcheck_id.to_hex(),
+      &entry.name,
+      &format_status(entry.base_status),
+      &format_status(entry.target_status),
+      if entry.notes_changed { "true" } else { "false" },
+      &entry.base_notes,
+      &entry.target_notes,
+    ]));
+  }
+  csv
+}
+
+pub fn get_testreport_comparison_markdown(comparison: &TestreportComparison) -> String {
+  let mut md = format!("# {} → {}\n\n", markdown_text(&comparison.base.name), markdown_text(&comparison.target.name));
+  md.push_str("| | Base | Target |\n|---|---|---|\n");
+  md.push_str(&format!("| Total | {} | {} |\n", comparison.base.total, comparison.target.total));
+  md.push_str(&format!("| Passed | {} | {} |\n", comparison.base.passed, comparison.target.passed));
+  md.push_str(&format!("| Failed | {} | {} |\n", comparison.base.failed, comparison.target.failed));
+  md.push_str(&format!("| Blocked | {} | {} |\n", comparison.base.blocked, comparison.target.blocked));
+  md.push_str(&format!("| Not executed | {} | {} |\n", comparison.base.total - comparison.base.executed, comparison.target.total - comparison.target.executed));
+
+  let groups = [
+    (TestreportChange::NewlyFailing, "Newly failing"),
+    (TestreportChange::NewlyPassing, "Newly passing"),
+    (TestreportChange::StillFailing, "Still failing"),
+    (TestreportChange::Added, "Added"),
+    (TestreportChange::Removed, "Removed"),
+  ];
+  for (change, title) in groups {
+    let entries: Vec<&TestreportComparisonEntry> = comparison.entries.iter().filter(|entry| entry.change == change).collect();
+    if entries.is_empty() {
+      continue;
+    }
+    md.push_str(&format!("\n## {} ({})\n\n", title, entries.len()));
+    for entry in entries {
+      md.push_str(&format!("- {} ({} → {})\n", markdown_text(&entry.name), format_status(entry.base_status), format_status(entry.target_status)));
+    }
+  }
+
+  let notes: Vec<&TestreportComparisonEntry> = comparison.entries.iter().filter(|entry| entry.notes_changed).collect();
+  if !notes.is_empty() {
+    md.push_str(&format!("\n## Notes changed ({})\n\n", notes.len()));
+    md.push_str("| Check | Base notes | Target notes |\n|---|---|---|\n");
+    for entry in notes {
+      md.push_str(&format!("| {} | {} | {} |\n", markdown_cell(&entry.name), markdown_cell(&entry.base_notes), markdown_cell(&entry.target_notes)));
+    }
+  }
+  md
+}
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
+
+use bson::{oid::ObjectId, DateTime};
 use log::{error, warn};
-use rocket::{delete, get, post, put, routes, serde::json::Json, State};
-use crate::{service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testresults::schema::{Testresult, TestresultDto}, testreports::schema::TestreportDto, users::{roles::is_admin, schema::User}};
+use rocket::{delete, get, http::ContentType, post, put, routes, serde::json::Json, State};
+use crate::{apitokens::{schema::Apitoken, token::{authorize_project_apitoken, ApiToken}}, ingestion::ctrf::get_testreport_ctrf, projects::schema::Project, testlists::schema::Testlist, service::{config::get_app_url, db::MongoRepo, download::Download, http_errors::JsonError, xlsx::xlsx_content_type}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testresults::schema::{Testresult, TestresultsAssignmentDto}, testreports::{export::{get_user_names, TestreportGroupBy}, html::get_testreport_html, markdown::get_testreport_markdown, pdf::{get_testreport_fingerprint, get_testreport_pdf}, xlsx::get_testreport_xlsx, compare::{compare_testreports, get_testreport_comparison_csv, get_testreport_comparison_markdown}, schema::{TestreportComparison, TestreportDto, TestreportFingerprint, TestreportRerunDto, TestreportSectionSummary, TestreportSignoff, TestreportStatus, TestreportTransition, TestreportWorkload}, service::{get_testreport_progress, get_testreport_workload, is_testreport_reopening, is_testreport_transition_allowed, normalize_testreport_build, summarize_testreport_sections}}, users::{roles::is_admin, schema::User}};
 
 use super::schema::Testreport;
 
@@ -28,13 +31,24 @@ async fn get_testreport(jwt: Result<JWT, JsonError>, id: &str, testreport_repo:
 
 
 #[put("/<id>", format = "json", data = "<data>")]
-async fn update_testreport(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestreportDto>, testreport_repo: &State<MongoRepo<Testreport>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testreport>, JsonError> {
+async fn update_testreport(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestreportDto>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, project_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testreport>, JsonError> {
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
-  allowed_for_testreport(jwts, testreport_repo, id).await?;
+  let user = jwts.user.clone();
+  let testreport = allowed_for_testreport(jwts, testreport_repo, id).await?;
+  let mut data = data.into_inner();
+  let status = data.status.take().unwrap_or(testreport.status);
+  data.build = data.build.map(normalize_testreport_build).transpose().map_err(JsonError::BadRequest)?;
 
-  match testreport_repo.update_testreport(id.to_string(), data.into_inner()).await {
+  if status == testreport.status && testreport.status == TestreportStatus::SignedOff {
+    return Err(JsonError::Forbidden("Signed off testreports are locked, reopen the testreport first".to_string()));
+  }
+  if status != testreport.status {
+    change_testreport_status(testreport_repo, testresult_repo, project_repo, &user, &testreport, status, data.justification.clone()).await?;
+  }
+
+  match testreport_repo.update_testreport(id.to_string(), data).await {
     Ok(updated) => {
-      if updated.modified_count == 0 {
+      if updated.matched_count == 0 {
         warn!("Testreport not found: {}", id);
         return Err(JsonError::NotFound("Testreport not found".to_string()));
       }
@@ -64,6 +78,10 @@ async fn delete_testreport(jwt: Result<JWT, JsonError>, id: &str, testreport_rep
   let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
   let testreport = allowed_for_testreport(jwts, testreport_repo, id).await?;
 
+  if testreport.status == TestreportStatus::SignedOff {
+    return Err(JsonError::Forbidden("Signed off testreports cannot be deleted".to_string()));
+  }
+
   if testresult_repo.delete_testreport_testresults(id).await.is_err() {
     error!("Error deleting testreport testresults: {}", id);
     return Err(JsonError::Internal("Error deleting testreport testresults".to_string()));
@@ -98,11 +116,471 @@ pub async fn get_testreport_testresults(jwt: Result<JWT, JsonError>, testreport_
   }
 }
 
+#[get("/<testreport_id>/sections")]
+pub async fn get_testreport_sections(jwt: Result<JWT, JsonError>, testreport_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<Vec<TestreportSectionSummary>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+
+  match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => Ok(Json(summarize_testreport_sections(&testreport, &testresults))),
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+#[put("/<testreport_id>/assignments", format = "json", data = "<data>")]
+pub async fn assign_testreport_testresults(jwt: Result<JWT, JsonError>, testreport_id: &str, data: Json<TestresultsAssignmentDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<Vec<Testresult>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let data = data.into_inner();
+
+  if testreport.status == TestreportStatus::SignedOff {
+    return Err(JsonError::Forbidden("Testresults of a signed off testreport are locked".to_string()));
+  }
+  if data.testresult_ids.is_empty() && data.testsection_ids.is_empty() {
+    return Err(JsonError::BadRequest("No testresults or testsections selected".to_string()));
+  }
+  let assignee_id = get_testresult_assignee_id(users_repo, testreport.account_id, data.assignee_id.as_deref()).await?;
+
+  let mut testresult_ids = vec![];
+  for id in &data.testresult_ids {
+    match ObjectId::parse_str(id) {
+      Ok(oid) => testresult_ids.push(oid),
+      Err(_) => return Err(JsonError::BadRequest(format!("Invalid testresult id: {}", id))),
+    }
+  }
+
+  let mut section_ids: Vec<ObjectId> = vec![];
+  for id in &data.testsection_ids {
+    match testreport.sections.iter().find(|section| section.testsection_id.to_hex() == *id) {
+      Some(section) => section_ids.push(section.testsection_id),
+      None => return Err(JsonError::BadRequest(format!("Testsection not found in testreport: {}", id))),
+    }
+  }
+  // Subsections follow their parent
+  let mut index = 0;
+  while index < section_ids.len() {
+    let parent_id = section_ids[index];
+    for section in testreport.sections.iter().filter(|section| section.parent_id == Some(parent_id)) {
+      if !section_ids.contains(&section.testsection_id) {
+        section_ids.push(section.testsection_id);
+      }
+    }
+    index += 1;
+  }
+
+  if let Err(e) = testresult_repo.assign_testresults(testreport.id, &testresult_ids, &section_ids, assignee_id).await {
+    error!("Error assigning testresults: {}", e);
+    return Err(JsonError::Internal("Error assigning testresults".to_string()));
+  }
+
+  match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => Ok(Json(testresults)),
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+#[get("/<testreport_id>/workload")]
+pub async fn get_testreport_testers_workload(jwt: Result<JWT, JsonError>, testreport_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<Vec<TestreportWorkload>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+
+  match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => Ok(Json(get_testreport_workload(&testresults))),
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+#[post("/<testreport_id>/rerun", format = "json", data = "<data>")]
+pub async fn rerun_testreport(jwt: Result<JWT, JsonError>, testreport_id: &str, data: Json<TestreportRerunDto>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<Testreport>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let parent = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let data = data.into_inner();
+  let build = data.build.map(normalize_testreport_build).transpose().map_err(JsonError::BadRequest)?;
+
+  if data.statuses.is_empty() {
+    return Err(JsonError::BadRequest("No testresult statuses selected".to_string()));
+  }
+  let testresults: Vec<Testresult> = match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => testresults.into_iter().filter(|testresult| data.statuses.contains(&testresult.status())).collect(),
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      return Err(JsonError::Internal("Error getting testresults".to_string()));
+    },
+  };
+  if testresults.is_empty() {
+    return Err(JsonError::BadRequest("No testresults match the selected statuses".to_string()));
+  }
+
+  let id = match testreport_repo.create_rerun_testreport(&parent, data.execution, build).await {
+    Ok(inserted) => inserted.inserted_id.as_object_id().unwrap().to_hex(),
+    Err(e) => {
+      error!("Error creating testreport: {}", e);
+      return Err(JsonError::Internal("Error creating testreport".to_string()));
+    },
+  };
+  let testreport = get_testreport_by_id(testreport_repo, &id).await?;
+  for testresult in testresults {
+    let testresult_id = testresult.id.to_hex();
+    if testresult_repo.rerun_testresult(testreport.id, testresult).await.is_err() {
+      let _ = testresult_repo.delete_testreport_testresults(id.as_str()).await;
+      let _ = testreport_repo.delete_testreport(id.clone()).await;
+      error!("Error creating testresult from testresult: {}", testresult_id);
+      return Err(JsonError::Internal("Error creating testresult".to_string()));
+    }
+  }
+  Ok(Json(testreport))
+}
+
+// The reports this one derives from, the original one first
+#[get("/<testreport_id>/chain")]
+pub async fn get_testreport_chain(jwt: Result<JWT, JsonError>, testreport_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>) -> Result<Json<Vec<Testreport>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+
+  let mut chain = vec![];
+  let mut parent_id = testreport.parent_id;
+  chain.push(testreport);
+  while let Some(id) = parent_id {
+    match testreport_repo.get_testreport_by_id(&id.to_hex()).await {
+      Ok(Some(parent)) => {
+        parent_id = parent.parent_id;
+        chain.push(parent);
+      },
+      // The parent was deleted, the chain ends here
+      Ok(None) => break,
+      Err(e) => {
+        error!("Error getting testreport: {}", e);
+        return Err(JsonError::Internal("Error getting testreport".to_string()));
+      },
+    }
+  }
+  chain.reverse();
+  Ok(Json(chain))
+}
+
+#[get("/<testreport_id>/compare/<other_id>")]
+pub async fn compare_testreport(jwt: Result<JWT, JsonError>, testreport_id: &str, other_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<TestreportComparison>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  get_testreports_comparison(jwts, testreport_repo, testresult_repo, testreport_id, other_id).await.map(Json)
+}
+
+#[get("/<testreport_id>/compare/<other_id>/csv")]
+pub async fn compare_testreport_csv(jwt: Result<JWT, JsonError>, testreport_id: &str, other_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let comparison = get_testreports_comparison(jwts, testreport_repo, testresult_repo, testreport_id, other_id).await?;
+  let filename = format!("compare-{}-{}.csv", testreport_id, other_id);
+  Ok(Download::new(ContentType::CSV, &filename, get_testreport_comparison_csv(&comparison).into_bytes()))
+}
+
+#[get("/<testreport_id>/compare/<other_id>/markdown")]
+pub async fn compare_testreport_markdown(jwt: Result<JWT, JsonError>, testreport_id: &str, other_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let comparison = get_testreports_comparison(jwts, testreport_repo, testresult_repo, testreport_id, other_id).await?;
+  let filename = format!("compare-{}-{}.md", testreport_id, other_id);
+  Ok(Download::new(ContentType::Markdown, &filename, get_testreport_comparison_markdown(&comparison).into_bytes()))
+}
+
+#[get("/<testreport_id>/ctrf")]
+pub async fn export_testreport_ctrf(jwt: Result<JWT, JsonError>, testreport_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, project_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let project = get_testreport_project(project_repo, &testreport).await?;
+
+  match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => {
+      let ctrf = get_testreport_ctrf(&project, &testreport, &testresults);
+      match serde_json::to_vec_pretty(&ctrf) {
+        Ok(body) => Ok(Download::new(ContentType::JSON, &format!("{}.ctrf.json", testreport.name), body)),
+        Err(e) => {
+          error!("Error serializing CTRF report: {}", e);
+          Err(JsonError::Internal("Error serializing CTRF report".to_string()))
+        },
+      }
+    },
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+// Self-contained page for readers without an account, results grouped by section or tag
+#[get("/<testreport_id>/html?<group_by>")]
+pub async fn export_testreport_html(jwt: Result<JWT, JsonError>, testreport_id: &str, group_by: Option<&str>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, project_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let group_by = TestreportGroupBy::parse(group_by).map_err(JsonError::BadRequest)?;
+  let project = get_testreport_project(project_repo, &testreport).await?;
+  let testresults = match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => testresults,
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      return Err(JsonError::Internal("Error getting testresults".to_string()));
+    },
+  };
+  let user_names = get_testreport_user_names(users_repo, &testreport, &testresults).await?;
+
+  let html = get_testreport_html(&project, &testreport, &testresults, &user_names, group_by);
+  Ok(Download::new(ContentType::HTML, &format!("{}.html", testreport.name), html.into_bytes()))
+}
+
+// Workbook with a summary sheet and a sheet per section or tag
+#[get("/<testreport_id>/xlsx?<group_by>")]
+pub async fn export_testreport_xlsx(jwt: Result<JWT, JsonError>, testreport_id: &str, group_by: Option<&str>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, project_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let group_by = TestreportGroupBy::parse(group_by).map_err(JsonError::BadRequest)?;
+  let project = get_testreport_project(project_repo, &testreport).await?;
+  let testresults = get_testreport_testresults_by_id(testresult_repo, testreport_id).await?;
+  let user_names = get_testreport_user_names(users_repo, &testreport, &testresults).await?;
+
+  match get_testreport_xlsx(&project, &testreport, &testresults, &user_names, group_by) {
+    Ok(xlsx) => Ok(Download::new(xlsx_content_type(), &format!("{}.xlsx", testreport.name), xlsx)),
+    Err(e) => {
+      error!("Error writing testreport workbook: {}", e);
+      Err(JsonError::Internal("Error writing testreport workbook".to_string()))
+    },
+  }
+}
+
+// Printable document for audits, its fingerprint identifies the report content it was made from
+#[get("/<testreport_id>/pdf")]
+pub async fn export_testreport_pdf(jwt: Result<JWT, JsonError>, testreport_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, project_repo: &State<MongoRepo<Project>>, testlist_repo: &State<MongoRepo<Testlist>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let project = get_testreport_project(project_repo, &testreport).await?;
+  // The report outlives its testlist
+  let testlist_name = match testlist_repo.get_testlist_by_id(&testreport.testlist_id.to_hex()).await {
+    Ok(testlist) => testlist.map(|testlist| testlist.name),
+    Err(e) => {
+      error!("Error getting testlist: {}", e);
+      return Err(JsonError::Internal("Error getting testlist".to_string()));
+    },
+  };
+  let testresults = match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => testresults,
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      return Err(JsonError::Internal("Error getting testresults".to_string()));
+    },
+  };
+  let user_names = get_testreport_user_names(users_repo, &testreport, &testresults).await?;
+  let fingerprint = get_testreport_content_fingerprint(&testreport, &testresults)?;
+
+  let pdf = get_testreport_pdf(&project, testlist_name.as_deref(), &testreport, &testresults, &user_names, &fingerprint);
+  Ok(Download::new(ContentType::PDF, &format!("{}.pdf", testreport.name), pdf))
+}
+
+// Tells whether a fingerprint printed on an exported PDF still matches the report
+#[get("/<testreport_id>/pdf/verify?<fingerprint>")]
+pub async fn verify_testreport_pdf(jwt: Result<JWT, JsonError>, testreport_id: &str, fingerprint: Option<&str>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Json<TestreportFingerprint>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testreport = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let expected = match fingerprint.map(|fingerprint| fingerprint.trim().to_lowercase()) {
+    Some(fingerprint) if !fingerprint.is_empty() => fingerprint,
+    _ => return Err(JsonError::BadRequest("Fingerprint is required".to_string())),
+  };
+  let testresults = match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => testresults,
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      return Err(JsonError::Internal("Error getting testresults".to_string()));
+    },
+  };
+  let fingerprint = get_testreport_content_fingerprint(&testreport, &testresults)?;
+  Ok(Json(TestreportFingerprint {
+    matches: fingerprint == expected,
+    fingerprint,
+  }))
+}
+
+// Summary to post on pull requests, callable from CI with an X-Api-Token header or with a user
+// session. `compare_to` is the id of a report of the project or `previous` for the latest earlier
+// report of the same testlist and configuration.
+#[get("/<testreport_id>/markdown?<compare_to>")]
+pub async fn export_testreport_markdown(api_token: Result<ApiToken, JsonError>, jwt: Result<JWT, JsonError>, testreport_id: &str, compare_to: Option<&str>, apitoken_repo: &State<MongoRepo<Apitoken>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, project_repo: &State<MongoRepo<Project>>, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>) -> Result<Download, JsonError> {
+  let testreport = match api_token {
+    Ok(api_token) => {
+      let testreport = get_testreport_by_id(testreport_repo, testreport_id).await?;
+      authorize_project_apitoken(apitoken_repo, api_token, testreport.project_id).await?;
+      testreport
+    },
+    Err(_) => {
+      let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+      allowed_for_testreport(jwts, testreport_repo, testreport_id).await?
+    },
+  };
+  let project = get_testreport_project(project_repo, &testreport).await?;
+  let testresults = get_testreport_testresults_by_id(testresult_repo, testreport_id).await?;
+
+  let previous = match compare_to {
+    None => None,
+    Some("previous") => match testreport_repo.get_previous_testreport(&testreport).await {
+      Ok(previous) => previous,
+      Err(e) => {
+        error!("Error getting previous testreport: {}", e);
+        return Err(JsonError::Internal("Error getting previous testreport".to_string()));
+      },
+    },
+    Some(other_id) => {
+      let other = get_testreport_by_id(testreport_repo, other_id).await?;
+      if other.project_id != testreport.project_id {
+        return Err(JsonError::BadRequest("Testreports must belong to the same project".to_string()));
+      }
+      Some(other)
+    },
+  };
+  let previous = match previous {
+    Some(previous) => {
+      let previous_testresults = get_testreport_testresults_by_id(testresult_repo, &previous.id.to_hex()).await?;
+      Some((previous, previous_testresults))
+    },
+    None => None,
+  };
+
+  let markdown = get_testreport_markdown(
+    &project, &testreport, &testresults,
+    previous.as_ref().map(|(previous, previous_testresults)| (previous, previous_testresults.as_slice())),
+    get_app_url().as_deref(),
+  );
+  Ok(Download::new(ContentType::Markdown, &format!("{}.md", testreport.name), markdown.into_bytes()))
+}
+
 pub fn get_testreports_routes() -> Vec<rocket::Route> {
-  routes![get_testreports, get_testreport, update_testreport, delete_testreport, get_testreport_testresults]
+  routes![get_testreports, get_testreport, update_testreport, delete_testreport, get_testreport_testresults, get_testreport_sections, assign_testreport_testresults, get_testreport_testers_workload, rerun_testreport, get_testreport_chain, compare_testreport, compare_testreport_csv, compare_testreport_markdown, export_testreport_ctrf, export_testreport_html, export_testreport_pdf, verify_testreport_pdf, export_testreport_markdown, export_testreport_xlsx]
+}
+
+// Enforces the lifecycle draft -> in progress -> completed -> signed off, sign-off checks the project policy
+async fn change_testreport_status(testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, project_repo: &State<MongoRepo<Project>>, user: &User, testreport: &Testreport, status: TestreportStatus, justification: String) -> Result<(), JsonError> {
+  if !is_testreport_transition_allowed(testreport.status, status) {
+    return Err(JsonError::BadRequest(format!("Testreport cannot move from {} to {}", testreport.status, status)));
+  }
+  if is_testreport_reopening(testreport.status, status) && justification.trim().is_empty() {
+    return Err(JsonError::BadRequest("A justification is required to reopen a testreport".to_string()));
+  }
+
+  let now = DateTime::from_chrono(chrono::Utc::now());
+  let mut signoff = None;
+  if status == TestreportStatus::SignedOff {
+    let project = match project_repo.get_project_by_id(&testreport.project_id.to_hex()).await {
+      Ok(Some(project)) => project,
+      Ok(None) => return Err(JsonError::NotFound("Project not found".to_string())),
+      Err(e) => {
+        error!("Error getting project: {}", e);
+        return Err(JsonError::Internal("Error getting project".to_string()));
+      },
+    };
+    let is_manager = user.accounts.as_ref().is_some_and(|accounts| accounts.iter().any(|account| account.account_id == testreport.account_id && account.is_manager));
+    if project.signoff.require_manager && !is_manager && !is_admin(user) {
+      return Err(JsonError::Forbidden("Only account managers can sign off this testreport".to_string()));
+    }
+
+    let testresults = match testresult_repo.get_testreport_testresults(&testreport.id.to_hex()).await {
+      Ok(testresults) => testresults,
+      Err(e) => {
+        error!("Error getting testresults: {}", e);
+        return Err(JsonError::Internal("Error getting testresults".to_string()));
+      },
+    };
+    let progress = get_testreport_progress(testreport, &testresults);
+    let pass_rate = if progress.total > 0 { progress.passed as f64 * 100.0 / progress.total as f64 } else { 0.0 };
+    if project.signoff.min_pass_rate > 0.0 && pass_rate < project.signoff.min_pass_rate {
+      return Err(JsonError::BadRequest(format!("Pass rate {:.1}% is below the required {:.1}%", pass_rate, project.signoff.min_pass_rate)));
+    }
+    signoff = Some(TestreportSignoff {
+      user_id: user.id,
+      pass_rate,
+      signed_at: now,
+    });
+  }
+
+  let transition = TestreportTransition {
+    from: testreport.status,
+    to: status,
+    user_id: user.id,
+    justification,
+    created_at: now,
+  };
+  match testreport_repo.update_testreport_status(&testreport.id.to_hex(), status, signoff, transition).await {
+    Ok(_) => Ok(()),
+    Err(e) => {
+      error!("Error updating testreport status: {}", e);
+      Err(JsonError::Internal("Error updating testreport status".to_string()))
+    },
+  }
 }
 
-async fn allowed_for_testreport(jwts: JWTSessionAndUser, testreport_repo: &State<MongoRepo<Testreport>>, testreport_id: &str) -> Result<Testreport, JsonError>  {
+// The base report is compared against the other one, both must belong to the same project
+async fn get_testreports_comparison(jwts: JWTSessionAndUser, testreport_repo: &State<MongoRepo<Testreport>>, testresult_repo: &State<MongoRepo<Testresult>>, testreport_id: &str, other_id: &str) -> Result<TestreportComparison, JsonError> {
+  let base = allowed_for_testreport(jwts, testreport_repo, testreport_id).await?;
+  let target = get_testreport_by_id(testreport_repo, other_id).await?;
+  if target.project_id != base.project_id {
+    return Err(JsonError::BadRequest("Testreports must belong to the same project".to_string()));
+  }
+
+  let base_testresults = get_testreport_testresults_by_id(testresult_repo, testreport_id).await?;
+  let target_testresults = get_testreport_testresults_by_id(testresult_repo, other_id).await?;
+  Ok(compare_testreports(&base, &base_testresults, &target, &target_testresults))
+}
+
+async fn get_testreport_testresults_by_id(testresult_repo: &State<MongoRepo<Testresult>>, testreport_id: &str) -> Result<Vec<Testresult>, JsonError> {
+  match testresult_repo.get_testreport_testresults(testreport_id).await {
+    Ok(testresults) => Ok(testresults),
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+// Testers must be members of the testreport account
+async fn get_testresult_assignee_id(users_repo: &State<MongoRepo<User>>, account_id: ObjectId, assignee_id: Option<&str>) -> Result<Option<ObjectId>, JsonError> {
+  let assignee_id = match assignee_id {
+    Some(assignee_id) => assignee_id,
+    None => return Ok(None),
+  };
+  match users_repo.get_user_by_id(assignee_id).await {
+    Ok(Some(user)) => {
+      let is_member = user.accounts.as_ref().is_some_and(|accounts| accounts.iter().any(|account| account.account_id == account_id));
+      if !is_member {
+        return Err(JsonError::BadRequest("Assignee must be a member of the account".to_string()));
+      }
+      Ok(Some(user.id))
+    },
+    Ok(None) => Err(JsonError::BadRequest("Assignee not found".to_string())),
+    Err(e) => {
+      error!("Error getting user: {}", e);
+      Err(JsonError::Internal("Error getting user".to_string()))
+    },
+  }
+}
+
+pub async fn get_testreport_by_id(testreport_repo: &State<MongoRepo<Testreport>>, id: &str) -> Result<Testreport, JsonError> {
+  match testreport_repo.get_testreport_by_id(id).await {
+    Ok(testreport) => match testreport {
+      Some(testreport) => Ok(testreport),
+      None => {
+        warn!("Testreport not found: {}", id);
+        Err(JsonError::NotFound("Testreport not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testreport: {}", e);
+      Err(JsonError::Internal("Error getting testreport".to_string()))
+    },
+  }
+}
+
+pub async fn allowed_for_testreport(jwts: JWTSessionAndUser, testreport_repo: &State<MongoRepo<Testreport>>, testreport_id: &str) -> Result<Testreport, JsonError>  {
   if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
     return Err(JsonError::Forbidden(
       "You are not allowed to retrieve this testreport".to_string(),
@@ -130,3 +608,44 @@ async fn allowed_for_testreport(jwts: JWTSessionAndUser, testreport_repo: &State
     },
   }
 }
+
+async fn get_testreport_project(project_repo: &State<MongoRepo<Project>>, testreport: &Testreport) -> Result<Project, JsonError> {
+  match project_repo.get_project_by_id(&testreport.project_id.to_hex()).await {
+    Ok(Some(project)) => Ok(project),
+    Ok(None) => {
+      warn!("Project not found: {}", testreport.project_id);
+      Err(JsonError::NotFound("Project not found".to_string()))
+    },
+    Err(e) => {
+      error!("Error getting project: {}", e);
+      Err(JsonError::Internal("Error getting project".to_string()))
+    },
+  }
+}
+
+// Names of the executors of the results and of the user who signed the report off
+async fn get_testreport_user_names(users_repo: &State<MongoRepo<User>>, testreport: &Testreport, testresults: &[Testresult]) -> Result<HashMap<ObjectId, String>, JsonError> {
+  let mut user_ids: Vec<ObjectId> = testresults.iter()
+    .flat_map(|testresult| testresult.executors.iter().map(|executor| executor.user_id))
+    .chain(testreport.signoff.iter().map(|signoff| signoff.user_id))
+    .collect();
+  user_ids.sort();
+  user_ids.dedup();
+  match users_repo.get_users_by_ids(&user_ids).await {
+    Ok(users) => Ok(get_user_names(users)),
+    Err(e) => {
+      error!("Error getting users: {}", e);
+      Err(JsonError::Internal("Error getting users".to_string()))
+    },
+  }
+}
+
+fn get_testreport_content_fingerprint(testreport: &Testreport, testresults: &[Testresult]) -> Result<String, JsonError> {
+  match get_testreport_fingerprint(testreport, testresults) {
+    Ok(fingerprint) => Ok(fingerprint),
+    Err(e) => {
+      error!("Error computing testreport fingerprint: {}", e);
+      Err(JsonError::Internal("Error computing testreport fingerprint".to_string()))
+    },
+  }
+}
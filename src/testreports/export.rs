@@ -0,0 +1,165 @@
+// Content shared by the report exports: counters, results grouped by section or tag with the
+// failures first, and the names of the executors.
+
+use std::collections::HashMap;
+
+use mongodb::bson::oid::ObjectId;
+
+use crate::{testresults::schema::{Testresult, TestresultStatus}, users::schema::User};
+
+use super::schema::{Testreport, TestreportSection};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestreportGroupBy {
+  Section,
+  Tag,
+}
+
+impl TestreportGroupBy {
+  // Sections when missing
+  pub fn parse(group_by: Option<&str>) -> Result<TestreportGroupBy, String> {
+    match group_by.unwrap_or("section") {
+      "section" => Ok(TestreportGroupBy::Section),
+      "tag" => Ok(TestreportGroupBy::Tag),
+      group_by => Err(format!("Unknown grouping {}, expected section or tag", group_by)),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TestreportCounters {
+  pub total: u32,
+  pub executed: u32,
+  pub passed: u32,
+  pub failed: u32,
+  pub blocked: u32,
+  pub not_executed: u32,
+  // Percentage of the executed results, 0 when nothing was executed
+  pub pass_rate: f64,
+}
+
+pub struct TestreportResultsGroup<'a> {
+  pub name: String,
+  pub counters: TestreportCounters,
+  pub testresults: Vec<&'a Testresult>,
+}
+
+pub fn count_testresults<'a>(testresults: impl IntoIterator<Item = &'a Testresult>) -> TestreportCounters {
+  let mut counters = TestreportCounters::default();
+  for testresult in testresults {
+    counters.total += 1;
+    if testresult.updated {
+      counters.executed += 1;
+    }
+    match testresult.status() {
+      TestresultStatus::Passed => counters.passed += 1,
+      TestresultStatus::Failed => counters.failed += 1,
+      TestresultStatus::Blocked => counters.blocked += 1,
+      TestresultStatus::NotExecuted => counters.not_executed += 1,
+    }
+  }
+  if counters.executed > 0 {
+    counters.pass_rate = counters.passed as f64 * 100.0 / counters.executed as f64;
+  }
+  counters
+}
+
+pub fn get_status_label(status: TestresultStatus) -> &'static str {
+  match status {
+    TestresultStatus::Passed => "Passed",
+    TestresultStatus::Failed => "Failed",
+    TestresultStatus::Blocked => "Blocked",
+    TestresultStatus::NotExecuted => "Not executed",
+  }
+}
+
+// Failed results first, then blocked, not executed and passed ones
+fn get_status_rank(status: TestresultStatus) -> u8 {
+  match status {
+    TestresultStatus::Failed => 0,
+    TestresultStatus::Blocked => 1,
+    TestresultStatus::NotExecuted => 2,
+    TestresultStatus::Passed => 3,
+  }
+}
+
+// Sections in testlist order, named after their path, then results outside any section.
+// A result is listed under each of its tags, tags in alphabetical order, then untagged results.
+pub fn group_testreport_results<'a>(testreport: &Testreport, testresults: &'a [Testresult], group_by: TestreportGroupBy) -> Vec<TestreportResultsGroup<'a>> {
+  let mut groups: Vec<(String, Vec<&'a Testresult>)> = Vec::new();
+  match group_by {
+    TestreportGroupBy::Section => {
+      let sections = get_section_paths(&testreport.sections);
+      let section_ids: Vec<ObjectId> = sections.iter().map(|(section_id, _)| *section_id).collect();
+      for (section_id, path) in sections {
+        groups.push((path, testresults.iter().filter(|testresult| testresult.section_id == Some(section_id)).collect()));
+      }
+      groups.push(("No section".to_string(), testresults.iter()
+        .filter(|testresult| testresult.section_id.is_none_or(|section_id| !section_ids.contains(&section_id)))
+        .collect()));
+    },
+    TestreportGroupBy::Tag => {
+      let mut tags: Vec<&String> = testresults.iter().flat_map(|testresult| testresult.tags.iter()).collect();
+      tags.sort();
+      tags.dedup();
+      for tag in tags {
+        groups.push((tag.clone(), testresults.iter().filter(|testresult| testresult.tags.contains(tag)).collect()));
+      }
+      groups.push(("Untagged".to_string(), testresults.iter().filter(|testresult| testresult.tags.is_empty()).collect()));
+    },
+  }
+
+  groups.into_iter()
+    .filter(|(_, testresults)| !testresults.is_empty())
+    .map(|(name, mut testresults)| {
+      testresults.sort_by_key(|testresult| (get_status_rank(testresult.status()), testresult.position, testresult.name.clone()));
+      TestreportResultsGroup {
+        name,
+        counters: count_testresults(testresults.iter().copied()),
+        testresults,
+      }
+    })
+    .collect()
+}
+
+// Sections depth first in testlist order, with their path from the top level section
+pub fn get_section_paths(sections: &[TestreportSection]) -> Vec<(ObjectId, String)> {
+  let mut paths = Vec::new();
+  add_section_paths(sections, None, "", &mut paths);
+  paths
+}
+
+fn add_section_paths(sections: &[TestreportSection], parent_id: Option<ObjectId>, parent_path: &str, paths: &mut Vec<(ObjectId, String)>) {
+  let mut children: Vec<&TestreportSection> = sections.iter().filter(|section| section.parent_id == parent_id).collect();
+  children.sort_by_key(|section| section.position);
+  for section in children {
+    let path = if parent_path.is_empty() { section.name.clone() } else { format!("{} > {}", parent_path, section.name) };
+    paths.push((section.testsection_id, path.clone()));
+    add_section_paths(sections, Some(section.testsection_id), &path, paths);
+  }
+}
+
+pub fn get_user_names(users: Vec<User>) -> HashMap<ObjectId, String> {
+  users.into_iter().map(|user| {
+    let name = format!("{} {}", user.firstname.trim(), user.lastname.trim()).trim().to_string();
+    (user.id, if name.is_empty() { user.email } else { name })
+  }).collect()
+}
+
+// Executors of the result in execution order, removed users are left out
+pub fn get_executor_names(testresult: &Testresult, user_names: &HashMap<ObjectId, String>) -> Vec<String> {
+  let mut names: Vec<String> = Vec::new();
+  for executor in &testresult.executors {
+    if let Some(name) = user_names.get(&executor.user_id) {
+      if !names.contains(name) {
+        names.push(name.clone());
+      }
+    }
+  }
+  names
+}
+
+// Only web links are rendered as links, other URLs are shown as text
+pub fn is_web_url(url: &str) -> bool {
+  url.starts_with("https://") || url.starts_with("http://")
+}
@@ -0,0 +1,207 @@
+// Single file HTML page of a report for readers without an account. Styles are embedded and
+// nothing is loaded from elsewhere; the page only depends on the report, so an unchanged report
+// is exported to the same bytes and can be archived with a release.
+
+use std::collections::HashMap;
+
+use bson::DateTime;
+use mongodb::bson::oid::ObjectId;
+
+use crate::{projects::schema::Project, service::html::html_text, testresults::schema::{TestresultStatus, Testresult}};
+
+use super::{export::{count_testresults, get_executor_names, get_status_label, group_testreport_results, is_web_url, TestreportCounters, TestreportGroupBy}, schema::Testreport};
+
+const HTML_STYLE: &str = "
+* { box-sizing: border-box; }
+body { margin: 0 auto; max-width: 1100px; padding: 24px; font: 14px/1.5 -apple-system, 'Segoe UI', Roboto, Helvetica, Arial, sans-serif; color: #1f2328; background: #fff; }
+h1 { margin: 0 0 4px; font-size: 24px; }
+h2 { margin: 32px 0 8px; font-size: 18px; border-bottom: 1px solid #d0d7de; padding-bottom: 4px; }
+h2 .counts { float: right; font-size: 13px; font-weight: normal; color: #59636e; }
+.subtitle { margin: 0 0 16px; color: #59636e; }
+dl.meta { display: grid; grid-template-columns: max-content 1fr; gap: 2px 16px; margin: 0 0 24px; }
+dl.meta dt { color: #59636e; }
+dl.meta dd { margin: 0; }
+.counters { display: flex; flex-wrap: wrap; gap: 12px; margin-bottom: 12px; }
+.counter { flex: 1 1 120px; border: 1px solid #d0d7de; border-radius: 6px; padding: 8px 12px; }
+.counter .value { display: block; font-size: 22px; font-weight: 600; }
+.counter .label { color: #59636e; }
+.progress { display: flex; height: 10px; border-radius: 5px; overflow: hidden; background: #eaeef2; }
+.progress span { display: block; height: 100%; }
+table { width: 100%; border-collapse: collapse; }
+th, td { text-align: left; vertical-align: top; padding: 6px 8px; border-bottom: 1px solid #eaeef2; }
+th { font-size: 12px; color: #59636e; font-weight: 600; }
+.status { display: inline-block; min-width: 90px; padding: 1px 8px; border-radius: 10px; font-size: 12px; font-weight: 600; text-align: center; color: #fff; }
+.passed { background: #1a7f37; }
+.failed { background: #cf222e; }
+.blocked { background: #9a6700; }
+.not_executed { background: #818b98; }
+.tag { display: inline-block; margin: 2px 4px 0 0; padding: 0 6px; border-radius: 10px; background: #eaeef2; font-size: 12px; }
+.flag { font-size: 12px; color: #59636e; }
+.notes { white-space: pre-wrap; word-break: break-word; }
+footer { margin-top: 32px; font-size: 12px; color: #59636e; }
+@media print { body { padding: 0; } h2 { break-after: avoid; } tr { break-inside: avoid; } }
+";
+
+pub fn get_testreport_html(project: &Project, testreport: &Testreport, testresults: &[Testresult], user_names: &HashMap<ObjectId, String>, group_by: TestreportGroupBy) -> String {
+  let mut html = String::new();
+  html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+  html.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+  html.push_str(&format!("<meta name=\"generator\" content=\"test-boss {}\">\n", env!("CARGO_PKG_VERSION")));
+  html.push_str(&format!("<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n", html_text(&testreport.name), HTML_STYLE));
+
+  html.push_str(&format!("<header>\n<h1>{}</h1>\n", html_text(&testreport.name)));
+  let project_name = match project.version.is_empty() {
+    true => project.name.clone(),
+    false => format!("{} {}", project.name, project.version),
+  };
+  html.push_str(&format!("<p class=\"subtitle\">{}</p>\n", html_text(&project_name)));
+  html.push_str(&get_metadata_html(testreport, user_names));
+  html.push_str("</header>\n");
+
+  let counters = count_testresults(testresults);
+  html.push_str(&get_counters_html(&counters));
+
+  for group in group_testreport_results(testreport, testresults, group_by) {
+    html.push_str(&format!(
+      "<section>\n<h2>{}<span class=\"counts\">{} passed, {} failed, {} blocked, {} not executed</span></h2>\n",
+      html_text(&group.name), group.counters.passed, group.counters.failed, group.counters.blocked, group.counters.not_executed,
+    ));
+    html.push_str("<table>\n<thead><tr><th>Status</th><th>Check</th><th>Notes</th><th>Links</th><th>Executors</th></tr></thead>\n<tbody>\n");
+    for testresult in group.testresults {
+      html.push_str(&get_testresult_html(testresult, user_names));
+    }
+    html.push_str("</tbody>\n</table>\n</section>\n");
+  }
+
+  html.push_str(&format!(
+    "<footer>Testreport {} as of {}, exported by test-boss {}</footer>\n</body>\n</html>\n",
+    testreport.id.to_hex(), format_datetime(testreport.updated_at), env!("CARGO_PKG_VERSION"),
+  ));
+  html
+}
+
+fn get_metadata_html(testreport: &Testreport, user_names: &HashMap<ObjectId, String>) -> String {
+  let mut fields: Vec<(&str, String)> = vec![("Status", html_text(&testreport.status.to_string().replace('_', " ")))];
+  if !testreport.execution.is_empty() {
+    fields.push(("Execution", html_text(&testreport.execution)));
+  }
+  if let Some(testconfiguration) = &testreport.testconfiguration {
+    fields.push(("Configuration", html_text(&testconfiguration.name)));
+    for parameter in &testconfiguration.parameters {
+      fields.push(("Parameter", html_text(&format!("{}: {}", parameter.name, parameter.value))));
+    }
+  }
+  if let Some(tags_filter) = &testreport.tags_filter {
+    fields.push(("Tags filter", html_text(tags_filter)));
+  }
+  if let Some(build) = &testreport.build {
+    let environment = build.environment.clone()
+      .or_else(|| testreport.testconfiguration.as_ref().and_then(|testconfiguration| testconfiguration.environment.clone()));
+    let build_fields = [
+      ("Environment", environment),
+      ("Branch", build.branch.clone()),
+      ("Tag", build.tag.clone()),
+      ("Commit", build.commit_sha.clone()),
+      ("Build", build.build_number.clone()),
+      ("Triggered by", build.actor.clone()),
+    ];
+    for (name, value) in build_fields {
+      if let Some(value) = value {
+        fields.push((name, html_text(&value)));
+      }
+    }
+    if let Some(pipeline_url) = &build.pipeline_url {
+      fields.push(("Pipeline", get_link_html(pipeline_url, pipeline_url)));
+    }
+  } else if let Some(environment) = testreport.testconfiguration.as_ref().and_then(|testconfiguration| testconfiguration.environment.as_ref()) {
+    fields.push(("Environment", html_text(environment)));
+  }
+  fields.push(("Created", format_datetime(testreport.created_at)));
+  if let Some(signoff) = &testreport.signoff {
+    let signer = user_names.get(&signoff.user_id).cloned().unwrap_or_else(|| "Unknown user".to_string());
+    fields.push(("Signed off", html_text(&format!("{} by {}, pass rate {:.1}%", format_datetime(signoff.signed_at), signer, signoff.pass_rate))));
+  }
+
+  let mut html = String::from("<dl class=\"meta\">\n");
+  for (name, value) in fields {
+    html.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", name, value));
+  }
+  html.push_str("</dl>\n");
+  html
+}
+
+fn get_counters_html(counters: &TestreportCounters) -> String {
+  let values = [
+    ("Total", counters.total.to_string()),
+    ("Passed", counters.passed.to_string()),
+    ("Failed", counters.failed.to_string()),
+    ("Blocked", counters.blocked.to_string()),
+    ("Not executed", counters.not_executed.to_string()),
+    ("Pass rate", format!("{:.1}%", counters.pass_rate)),
+  ];
+  let mut html = String::from("<section>\n<div class=\"counters\">\n");
+  for (label, value) in values {
+    html.push_str(&format!("<div class=\"counter\"><span class=\"value\">{}</span><span class=\"label\">{}</span></div>\n", value, label));
+  }
+  html.push_str("</div>\n<div class=\"progress\">");
+  let shares = [
+    (TestresultStatus::Passed, counters.passed),
+    (TestresultStatus::Failed, counters.failed),
+    (TestresultStatus::Blocked, counters.blocked),
+  ];
+  for (status, count) in shares {
+    if count > 0 {
+      html.push_str(&format!("<span class=\"{}\" style=\"width: {:.2}%\"></span>", status, count as f64 * 100.0 / counters.total as f64));
+    }
+  }
+  html.push_str("</div>\n</section>\n");
+  html
+}
+
+fn get_testresult_html(testresult: &Testresult, user_names: &HashMap<ObjectId, String>) -> String {
+  let status = testresult.status();
+  let mut check = format!("<strong>{}</strong>", html_text(&testresult.name));
+  let mut flags = Vec::new();
+  if testresult.automated {
+    flags.push("automated".to_string());
+  }
+  if testresult.flacky {
+    flags.push("flaky".to_string());
+  }
+  if let Some(duration_ms) = testresult.duration_ms {
+    flags.push(format!("{:.1} s", duration_ms as f64 / 1000.0));
+  }
+  if !flags.is_empty() {
+    check.push_str(&format!(" <span class=\"flag\">{}</span>", flags.join(", ")));
+  }
+  if !testresult.tags.is_empty() {
+    check.push_str("<br>");
+    for tag in &testresult.tags {
+      check.push_str(&format!("<span class=\"tag\">{}</span>", html_text(tag)));
+    }
+  }
+
+  let mut links = Vec::new();
+  if !testresult.url_issue.is_empty() {
+    links.push(get_link_html(&testresult.url_issue, "Issue"));
+  }
+  if !testresult.url_result.is_empty() {
+    links.push(get_link_html(&testresult.url_result, "Result"));
+  }
+
+  format!(
+    "<tr><td><span class=\"status {}\">{}</span></td><td>{}</td><td class=\"notes\">{}</td><td>{}</td><td>{}</td></tr>\n",
+    status, get_status_label(status), check, html_text(&testresult.notes), links.join("<br>"), html_text(&get_executor_names(testresult, user_names).join(", ")),
+  )
+}
+
+fn get_link_html(url: &str, label: &str) -> String {
+  match is_web_url(url) {
+    true => format!("<a href=\"{}\">{}</a>", html_text(url), html_text(label)),
+    false => html_text(url),
+  }
+}
+
+fn format_datetime(datetime: DateTime) -> String {
+  datetime.to_chrono().format("%Y-%m-%d %H:%M UTC").to_string()
+}
@@ -0,0 +1,215 @@
+// GitHub flavoured Markdown summary of a report to paste in pull requests and release notes or
+// to post as a CI comment. Details are folded in `<details>` blocks to keep comments short.
+
+use crate::{projects::schema::Project, service::{html::html_text, markdown::{markdown_cell, markdown_text, markdown_url}}, testresults::schema::{Testresult, TestresultStatus}};
+
+use super::{compare::compare_testreports, export::{count_testresults, group_testreport_results, is_web_url, TestreportCounters, TestreportGroupBy}, schema::{TestreportChange, TestreportComparisonEntry, Testreport}};
+
+// GitHub comments are limited to 65536 characters
+const MAX_LISTED_CHECKS: usize = 50;
+const MAX_NOTES_LENGTH: usize = 500;
+
+pub fn get_testreport_markdown(project: &Project, testreport: &Testreport, testresults: &[Testresult], previous: Option<(&Testreport, &[Testresult])>, app_url: Option<&str>) -> String {
+  let counters = count_testresults(testresults);
+  let testreport_url = app_url.map(|app_url| get_testreport_url(app_url, testreport));
+
+  let title = match &testreport_url {
+    Some(testreport_url) => format!("[{}]({})", markdown_text(&testreport.name), markdown_url(testreport_url)),
+    None => markdown_text(&testreport.name),
+  };
+  let mut md = format!("## {} {}\n\n", get_outcome_emoji(&counters), title);
+  md.push_str(&format!("{}\n\n", get_metadata_line(project, testreport)));
+
+  md.push_str("| | Total | \u{2705} Passed | \u{274c} Failed | \u{1f6ab} Blocked | \u{23f8}\u{fe0f} Not executed | Pass rate |\n");
+  md.push_str("|---|---:|---:|---:|---:|---:|---:|\n");
+  md.push_str(&get_counters_row("This run", &counters));
+  if let Some((previous_testreport, previous_testresults)) = previous {
+    md.push_str(&get_counters_row(&format!("Previous ({})", markdown_cell(&previous_testreport.name)), &count_testresults(previous_testresults)));
+  }
+
+  let groups = group_testreport_results(testreport, testresults, TestreportGroupBy::Section);
+  for (status, title) in [(TestresultStatus::Failed, "Failed checks"), (TestresultStatus::Blocked, "Blocked checks")] {
+    let testresults: Vec<(&str, &Testresult)> = groups.iter()
+      .flat_map(|group| group.testresults.iter().map(|testresult| (group.name.as_str(), *testresult)))
+      .filter(|(_, testresult)| testresult.status() == status)
+      .collect();
+    if testresults.is_empty() {
+      continue;
+    }
+    md.push_str(&format!("\n<details>\n<summary>{} {} ({})</summary>\n\n", get_status_emoji(status), title, testresults.len()));
+    for (section, testresult) in testresults.iter().take(MAX_LISTED_CHECKS) {
+      md.push_str(&get_testresult_item(section, testresult));
+    }
+    if testresults.len() > MAX_LISTED_CHECKS {
+      md.push_str(&format!("\n{}\n", get_more_line(testresults.len() - MAX_LISTED_CHECKS, &testreport_url)));
+    }
+    md.push_str("\n</details>\n");
+  }
+
+  if let Some((previous_testreport, previous_testresults)) = previous {
+    md.push_str(&get_changes_markdown(previous_testreport, previous_testresults, testreport, testresults, app_url));
+  }
+
+  md.push_str("\n<sub>Generated by Test-Boss");
+  if let Some(testreport_url) = &testreport_url {
+    md.push_str(&format!(" \u{b7} [View the full report]({})", markdown_url(testreport_url)));
+  }
+  md.push_str("</sub>\n");
+  md
+}
+
+fn get_testreport_url(app_url: &str, testreport: &Testreport) -> String {
+  format!("{}/testreports/{}", app_url, testreport.id.to_hex())
+}
+
+fn get_outcome_emoji(counters: &TestreportCounters) -> &'static str {
+  if counters.failed > 0 {
+    "\u{274c}"
+  } else if counters.blocked > 0 {
+    "\u{1f6ab}"
+  } else if counters.not_executed > 0 {
+    "\u{23f3}"
+  } else {
+    "\u{2705}"
+  }
+}
+
+fn get_status_emoji(status: TestresultStatus) -> &'static str {
+  match status {
+    TestresultStatus::Passed => "\u{2705}",
+    TestresultStatus::Failed => "\u{274c}",
+    TestresultStatus::Blocked => "\u{1f6ab}",
+    TestresultStatus::NotExecuted => "\u{23f8}\u{fe0f}",
+  }
+}
+
+// Project, configuration and build on a single line
+fn get_metadata_line(project: &Project, testreport: &Testreport) -> String {
+  let mut parts = vec![match project.version.is_empty() {
+    true => format!("**{}**", markdown_text(&project.name)),
+    false => format!("**{}** {}", markdown_text(&project.name), markdown_text(&project.version)),
+  }];
+  parts.push(format!("Status: {}", testreport.status.to_string().replace('_', " ")));
+  if let Some(testconfiguration) = &testreport.testconfiguration {
+    parts.push(format!("Configuration: {}", markdown_text(&testconfiguration.name)));
+  }
+  if let Some(build) = &testreport.build {
+    if let Some(branch) = &build.branch {
+      parts.push(format!("Branch: {}", markdown_code(branch)));
+    }
+    if let Some(tag) = &build.tag {
+      parts.push(format!("Tag: {}", markdown_code(tag)));
+    }
+    if let Some(commit_sha) = &build.commit_sha {
+      parts.push(format!("Commit: {}", markdown_code(&commit_sha.chars().take(7).collect::<String>())));
+    }
+    match (&build.build_number, &build.pipeline_url) {
+      (Some(build_number), Some(pipeline_url)) if is_web_url(pipeline_url) => parts.push(format!("Build: [{}]({})", markdown_text(build_number), markdown_url(pipeline_url))),
+      (None, Some(pipeline_url)) if is_web_url(pipeline_url) => parts.push(format!("[Pipeline]({})", markdown_url(pipeline_url))),
+      (Some(build_number), _) => parts.push(format!("Build: {}", markdown_text(build_number))),
+      _ => {},
+    }
+  }
+  parts.join(" \u{b7} ")
+}
+
+fn get_counters_row(label: &str, counters: &TestreportCounters) -> String {
+  format!(
+    "| {} | {} | {} | {} | {} | {} | {:.1}% |\n",
+    label, counters.total, counters.passed, counters.failed, counters.blocked, counters.not_executed, counters.pass_rate,
+  )
+}
+
+// Name in bold, then its section, links and the notes quoted below
+fn get_testresult_item(section: &str, testresult: &Testresult) -> String {
+  let mut parts = vec![format!("**{}**", markdown_text(&testresult.name)), markdown_text(section)];
+  if testresult.flacky {
+    parts.push("flaky".to_string());
+  }
+  for (url, label) in [(&testresult.url_issue, "Issue"), (&testresult.url_result, "Result")] {
+    if is_web_url(url) {
+      parts.push(format!("[{}]({})", label, markdown_url(url)));
+    }
+  }
+  let mut item = format!("- {}\n", parts.join(" \u{b7} "));
+  let notes = testresult.notes.trim();
+  if !notes.is_empty() {
+    let mut notes: String = notes.chars().take(MAX_NOTES_LENGTH).collect();
+    if testresult.notes.trim().chars().count() > MAX_NOTES_LENGTH {
+      notes.push_str("...");
+    }
+    // Trailing double spaces keep the line breaks of the notes
+    let lines: Vec<String> = notes.lines().map(|line| format!("  > {}", markdown_text(line.trim_end()))).collect();
+    item.push_str(&lines.join("  \n"));
+    item.push('\n');
+  }
+  item
+}
+
+fn get_more_line(count: usize, testreport_url: &Option<String>) -> String {
+  match testreport_url {
+    Some(testreport_url) => format!("...and {} more, see [the full report]({}).", count, markdown_url(testreport_url)),
+    None => format!("...and {} more.", count),
+  }
+}
+
+// Outcome changes of the checks since the previous report
+fn get_changes_markdown(previous: &Testreport, previous_testresults: &[Testresult], testreport: &Testreport, testresults: &[Testresult], app_url: Option<&str>) -> String {
+  let comparison = compare_testreports(previous, previous_testresults, testreport, testresults);
+  let previous_name = match app_url {
+    Some(app_url) => format!("[{}]({})", markdown_text(&previous.name), markdown_url(&get_testreport_url(app_url, previous))),
+    None => markdown_text(&previous.name),
+  };
+  let mut md = format!("\n### Changes since {}\n\n", previous_name);
+  let changes = [
+    (TestreportChange::NewlyFailing, "\u{1f534} Newly failing", comparison.newly_failing),
+    (TestreportChange::NewlyPassing, "\u{1f7e2} Newly passing", comparison.newly_passing),
+    (TestreportChange::StillFailing, "\u{1f7e0} Still failing", comparison.still_failing),
+    (TestreportChange::Added, "\u{2795} Added", comparison.added),
+    (TestreportChange::Removed, "\u{2796} Removed", comparison.removed),
+  ];
+  if changes.iter().all(|(_, _, count)| *count == 0) {
+    md.push_str("No outcome changed.\n");
+    return md;
+  }
+  md.push_str(&changes.iter()
+    .filter(|(_, _, count)| *count > 0)
+    .map(|(_, title, count)| format!("{}: {}", title, count))
+    .collect::<Vec<String>>().join(" \u{b7} "));
+  md.push('\n');
+
+  for (change, title, count) in changes {
+    if count == 0 {
+      continue;
+    }
+    let entries: Vec<&TestreportComparisonEntry> = comparison.entries.iter().filter(|entry| entry.change == change).collect();
+    // Open when a check started failing, that is what a reviewer is looking for
+    let open = if change == TestreportChange::NewlyFailing { " open" } else { "" };
+    md.push_str(&format!("\n<details{}>\n<summary>{} ({})</summary>\n\n", open, html_text(title), count));
+    for entry in entries.iter().take(MAX_LISTED_CHECKS) {
+      md.push_str(&format!("- {} ({} \u{2192} {})\n", markdown_text(&entry.name), format_status(entry.base_status), format_status(entry.target_status)));
+    }
+    if entries.len() > MAX_LISTED_CHECKS {
+      md.push_str(&format!("\n...and {} more.\n", entries.len() - MAX_LISTED_CHECKS));
+    }
+    md.push_str("\n</details>\n");
+  }
+  md
+}
+
+fn format_status(status: Option<TestresultStatus>) -> String {
+  match status {
+    Some(status) => status.to_string().replace('_', " "),
+    None => "none".to_string(),
+  }
+}
+
+// Inline code, with a fence longer than any backtick run of the text
+fn markdown_code(text: &str) -> String {
+  let mut fence = "`".to_string();
+  while text.contains(fence.as_str()) {
+    fence.push('`');
+  }
+  let padding = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+  format!("{}{}{}{}{}", fence, padding, text, padding, fence)
+}
@@ -1,3 +1,9 @@
 pub mod schema;
 pub mod service;
 pub mod endpoints;
+pub mod compare;
+pub mod export;
+pub mod html;
+pub mod pdf;
+pub mod markdown;
+pub mod xlsx;
@@ -0,0 +1,676 @@
+// Printable A4 document of a report for audits, written with the standard PDF fonts so no font
+// or browser is needed. Every page carries the SHA-256 fingerprint of the report and its results,
+// recomputing it from the stored report tells whether the document still matches it.
+
+use std::collections::HashMap;
+
+use bson::DateTime;
+use chrono::{Datelike, Timelike};
+use mongodb::bson::oid::ObjectId;
+use pdf_writer::{Content, Date, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{projects::schema::Project, testresults::schema::{Testresult, TestresultStatus}};
+
+use super::{export::{count_testresults, get_executor_names, get_status_label, group_testreport_results, TestreportCounters, TestreportGroupBy, TestreportResultsGroup}, schema::Testreport};
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+// Lowest position of the content, the footer is below
+const CONTENT_BOTTOM: f32 = 56.0;
+// A cell longer than this is cut so a row always fits on a page
+const MAX_CELL_LINES: usize = 40;
+
+const TEXT_COLOR: (f32, f32, f32) = (0.12, 0.14, 0.16);
+const MUTED_COLOR: (f32, f32, f32) = (0.35, 0.39, 0.43);
+const RULE_COLOR: (f32, f32, f32) = (0.82, 0.84, 0.87);
+const SHADE_COLOR: (f32, f32, f32) = (0.92, 0.93, 0.95);
+
+// Status, check, notes and executors
+const TABLE_COLUMNS: [(&str, f32); 4] = [("Status", 64.0), ("Check", 200.0), ("Notes", 161.0), ("Executors", 90.0)];
+
+// Widths of the characters 32 to 126 in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+  278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+  556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+  1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+  667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+  333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+  556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+  278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+  556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+  975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+  667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+  333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+  611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PdfFont {
+  Regular,
+  Bold,
+}
+
+impl PdfFont {
+  fn resource_name(&self) -> Name<'static> {
+    match self {
+      PdfFont::Regular => Name(b"F1"),
+      PdfFont::Bold => Name(b"F2"),
+    }
+  }
+
+  fn char_width(&self, c: char) -> f32 {
+    let widths = match self {
+      PdfFont::Regular => &HELVETICA_WIDTHS,
+      PdfFont::Bold => &HELVETICA_BOLD_WIDTHS,
+    };
+    match c as u32 {
+      32..=126 => widths[c as usize - 32] as f32,
+      // Accented letters and symbols, close to the average width
+      _ => 556.0,
+    }
+  }
+
+  fn text_width(&self, text: &str, size: f32) -> f32 {
+    text.chars().map(|c| self.char_width(c)).sum::<f32>() * size / 1000.0
+  }
+}
+
+// Content of the pages written so far, the latest page is being filled from top to bottom
+struct PdfPages {
+  pages: Vec<Content>,
+  content: Content,
+  y: f32,
+}
+
+impl PdfPages {
+  fn new() -> PdfPages {
+    PdfPages {
+      pages: Vec::new(),
+      content: Content::new(),
+      y: PAGE_HEIGHT - MARGIN,
+    }
+  }
+
+  fn add_page(&mut self) {
+    let content = std::mem::replace(&mut self.content, Content::new());
+    self.pages.push(content);
+    self.y = PAGE_HEIGHT - MARGIN;
+  }
+
+  // Starts a new page when less than `height` is left on the current one
+  fn reserve(&mut self, height: f32) -> bool {
+    if self.y - height < CONTENT_BOTTOM {
+      self.add_page();
+      return true;
+    }
+    false
+  }
+
+  fn finish(mut self) -> Vec<Content> {
+    self.pages.push(self.content);
+    self.pages
+  }
+
+  // `y` is the baseline of the text
+  fn text(&mut self, x: f32, y: f32, font: PdfFont, size: f32, color: (f32, f32, f32), text: &str) {
+    self.content.set_fill_rgb(color.0, color.1, color.2);
+    self.content.begin_text();
+    self.content.set_font(font.resource_name(), size);
+    self.content.next_line(x, y);
+    self.content.show(Str(&encode_win_ansi(text)));
+    self.content.end_text();
+  }
+
+  fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: (f32, f32, f32)) {
+    self.content.set_fill_rgb(color.0, color.1, color.2);
+    self.content.rect(x, y, width, height);
+    self.content.fill_nonzero();
+  }
+
+  fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, color: (f32, f32, f32)) {
+    self.content.set_stroke_rgb(color.0, color.1, color.2);
+    self.content.set_line_width(0.5);
+    self.content.move_to(x1, y1);
+    self.content.line_to(x2, y2);
+    self.content.stroke();
+  }
+
+  fn heading(&mut self, title: &str) {
+    self.reserve(60.0);
+    self.y -= 22.0;
+    self.text(MARGIN, self.y, PdfFont::Bold, 13.0, TEXT_COLOR, title);
+    self.y -= 6.0;
+    self.line(MARGIN, self.y, PAGE_WIDTH - MARGIN, self.y, RULE_COLOR);
+    self.y -= 8.0;
+  }
+}
+
+// Version of the fingerprinted fields, prefixed to the fingerprint. Adding a field to the schemas
+// leaves fingerprints untouched, fingerprinting a new field needs a new version.
+const FINGERPRINT_VERSION: &str = "v1";
+
+// SHA-256 of the listed fields of the report and of its results in creation order, as lowercase
+// hex after the version. Fields are hashed as JSON arrays so that no key order is involved.
+pub fn get_testreport_fingerprint(testreport: &Testreport, testresults: &[Testresult]) -> Result<String, String> {
+  let mut testresults: Vec<&Testresult> = testresults.iter().collect();
+  testresults.sort_by_key(|testresult| testresult.id);
+
+  let testconfiguration = testreport.testconfiguration.as_ref().map(|testconfiguration| json!([
+    testconfiguration.testconfiguration_id.to_hex(),
+    testconfiguration.name,
+    testconfiguration.environment,
+    testconfiguration.parameters.iter().map(|parameter| json!([parameter.name, parameter.value])).collect::<Vec<Value>>(),
+  ]));
+  let signoff = testreport.signoff.as_ref().map(|signoff| json!([
+    signoff.user_id.to_hex(),
+    signoff.pass_rate,
+    signoff.signed_at.timestamp_millis(),
+  ]));
+  let report = json!([
+    testreport.id.to_hex(),
+    testreport.name,
+    testreport.description,
+    testreport.execution,
+    testreport.status.to_string(),
+    testconfiguration,
+    signoff,
+  ]);
+  let results: Vec<Value> = testresults.iter().map(|testresult| json!([
+    testresult.id.to_hex(),
+    testresult.testcheck_id.to_hex(),
+    testresult.testcheck_revision,
+    testresult.name,
+    testresult.description,
+    testresult.expected,
+    testresult.status().to_string(),
+    testresult.flacky,
+    testresult.automated,
+    testresult.notes,
+    testresult.url_issue,
+    testresult.url_result,
+    testresult.executors.iter().map(|executor| executor.user_id.to_hex()).collect::<Vec<String>>(),
+  ])).collect();
+
+  let content = serde_json::to_vec(&json!([FINGERPRINT_VERSION, report, results])).map_err(|e| e.to_string())?;
+  let digest: String = Sha256::digest(&content).iter().map(|byte| format!("{:02x}", byte)).collect();
+  Ok(format!("{}:{}", FINGERPRINT_VERSION, digest))
+}
+
+pub fn get_testreport_pdf(project: &Project, testlist_name: Option<&str>, testreport: &Testreport, testresults: &[Testresult], user_names: &HashMap<ObjectId, String>, fingerprint: &str) -> Vec<u8> {
+  let mut pages = PdfPages::new();
+  let groups = group_testreport_results(testreport, testresults, TestreportGroupBy::Section);
+
+  add_header(&mut pages, project, testlist_name, testreport, testresults, user_names);
+  add_summary(&mut pages, &count_testresults(testresults), &groups);
+  add_results_table(&mut pages, &groups, user_names);
+  add_signoff(&mut pages, testreport, user_names, fingerprint);
+
+  write_pdf(pages.finish(), testreport, fingerprint)
+}
+
+fn add_header(pages: &mut PdfPages, project: &Project, testlist_name: Option<&str>, testreport: &Testreport, testresults: &[Testresult], user_names: &HashMap<ObjectId, String>) {
+  for line in wrap_text(&testreport.name, PdfFont::Bold, 18.0, CONTENT_WIDTH) {
+    pages.y -= 20.0;
+    pages.text(MARGIN, pages.y, PdfFont::Bold, 18.0, TEXT_COLOR, &line);
+  }
+  let mut subtitle = match project.version.is_empty() {
+    true => project.name.clone(),
+    false => format!("{} {}", project.name, project.version),
+  };
+  if let Some(testlist_name) = testlist_name {
+    subtitle.push_str(&format!(" \u{b7} {}", testlist_name));
+  }
+  for line in wrap_text(&subtitle, PdfFont::Regular, 11.0, CONTENT_WIDTH) {
+    pages.y -= 15.0;
+    pages.text(MARGIN, pages.y, PdfFont::Regular, 11.0, MUTED_COLOR, &line);
+  }
+  pages.y -= 10.0;
+
+  let mut fields: Vec<(&str, String)> = vec![("Status", testreport.status.to_string().replace('_', " "))];
+  if !testreport.execution.is_empty() {
+    fields.push(("Execution", testreport.execution.clone()));
+  }
+  fields.push(("Execution period", get_execution_period(testreport, testresults)));
+  fields.push(("Executors", get_executors_summary(testresults, user_names)));
+  if let Some(testconfiguration) = &testreport.testconfiguration {
+    fields.push(("Configuration", testconfiguration.name.clone()));
+    for parameter in &testconfiguration.parameters {
+      fields.push(("Parameter", format!("{}: {}", parameter.name, parameter.value)));
+    }
+  }
+  if let Some(tags_filter) = &testreport.tags_filter {
+    fields.push(("Tags filter", tags_filter.clone()));
+  }
+  let environment = testreport.build.as_ref().and_then(|build| build.environment.clone())
+    .or_else(|| testreport.testconfiguration.as_ref().and_then(|testconfiguration| testconfiguration.environment.clone()));
+  if let Some(environment) = environment {
+    fields.push(("Environment", environment));
+  }
+  if let Some(build) = &testreport.build {
+    let build_fields = [
+      ("Branch", &build.branch),
+      ("Tag", &build.tag),
+      ("Commit", &build.commit_sha),
+      ("Build", &build.build_number),
+      ("Pipeline", &build.pipeline_url),
+      ("Triggered by", &build.actor),
+    ];
+    for (name, value) in build_fields {
+      if let Some(value) = value {
+        fields.push((name, value.clone()));
+      }
+    }
+  }
+  fields.push(("Created", format_datetime(testreport.created_at)));
+
+  for (name, value) in fields {
+    let lines = wrap_text(&value, PdfFont::Regular, 9.0, CONTENT_WIDTH - 110.0);
+    pages.reserve(12.0 * lines.len() as f32);
+    pages.y -= 12.0;
+    pages.text(MARGIN, pages.y, PdfFont::Bold, 9.0, MUTED_COLOR, name);
+    for (index, line) in lines.iter().enumerate() {
+      if index > 0 {
+        pages.y -= 12.0;
+      }
+      pages.text(MARGIN + 110.0, pages.y, PdfFont::Regular, 9.0, TEXT_COLOR, line);
+    }
+  }
+}
+
+// From the first execution to the latest executed result
+fn get_execution_period(testreport: &Testreport, testresults: &[Testresult]) -> String {
+  let executed: Vec<&Testresult> = testresults.iter().filter(|testresult| testresult.updated).collect();
+  let end = match executed.iter().map(|testresult| testresult.updated_at).max() {
+    Some(end) => end,
+    None => return "Not started".to_string(),
+  };
+  let start = executed.iter()
+    .flat_map(|testresult| testresult.executors.iter().map(|executor| executor.start_date))
+    .chain(testreport.executors.iter().flatten().map(|executor| executor.start_date))
+    .min()
+    .unwrap_or(testreport.created_at);
+  format!("{} to {}", format_datetime(start.min(end)), format_datetime(end))
+}
+
+// Executors by number of executed results
+fn get_executors_summary(testresults: &[Testresult], user_names: &HashMap<ObjectId, String>) -> String {
+  let mut counts: Vec<(String, u32)> = Vec::new();
+  for testresult in testresults {
+    for name in get_executor_names(testresult, user_names) {
+      match counts.iter_mut().find(|(executor, _)| *executor == name) {
+        Some((_, count)) => *count += 1,
+        None => counts.push((name, 1)),
+      }
+    }
+  }
+  if counts.is_empty() {
+    return "None".to_string();
+  }
+  counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+  counts.iter().map(|(name, count)| format!("{} ({})", name, count)).collect::<Vec<String>>().join(", ")
+}
+
+// Pie chart of the statuses with its legend, then a bar per section
+fn add_summary(pages: &mut PdfPages, counters: &TestreportCounters, groups: &[TestreportResultsGroup]) {
+  pages.heading("Summary");
+  pages.reserve(130.0);
+  let radius = 52.0;
+  let center = (MARGIN + radius + 4.0, pages.y - radius - 6.0);
+  let shares = get_status_shares(counters);
+  if counters.total == 0 {
+    pages.content.set_stroke_rgb(RULE_COLOR.0, RULE_COLOR.1, RULE_COLOR.2);
+    pages.content.set_line_width(1.0);
+    add_arc_path(&mut pages.content, center, radius, 0.0, std::f32::consts::TAU, false);
+    pages.content.stroke();
+  } else {
+    let mut angle = std::f32::consts::FRAC_PI_2;
+    for (status, count) in &shares {
+      if *count == 0 {
+        continue;
+      }
+      let sweep = *count as f32 / counters.total as f32 * std::f32::consts::TAU;
+      let color = get_status_color(*status);
+      pages.content.set_fill_rgb(color.0, color.1, color.2);
+      pages.content.move_to(center.0, center.1);
+      add_arc_path(&mut pages.content, center, radius, angle, angle - sweep, true);
+      pages.content.close_path();
+      pages.content.fill_nonzero();
+      angle -= sweep;
+    }
+  }
+
+  let legend_x = MARGIN + 2.0 * radius + 40.0;
+  let mut legend_y = pages.y - 14.0;
+  pages.text(legend_x, legend_y, PdfFont::Bold, 10.0, TEXT_COLOR, &format!("{} checks, {} executed, pass rate {:.1}%", counters.total, counters.executed, counters.pass_rate));
+  for (status, count) in &shares {
+    legend_y -= 18.0;
+    pages.fill_rect(legend_x, legend_y - 1.0, 9.0, 9.0, get_status_color(*status));
+    let percentage = if counters.total > 0 { *count as f32 * 100.0 / counters.total as f32 } else { 0.0 };
+    pages.text(legend_x + 16.0, legend_y, PdfFont::Regular, 9.0, TEXT_COLOR, &format!("{}: {} ({:.1}%)", get_status_label(*status), count, percentage));
+  }
+  pages.y -= 2.0 * radius + 24.0;
+
+  if groups.is_empty() {
+    return;
+  }
+  pages.reserve(40.0);
+  pages.y -= 14.0;
+  pages.text(MARGIN, pages.y, PdfFont::Bold, 10.0, TEXT_COLOR, "Results by section");
+  pages.y -= 6.0;
+  let label_width = 170.0;
+  let bar_width = CONTENT_WIDTH - label_width - 90.0;
+  for group in groups {
+    pages.reserve(16.0);
+    pages.y -= 16.0;
+    let label = truncate_text(&group.name, PdfFont::Regular, 8.0, label_width - 8.0);
+    pages.text(MARGIN, pages.y + 2.0, PdfFont::Regular, 8.0, TEXT_COLOR, &label);
+    let mut x = MARGIN + label_width;
+    for (status, count) in get_status_shares(&group.counters) {
+      if count == 0 {
+        continue;
+      }
+      let width = count as f32 / group.counters.total as f32 * bar_width;
+      pages.fill_rect(x, pages.y, width, 10.0, get_status_color(status));
+      x += width;
+    }
+    let counts = format!("{}/{} passed", group.counters.passed, group.counters.total);
+    pages.text(MARGIN + label_width + bar_width + 8.0, pages.y + 2.0, PdfFont::Regular, 8.0, MUTED_COLOR, &counts);
+  }
+}
+
+fn get_status_shares(counters: &TestreportCounters) -> [(TestresultStatus, u32); 4] {
+  [
+    (TestresultStatus::Passed, counters.passed),
+    (TestresultStatus::Failed, counters.failed),
+    (TestresultStatus::Blocked, counters.blocked),
+    (TestresultStatus::NotExecuted, counters.not_executed),
+  ]
+}
+
+// Circle arc from `start` to `end` in radians as Bézier curves of at most a quarter turn
+fn add_arc_path(content: &mut Content, center: (f32, f32), radius: f32, start: f32, end: f32, connected: bool) {
+  let point = |angle: f32| (center.0 + radius * angle.cos(), center.1 + radius * angle.sin());
+  let segments = ((end - start).abs() / std::f32::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+  let step = (end - start) / segments as f32;
+  let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+  let (x, y) = point(start);
+  match connected {
+    true => content.line_to(x, y),
+    false => content.move_to(x, y),
+  };
+  for index in 0..segments {
+    let a0 = start + step * index as f32;
+    let a1 = a0 + step;
+    let (x0, y0) = point(a0);
+    let (x3, y3) = point(a1);
+    content.cubic_to(x0 - k * a0.sin(), y0 + k * a0.cos(), x3 + k * a1.sin(), y3 - k * a1.cos(), x3, y3);
+  }
+}
+
+fn add_results_table(pages: &mut PdfPages, groups: &[TestreportResultsGroup], user_names: &HashMap<ObjectId, String>) {
+  pages.heading("Results");
+  add_table_header(pages);
+  for group in groups {
+    if pages.reserve(40.0) {
+      add_table_header(pages);
+    }
+    pages.y -= 16.0;
+    let counts = format!("{} passed, {} failed, {} blocked, {} not executed", group.counters.passed, group.counters.failed, group.counters.blocked, group.counters.not_executed);
+    let counts_width = PdfFont::Regular.text_width(&counts, 8.0);
+    let name = truncate_text(&group.name, PdfFont::Bold, 9.0, CONTENT_WIDTH - counts_width - 12.0);
+    pages.text(MARGIN, pages.y, PdfFont::Bold, 9.0, TEXT_COLOR, &name);
+    pages.text(PAGE_WIDTH - MARGIN - counts_width, pages.y, PdfFont::Regular, 8.0, MUTED_COLOR, &counts);
+    pages.y -= 5.0;
+    pages.line(MARGIN, pages.y, PAGE_WIDTH - MARGIN, pages.y, RULE_COLOR);
+    for testresult in &group.testresults {
+      add_testresult_row(pages, testresult, user_names);
+    }
+  }
+}
+
+fn add_table_header(pages: &mut PdfPages) {
+  pages.fill_rect(MARGIN, pages.y - 16.0, CONTENT_WIDTH, 16.0, SHADE_COLOR);
+  let mut x = MARGIN;
+  for (title, width) in TABLE_COLUMNS {
+    pages.text(x + 4.0, pages.y - 11.0, PdfFont::Bold, 8.0, MUTED_COLOR, title);
+    x += width;
+  }
+  pages.y -= 18.0;
+}
+
+fn add_testresult_row(pages: &mut PdfPages, testresult: &Testresult, user_names: &HashMap<ObjectId, String>) {
+  let line_height = 10.0;
+  let column_width = |index: usize| TABLE_COLUMNS[index].1 - 8.0;
+
+  let mut check_lines: Vec<(PdfFont, String)> = wrap_text(&testresult.name, PdfFont::Bold, 8.0, column_width(1)).into_iter().map(|line| (PdfFont::Bold, line)).collect();
+  let mut details = Vec::new();
+  if testresult.automated {
+    details.push("automated".to_string());
+  }
+  if testresult.flacky {
+    details.push("flaky".to_string());
+  }
+  if let Some(duration_ms) = testresult.duration_ms {
+    details.push(format!("{:.1} s", duration_ms as f64 / 1000.0));
+  }
+  if !testresult.tags.is_empty() {
+    details.push(testresult.tags.join(", "));
+  }
+  if !testresult.url_issue.is_empty() {
+    details.push(format!("Issue: {}", testresult.url_issue));
+  }
+  if !testresult.url_result.is_empty() {
+    details.push(format!("Result: {}", testresult.url_result));
+  }
+  for detail in details {
+    check_lines.extend(wrap_text(&detail, PdfFont::Regular, 7.0, column_width(1)).into_iter().map(|line| (PdfFont::Regular, line)));
+  }
+  let check_lines = limit_lines(check_lines, (PdfFont::Regular, "[...]".to_string()));
+  let notes_lines = limit_lines(wrap_text(&testresult.notes, PdfFont::Regular, 8.0, column_width(2)), "[...]".to_string());
+  let executors_lines = wrap_text(&get_executor_names(testresult, user_names).join(", "), PdfFont::Regular, 8.0, column_width(3));
+  let executors_lines = limit_lines(executors_lines, "[...]".to_string());
+
+  let lines = check_lines.len().max(notes_lines.len()).max(executors_lines.len()).max(1);
+  let height = lines as f32 * line_height + 8.0;
+  if pages.reserve(height) {
+    add_table_header(pages);
+  }
+  let top = pages.y;
+
+  let status = testresult.status();
+  pages.fill_rect(MARGIN + 4.0, top - 14.0, TABLE_COLUMNS[0].1 - 8.0, 11.0, get_status_color(status));
+  pages.text(MARGIN + 7.0, top - 11.0, PdfFont::Bold, 7.0, (1.0, 1.0, 1.0), get_status_label(status));
+
+  let mut x = MARGIN + TABLE_COLUMNS[0].1;
+  for (index, (font, line)) in check_lines.iter().enumerate() {
+    let size = if *font == PdfFont::Bold { 8.0 } else { 7.0 };
+    let color = if *font == PdfFont::Bold { TEXT_COLOR } else { MUTED_COLOR };
+    pages.text(x + 4.0, top - 11.0 - index as f32 * line_height, *font, size, color, line);
+  }
+  x += TABLE_COLUMNS[1].1;
+  for (index, line) in notes_lines.iter().enumerate() {
+    pages.text(x + 4.0, top - 11.0 - index as f32 * line_height, PdfFont::Regular, 8.0, TEXT_COLOR, line);
+  }
+  x += TABLE_COLUMNS[2].1;
+  for (index, line) in executors_lines.iter().enumerate() {
+    pages.text(x + 4.0, top - 11.0 - index as f32 * line_height, PdfFont::Regular, 8.0, TEXT_COLOR, line);
+  }
+
+  pages.y -= height;
+  pages.line(MARGIN, pages.y, PAGE_WIDTH - MARGIN, pages.y, SHADE_COLOR);
+}
+
+// Recorded sign-off, fields to sign the printed document and the fingerprint
+fn add_signoff(pages: &mut PdfPages, testreport: &Testreport, user_names: &HashMap<ObjectId, String>, fingerprint: &str) {
+  pages.heading("Sign-off");
+  pages.reserve(150.0);
+  let signoff = match &testreport.signoff {
+    Some(signoff) => {
+      let signer = user_names.get(&signoff.user_id).cloned().unwrap_or_else(|| "Unknown user".to_string());
+      format!("Signed off by {} on {} with a pass rate of {:.1}%.", signer, format_datetime(signoff.signed_at), signoff.pass_rate)
+    },
+    None => "The report has not been signed off.".to_string(),
+  };
+  for line in wrap_text(&signoff, PdfFont::Regular, 9.0, CONTENT_WIDTH) {
+    pages.y -= 12.0;
+    pages.text(MARGIN, pages.y, PdfFont::Regular, 9.0, TEXT_COLOR, &line);
+  }
+
+  pages.y -= 40.0;
+  let field_width = (CONTENT_WIDTH - 2.0 * 20.0) / 3.0;
+  for (index, label) in ["Name", "Date", "Signature"].iter().enumerate() {
+    let x = MARGIN + index as f32 * (field_width + 20.0);
+    pages.line(x, pages.y, x + field_width, pages.y, MUTED_COLOR);
+    pages.text(x, pages.y - 10.0, PdfFont::Regular, 8.0, MUTED_COLOR, label);
+  }
+
+  pages.y -= 34.0;
+  pages.text(MARGIN, pages.y, PdfFont::Bold, 8.0, MUTED_COLOR, "SHA-256 fingerprint of the report content");
+  pages.y -= 11.0;
+  pages.text(MARGIN, pages.y, PdfFont::Regular, 8.0, TEXT_COLOR, fingerprint);
+}
+
+fn write_pdf(pages: Vec<Content>, testreport: &Testreport, fingerprint: &str) -> Vec<u8> {
+  let mut pdf = Pdf::new();
+  let catalog_id = Ref::new(1);
+  let page_tree_id = Ref::new(2);
+  let regular_font_id = Ref::new(3);
+  let bold_font_id = Ref::new(4);
+  let info_id = Ref::new(5);
+  let page_ids: Vec<Ref> = (0..pages.len()).map(|index| Ref::new(6 + 2 * index as i32)).collect();
+
+  pdf.catalog(catalog_id).pages(page_tree_id);
+  pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(page_ids.len() as i32);
+  pdf.type1_font(regular_font_id).base_font(Name(b"Helvetica")).encoding_predefined(Name(b"WinAnsiEncoding"));
+  pdf.type1_font(bold_font_id).base_font(Name(b"Helvetica-Bold")).encoding_predefined(Name(b"WinAnsiEncoding"));
+
+  let page_count = pages.len();
+  for (index, mut content) in pages.into_iter().enumerate() {
+    let mut footer = PdfPages { pages: Vec::new(), content, y: 0.0 };
+    footer.line(MARGIN, 40.0, PAGE_WIDTH - MARGIN, 40.0, RULE_COLOR);
+    let page_number = format!("Page {} of {}", index + 1, page_count);
+    let page_number_width = PdfFont::Regular.text_width(&page_number, 7.0);
+    let report = truncate_text(&format!("{} \u{b7} SHA-256 {}", testreport.name, fingerprint), PdfFont::Regular, 7.0, CONTENT_WIDTH - page_number_width - 12.0);
+    footer.text(MARGIN, 28.0, PdfFont::Regular, 7.0, MUTED_COLOR, &report);
+    footer.text(PAGE_WIDTH - MARGIN - page_number_width, 28.0, PdfFont::Regular, 7.0, MUTED_COLOR, &page_number);
+    content = footer.content;
+
+    let content_id = Ref::new(page_ids[index].get() + 1);
+    let mut page = pdf.page(page_ids[index]);
+    page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+    page.parent(page_tree_id);
+    page.contents(content_id);
+    page.resources().fonts()
+      .pair(PdfFont::Regular.resource_name(), regular_font_id)
+      .pair(PdfFont::Bold.resource_name(), bold_font_id);
+    page.finish();
+    pdf.stream(content_id, &content.finish());
+  }
+
+  // Dated with the latest report change so an unchanged report gives the same document
+  let updated_at = testreport.updated_at.to_chrono();
+  let date = Date::new(updated_at.year() as u16)
+    .month(updated_at.month() as u8)
+    .day(updated_at.day() as u8)
+    .hour(updated_at.hour() as u8)
+    .minute(updated_at.minute() as u8)
+    .utc_offset_hour(0);
+  let generator = format!("test-boss {}", env!("CARGO_PKG_VERSION"));
+  let mut info = pdf.document_info(info_id);
+  info.title(TextStr(&testreport.name))
+    .subject(TextStr(&format!("Testreport {}", testreport.id.to_hex())))
+    .keywords(TextStr(&format!("sha256:{}", fingerprint)))
+    .creator(TextStr(&generator))
+    .producer(TextStr(&generator))
+    .creation_date(date);
+  info.pair(Name(b"Fingerprint"), TextStr(fingerprint));
+  info.finish();
+
+  pdf.finish()
+}
+
+fn get_status_color(status: TestresultStatus) -> (f32, f32, f32) {
+  match status {
+    TestresultStatus::Passed => (0.10, 0.50, 0.22),
+    TestresultStatus::Failed => (0.81, 0.13, 0.18),
+    TestresultStatus::Blocked => (0.60, 0.40, 0.0),
+    TestresultStatus::NotExecuted => (0.51, 0.55, 0.60),
+  }
+}
+
+// Characters the standard fonts cannot show are replaced by a question mark
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+  text.chars().filter(|c| !c.is_control()).map(|c| match c {
+    ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+    '\u{20ac}' => 0x80,
+    '\u{2026}' => 0x85,
+    '\u{2018}' => 0x91,
+    '\u{2019}' => 0x92,
+    '\u{201c}' => 0x93,
+    '\u{201d}' => 0x94,
+    '\u{2022}' => 0x95,
+    '\u{2013}' => 0x96,
+    '\u{2014}' => 0x97,
+    _ => b'?',
+  }).collect()
+}
+
+// Lines of at most `width`, words longer than a line are cut
+fn wrap_text(text: &str, font: PdfFont, size: f32, width: f32) -> Vec<String> {
+  let mut lines = Vec::new();
+  for paragraph in text.lines() {
+    let mut line = String::new();
+    for word in paragraph.split_whitespace() {
+      let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+      if font.text_width(&candidate, size) <= width {
+        line = candidate;
+        continue;
+      }
+      if !line.is_empty() {
+        lines.push(std::mem::take(&mut line));
+      }
+      for c in word.chars() {
+        if !line.is_empty() && font.text_width(&line, size) + font.char_width(c) * size / 1000.0 > width {
+          lines.push(std::mem::take(&mut line));
+        }
+        line.push(c);
+      }
+    }
+    if !line.is_empty() {
+      lines.push(line);
+    }
+  }
+  lines
+}
+
+fn truncate_text(text: &str, font: PdfFont, size: f32, width: f32) -> String {
+  if font.text_width(text, size) <= width {
+    return text.to_string();
+  }
+  let mut truncated = String::new();
+  for c in text.chars() {
+    if font.text_width(&truncated, size) + font.char_width(c) * size / 1000.0 + font.text_width("...", size) > width {
+      break;
+    }
+    truncated.push(c);
+  }
+  format!("{}...", truncated.trim_end())
+}
+
+fn limit_lines<T>(mut lines: Vec<T>, ellipsis: T) -> Vec<T> {
+  if lines.len() > MAX_CELL_LINES {
+    lines.truncate(MAX_CELL_LINES - 1);
+    lines.push(ellipsis);
+  }
+  lines
+}
+
+fn format_datetime(datetime: DateTime) -> String {
+  datetime.to_chrono().format("%Y-%m-%d %H:%M UTC").to_string()
+}
@@ -2,7 +2,7 @@ use bson::DateTime;
 use mongodb::bson::oid::ObjectId;
 use rocket::serde::{Deserialize, Serialize};
 
-use crate::service::db::{serialize_datetime, serialize_object_id};
+use crate::{service::db::{serialize_datetime, serialize_object_id, serialize_option_object_id}, testconfigurations::schema::TestconfigurationParameter, testresults::schema::TestresultStatus};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Testreport {
@@ -14,10 +14,32 @@ pub struct Testreport {
   pub project_id: ObjectId,
   #[serde(serialize_with = "serialize_object_id")]
   pub testlist_id: ObjectId,
+  // Report this one re-runs a subset of
+  #[serde(default, serialize_with = "serialize_option_object_id")]
+  pub parent_id: Option<ObjectId>,
   pub name: String,
   pub description: String,
   pub execution: String,
+  // Tags expression selecting the checks the report was created with
+  #[serde(default)]
+  pub tags_filter: Option<String>,
+  #[serde(default)]
+  pub status: TestreportStatus,
+  #[serde(default)]
+  pub signoff: Option<TestreportSignoff>,
+  // Status transitions, oldest first
+  #[serde(default)]
+  pub history: Vec<TestreportTransition>,
   pub executors: Option<Vec<TestExecutor>>,
+  // Snapshot of the testlist sections when the report was created
+  #[serde(default)]
+  pub sections: Vec<TestreportSection>,
+  // Snapshot of the configuration the report was created for
+  #[serde(default)]
+  pub testconfiguration: Option<TestreportConfiguration>,
+  // CI build the report was produced by
+  #[serde(default)]
+  pub build: Option<TestreportBuild>,
   #[serde(serialize_with = "serialize_datetime")]
   pub created_at: DateTime,
   #[serde(serialize_with = "serialize_datetime")]
@@ -29,6 +51,113 @@ pub struct TestreportDto {
   pub name: String,
   pub description: String,
   pub execution: String,
+  // Only the checks matching the expression are included on creation, e.g. `smoke AND NOT slow`
+  #[serde(default)]
+  pub tags_filter: Option<String>,
+  // Unchanged when missing
+  #[serde(default)]
+  pub status: Option<TestreportStatus>,
+  // Required to reopen a completed or signed off report
+  #[serde(default)]
+  pub justification: String,
+  // Unchanged when missing
+  #[serde(default)]
+  pub build: Option<TestreportBuild>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TestreportBuild {
+  // Full or abbreviated, stored lowercase
+  pub commit_sha: Option<String>,
+  pub branch: Option<String>,
+  pub tag: Option<String>,
+  pub pipeline_url: Option<String>,
+  pub build_number: Option<String>,
+  // User or bot who triggered the pipeline
+  pub actor: Option<String>,
+  // Deployment environment the tests ran against, e.g. `staging`
+  pub environment: Option<String>,
+}
+
+impl TestreportBuild {
+  pub fn is_empty(&self) -> bool {
+    *self == TestreportBuild::default()
+  }
+}
+
+// Reports of a project produced by matching builds, a commit matches its abbreviations
+#[derive(Debug, Clone, Default)]
+pub struct TestreportBuildFilter {
+  pub commit_sha: Option<String>,
+  pub branch: Option<String>,
+  pub tag: Option<String>,
+  pub build_number: Option<String>,
+  pub actor: Option<String>,
+  pub environment: Option<String>,
+}
+
+// Creates a report with the parent results matching the statuses
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportRerunDto {
+  pub execution: String,
+  pub statuses: Vec<TestresultStatus>,
+  // Build of the parent report when missing
+  #[serde(default)]
+  pub build: Option<TestreportBuild>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TestreportStatus {
+  #[default]
+  Draft,
+  InProgress,
+  Completed,
+  SignedOff,
+}
+
+impl std::fmt::Display for TestreportStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TestreportStatus::Draft => write!(f, "draft"),
+      TestreportStatus::InProgress => write!(f, "in_progress"),
+      TestreportStatus::Completed => write!(f, "completed"),
+      TestreportStatus::SignedOff => write!(f, "signed_off"),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportSignoff {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub user_id: ObjectId,
+  pub pass_rate: f64,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub signed_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportTransition {
+  pub from: TestreportStatus,
+  pub to: TestreportStatus,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub user_id: ObjectId,
+  pub justification: String,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub created_at: DateTime,
+}
+
+// Creates one report for each of the selected configurations
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportMatrixDto {
+  pub execution: String,
+  #[serde(default)]
+  pub tags_filter: Option<String>,
+  pub testconfiguration_ids: Vec<String>,
+  // Shared by the reports, the environment defaults to the one of each configuration
+  #[serde(default)]
+  pub build: Option<TestreportBuild>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,3 +167,152 @@ pub struct TestExecutor {
   #[serde(serialize_with = "serialize_datetime")]
   pub start_date: DateTime,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportSection {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testsection_id: ObjectId,
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub parent_id: Option<ObjectId>,
+  pub name: String,
+  pub description: String,
+  pub position: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportSectionSummary {
+  // None for the summary of the whole report
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub testsection_id: Option<ObjectId>,
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub parent_id: Option<ObjectId>,
+  pub name: String,
+  pub depth: u16,
+  pub total: u32,
+  pub executed: u32,
+  pub passed: u32,
+  pub failed: u32,
+  pub blocked: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportProgress {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testreport_id: ObjectId,
+  pub name: String,
+  pub total: u32,
+  pub executed: u32,
+  pub passed: u32,
+  pub failed: u32,
+  pub blocked: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportConfiguration {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testconfiguration_id: ObjectId,
+  pub name: String,
+  pub environment: Option<String>,
+  pub parameters: Vec<TestconfigurationParameter>,
+}
+
+// Results of the latest report of each configuration, one column per configuration
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportMatrix {
+  pub columns: Vec<TestreportMatrixColumn>,
+  pub rows: Vec<TestreportMatrixRow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportMatrixColumn {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testconfiguration_id: ObjectId,
+  pub name: String,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testreport_id: ObjectId,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportMatrixRow {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testcheck_id: ObjectId,
+  pub name: String,
+  pub position: u16,
+  // Same order as the columns, None when the check is missing from the report
+  pub results: Vec<Option<TestresultStatus>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportWorkload {
+  // None for the unassigned results
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub assignee_id: Option<ObjectId>,
+  pub total: u32,
+  pub executed: u32,
+  pub passed: u32,
+  pub failed: u32,
+  pub blocked: u32,
+  // Percentage of the report results assigned to the tester
+  pub share: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestreportChange {
+  NewlyFailing,
+  NewlyPassing,
+  StillFailing,
+  Added,
+  Removed,
+  // Same outcome, listed only when the notes changed
+  Unchanged,
+}
+
+impl std::fmt::Display for TestreportChange {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TestreportChange::NewlyFailing => write!(f, "newly_failing"),
+      TestreportChange::NewlyPassing => write!(f, "newly_passing"),
+      TestreportChange::StillFailing => write!(f, "still_failing"),
+      TestreportChange::Added => write!(f, "added"),
+      TestreportChange::Removed => write!(f, "removed"),
+      TestreportChange::Unchanged => write!(f, "unchanged"),
+    }
+  }
+}
+
+// Differences from the base report to the target one, results are matched by testcheck
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportComparison {
+  pub base: TestreportProgress,
+  pub target: TestreportProgress,
+  pub newly_failing: u32,
+  pub newly_passing: u32,
+  pub still_failing: u32,
+  pub added: u32,
+  pub removed: u32,
+  pub notes_changed: u32,
+  pub entries: Vec<TestreportComparisonEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportComparisonEntry {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testcheck_id: ObjectId,
+  pub name: String,
+  pub change: TestreportChange,
+  pub base_status: Option<TestresultStatus>,
+  pub target_status: Option<TestresultStatus>,
+  pub notes_changed: bool,
+  pub base_notes: String,
+  pub target_notes: String,
+}
+
+// Fingerprint of the stored report compared to the one printed on an exported PDF
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestreportFingerprint {
+  pub fingerprint: String,
+  pub matches: bool,
+}
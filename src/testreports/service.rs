@@ -2,10 +2,10 @@ use std::error::Error;
 
 use bson::DateTime;
 use mongodb::{
-  bson::{self, doc, oid::ObjectId}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
+  bson::{self, doc, oid::ObjectId}, options::{FindOneOptions, FindOptions}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
 };
-use crate::{service::db::{ self, MongoRepo}, testlists::schema::Testlist};
-use super::schema::{Testreport, TestreportDto};
+use crate::{service::db::{ self, MongoRepo}, testlists::schema::Testlist, testresults::schema::{Testresult, TestresultStatus}, testsections::schema::Testsection};
+use super::schema::{TestExecutor, Testreport, TestreportBuild, TestreportBuildFilter, TestreportConfiguration, TestreportDto, TestreportMatrix, TestreportMatrixColumn, TestreportMatrixRow, TestreportProgress, TestreportSection, TestreportSectionSummary, TestreportSignoff, TestreportStatus, TestreportTransition, TestreportWorkload};
 use rocket::futures::TryStreamExt;
 
 pub fn get_testreports_repo(client: Client) -> MongoRepo<Testreport> {
@@ -26,6 +26,66 @@ impl MongoRepo<Testreport> {
     Ok(testreports)
   }
 
+  // Latest first
+  pub async fn get_project_build_testreports(&self, project_id: &str, build_filter: &TestreportBuildFilter, limit: Option<i64>) -> Result<Vec<Testreport>, Box<dyn Error + Send + Sync>> {
+    let mut filter = doc! { "project_id": ObjectId::parse_str(project_id)? };
+    if let Some(commit_sha) = &build_filter.commit_sha {
+      // Reports may record an abbreviation of the commit, or the full commit of an abbreviation
+      let abbreviations: Vec<&str> = (MIN_COMMIT_SHA_LENGTH..commit_sha.len()).map(|length| &commit_sha[..length]).collect();
+      filter.insert("$or", vec![
+        doc! { "build.commit_sha": { "$regex": format!("^{}", commit_sha) } },
+        doc! { "build.commit_sha": { "$in": abbreviations } },
+      ]);
+    }
+    let fields = [
+      ("build.branch", &build_filter.branch),
+      ("build.tag", &build_filter.tag),
+      ("build.build_number", &build_filter.build_number),
+      ("build.actor", &build_filter.actor),
+      ("build.environment", &build_filter.environment),
+    ];
+    for (field, value) in fields {
+      if let Some(value) = value {
+        filter.insert(field, value);
+      }
+    }
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).limit(limit).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testreports: Vec<Testreport> = cursor.try_collect().await?;
+    Ok(testreports)
+  }
+
+  pub async fn get_testreports_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Testreport>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": { "$in": ids } };
+    let cursor = self.col.find(filter, None).await?;
+    let testreports: Vec<Testreport> = cursor.try_collect().await?;
+    Ok(testreports)
+  }
+
+  // Latest first
+  pub async fn get_testlist_configured_testreports(&self, testlist_id: &str) -> Result<Vec<Testreport>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testlist_id": ObjectId::parse_str(testlist_id)?, "testconfiguration": { "$ne": null } };
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testreports: Vec<Testreport> = cursor.try_collect().await?;
+    Ok(testreports)
+  }
+
+  // Latest report of the same testlist and configuration created before this one, reruns cover a
+  // subset of the checks and are left out
+  pub async fn get_previous_testreport(&self, testreport: &Testreport) -> Result<Option<Testreport>, Box<dyn Error + Send + Sync>> {
+    let testconfiguration_id = testreport.testconfiguration.as_ref().map(|testconfiguration| testconfiguration.testconfiguration_id);
+    let filter = doc! {
+      "testlist_id": testreport.testlist_id,
+      "testconfiguration.testconfiguration_id": testconfiguration_id,
+      "parent_id": null,
+      "created_at": { "$lt": testreport.created_at },
+    };
+    let options = FindOneOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let result = self.col.find_one(filter, options).await?;
+    Ok(result)
+  }
+
   pub async fn get_testreport_by_id(&self, id: &str) -> Result<Option<Testreport>, Box<dyn Error + Send + Sync>> {
     let oid = ObjectId::parse_str(id)?;
     let filter = doc! { "_id": oid };
@@ -33,17 +93,62 @@ impl MongoRepo<Testreport> {
     Ok(result)
   }
 
-  pub async fn create_testreport(&self, account_id: &str, project_id: &str, testlist: Testlist, data: TestreportDto) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+  pub async fn create_testreport(&self, account_id: &str, project_id: &str, testlist: Testlist, testsections: Vec<Testsection>, testconfiguration: Option<TestreportConfiguration>, data: TestreportDto) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
     let now = DateTime::from_chrono(chrono::Utc::now());
+    let sections = testsections.into_iter().map(|testsection| TestreportSection {
+      testsection_id: testsection.id,
+      parent_id: testsection.parent_id,
+      name: testsection.name,
+      description: testsection.description,
+      position: testsection.position,
+    }).collect();
+    let name = match &testconfiguration {
+      Some(testconfiguration) => format!("{} [{}]", testlist.name, testconfiguration.name),
+      None => testlist.name,
+    };
     let new_doc = Testreport {
       id: ObjectId::new(),
       account_id: ObjectId::parse_str(account_id)?,
       project_id: ObjectId::parse_str(project_id)?,
       testlist_id: testlist.id,
-      name: testlist.name,
+      parent_id: None,
+      name,
       description: testlist.description,
       execution: data.execution,
+      tags_filter: data.tags_filter,
+      status: TestreportStatus::Draft,
+      signoff: None,
+      history: vec![],
       executors: None,
+      sections,
+      testconfiguration,
+      build: data.build.filter(|build| !build.is_empty()),
+      created_at: now,
+      updated_at: now,
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn create_rerun_testreport(&self, parent: &Testreport, execution: String, build: Option<TestreportBuild>) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let new_doc = Testreport {
+      id: ObjectId::new(),
+      account_id: parent.account_id,
+      project_id: parent.project_id,
+      testlist_id: parent.testlist_id,
+      parent_id: Some(parent.id),
+      name: parent.name.clone(),
+      description: parent.description.clone(),
+      execution,
+      tags_filter: parent.tags_filter.clone(),
+      status: TestreportStatus::Draft,
+      signoff: None,
+      history: vec![],
+      executors: None,
+      sections: parent.sections.clone(),
+      testconfiguration: parent.testconfiguration.clone(),
+      build: build.or_else(|| parent.build.clone()).filter(|build| !build.is_empty()),
       created_at: now,
       updated_at: now,
     };
@@ -54,11 +159,49 @@ impl MongoRepo<Testreport> {
   pub async fn update_testreport(&self, id: String, data: TestreportDto) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
     let now = chrono::Utc::now();
     let filter = doc! { "_id": ObjectId::parse_str(&id)? };
-    let upd_doc = doc! { "$set": {
+    let mut set_doc = doc! {
       "name": data.name,
       "description": data.description,
       "execution": data.execution,
       "updated_at": DateTime::from_chrono(now)
+    };
+    // An empty build removes the build of the report
+    if let Some(build) = data.build {
+      set_doc.insert("build", bson::to_bson(&Some(build).filter(|build| !build.is_empty()))?);
+    }
+    let upd_doc = doc! { "$set": set_doc };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testreport_build(&self, id: ObjectId, build: &TestreportBuild) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": id };
+    let upd_doc = doc! { "$set": {
+      "build": bson::to_bson(build)?,
+      "updated_at": DateTime::from_chrono(chrono::Utc::now())
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testreport_status(&self, id: &str, status: TestreportStatus, signoff: Option<TestreportSignoff>, transition: TestreportTransition) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(id)? };
+    let upd_doc = doc! {
+      "$set": {
+        "status": bson::to_bson(&status)?,
+        "signoff": bson::to_bson(&signoff)?,
+        "updated_at": transition.created_at
+      },
+      "$push": { "history": bson::to_bson(&transition)? }
+    };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testreport_executors(&self, id: &str, executors: Vec<TestExecutor>) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(id)? };
+    let upd_doc = doc! { "$set": {
+      "executors": bson::to_bson(&executors)?
     } };
     let result = self.col.update_one(filter, upd_doc, None).await?;
     Ok(result)
@@ -71,3 +214,281 @@ impl MongoRepo<Testreport> {
   }
 
 }
+
+pub const MIN_COMMIT_SHA_LENGTH: usize = 7;
+
+// Trims the build fields, blank ones are removed
+pub fn normalize_testreport_build(build: TestreportBuild) -> Result<TestreportBuild, String> {
+  let trim = |value: Option<String>| value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+  let build = TestreportBuild {
+    commit_sha: trim(build.commit_sha).map(|commit_sha| commit_sha.to_lowercase()),
+    branch: trim(build.branch).map(|branch| branch.trim_start_matches("refs/heads/").to_string()),
+    tag: trim(build.tag).map(|tag| tag.trim_start_matches("refs/tags/").to_string()),
+    pipeline_url: trim(build.pipeline_url),
+    build_number: trim(build.build_number),
+    actor: trim(build.actor),
+    environment: trim(build.environment),
+  };
+  if let Some(commit_sha) = &build.commit_sha {
+    check_commit_sha(commit_sha)?;
+  }
+  if let Some(pipeline_url) = &build.pipeline_url {
+    if !pipeline_url.starts_with("https://") && !pipeline_url.starts_with("http://") {
+      return Err(format!("Invalid pipeline URL: {}", pipeline_url));
+    }
+  }
+  Ok(build)
+}
+
+// Abbreviated or full SHA-1 or SHA-256 commit hash
+pub fn check_commit_sha(commit_sha: &str) -> Result<(), String> {
+  if commit_sha.len() < MIN_COMMIT_SHA_LENGTH || commit_sha.len() > 64 || !commit_sha.chars().all(|c| c.is_ascii_hexdigit()) {
+    return Err(format!("Invalid commit SHA: {}", commit_sha));
+  }
+  Ok(())
+}
+
+pub fn is_testreport_transition_allowed(from: TestreportStatus, to: TestreportStatus) -> bool {
+  matches!((from, to),
+    (TestreportStatus::Draft, TestreportStatus::InProgress) |
+    (TestreportStatus::InProgress, TestreportStatus::Completed) |
+    (TestreportStatus::Completed, TestreportStatus::SignedOff) |
+    (TestreportStatus::Completed, TestreportStatus::InProgress) |
+    (TestreportStatus::SignedOff, TestreportStatus::InProgress)
+  )
+}
+
+// Reopening moves a completed or signed off report back in progress
+pub fn is_testreport_reopening(from: TestreportStatus, to: TestreportStatus) -> bool {
+  to == TestreportStatus::InProgress && (from == TestreportStatus::Completed || from == TestreportStatus::SignedOff)
+}
+
+pub fn get_testreport_progress(testreport: &Testreport, testresults: &[Testresult]) -> TestreportProgress {
+  let count = |status: TestresultStatus| testresults.iter().filter(|testresult| testresult.status() == status).count() as u32;
+  TestreportProgress {
+    testreport_id: testreport.id,
+    name: testreport.name.clone(),
+    total: testresults.len() as u32,
+    executed: testresults.iter().filter(|testresult| testresult.updated).count() as u32,
+    passed: count(TestresultStatus::Passed),
+    failed: count(TestresultStatus::Failed),
+    blocked: count(TestresultStatus::Blocked),
+  }
+}
+
+// Expects the testreports latest first, only the latest report of each configuration is shown
+pub fn get_testreport_matrix(testreports: Vec<(Testreport, Vec<Testresult>)>) -> TestreportMatrix {
+  let mut latest: Vec<(Testreport, Vec<Testresult>)> = vec![];
+  for (testreport, testresults) in testreports {
+    let testconfiguration_id = match &testreport.testconfiguration {
+      Some(testconfiguration) => testconfiguration.testconfiguration_id,
+      None => continue,
+    };
+    let seen = latest.iter().any(|(report, _)| report.testconfiguration.as_ref().is_some_and(|c| c.testconfiguration_id == testconfiguration_id));
+    if !seen {
+      latest.push((testreport, testresults));
+    }
+  }
+  latest.sort_by_key(|(testreport, _)| testreport.testconfiguration.as_ref().map(|c| c.name.clone()));
+
+  let mut rows: Vec<TestreportMatrixRow> = vec![];
+  for (index, (_, testresults)) in latest.iter().enumerate() {
+    for testresult in testresults {
+      let row = match rows.iter().position(|row| row.testcheck_id == testresult.testcheck_id) {
+        Some(position) => &mut rows[position],
+        None => {
+          rows.push(TestreportMatrixRow {
+            testcheck_id: testresult.testcheck_id,
+            name: testresult.name.clone(),
+            position: testresult.position,
+            results: vec![None; latest.len()],
+          });
+          rows.last_mut().unwrap()
+        },
+      };
+      row.results[index] = Some(testresult.status());
+    }
+  }
+  rows.sort_by_key(|row| row.position);
+
+  let columns = latest.into_iter().map(|(testreport, _)| {
+    let testconfiguration = testreport.testconfiguration.unwrap();
+    TestreportMatrixColumn {
+      testconfiguration_id: testconfiguration.testconfiguration_id,
+      name: testconfiguration.name,
+      testreport_id: testreport.id,
+      created_at: testreport.created_at,
+    }
+  }).collect();
+
+  TestreportMatrix { columns, rows }
+}
+
+pub fn get_testreport_workload(testresults: &[Testresult]) -> Vec<TestreportWorkload> {
+  let mut workload: Vec<TestreportWorkload> = vec![];
+  for testresult in testresults {
+    let index = match workload.iter().position(|tester| tester.assignee_id == testresult.assignee_id) {
+      Some(index) => index,
+      None => {
+        workload.push(TestreportWorkload {
+          assignee_id: testresult.assignee_id,
+          total: 0,
+          executed: 0,
+          passed: 0,
+          failed: 0,
+          blocked: 0,
+          share: 0.0,
+        });
+        workload.len() - 1
+      },
+    };
+    let tester = &mut workload[index];
+    tester.total += 1;
+    match testresult.status() {
+      TestresultStatus::NotExecuted => continue,
+      TestresultStatus::Passed => tester.passed += 1,
+      TestresultStatus::Failed => tester.failed += 1,
+      TestresultStatus::Blocked => tester.blocked += 1,
+    }
+    tester.executed += 1;
+  }
+  for tester in workload.iter_mut() {
+    tester.share = tester.total as f64 * 100.0 / testresults.len() as f64;
+  }
+  workload.sort_by_key(|tester| std::cmp::Reverse(tester.total));
+  workload
+}
+
+// Summaries include the results of all the nested sections, the first one covers the whole report
+pub fn summarize_testreport_sections(testreport: &Testreport, testresults: &[Testresult]) -> Vec<TestreportSectionSummary> {
+  let mut summaries = vec![TestreportSectionSummary {
+    testsection_id: None,
+    parent_id: None,
+    name: testreport.name.clone(),
+    depth: 0,
+    total: 0,
+    executed: 0,
+    passed: 0,
+    failed: 0,
+    blocked: 0,
+  }];
+  add_section_summaries(&testreport.sections, None, 1, &mut summaries);
+
+  for testresult in testresults {
+    let mut section_id = testresult.section_id;
+    let mut indexes = vec![0];
+    while let Some(id) = section_id {
+      match summaries.iter().position(|summary| summary.testsection_id == Some(id)) {
+        Some(index) => {
+          indexes.push(index);
+          section_id = summaries[index].parent_id;
+        },
+        None => break,
+      }
+    }
+    for index in indexes {
+      let summary = &mut summaries[index];
+      summary.total += 1;
+      match testresult.status() {
+        TestresultStatus::NotExecuted => continue,
+        TestresultStatus::Passed => summary.passed += 1,
+        TestresultStatus::Failed => summary.failed += 1,
+        TestresultStatus::Blocked => summary.blocked += 1,
+      }
+      summary.executed += 1;
+    }
+  }
+  summaries
+}
+
+fn add_section_summaries(sections: &[TestreportSection], parent_id: Option<ObjectId>, depth: u16, summaries: &mut Vec<TestreportSectionSummary>) {
+  let mut children: Vec<&TestreportSection> = sections.iter().filter(|section| section.parent_id == parent_id).collect();
+  children.sort_by_key(|section| section.position);
+  for section in children {
+    summaries.push(TestreportSectionSummary {
+      testsection_id: Some(section.testsection_id),
+      parent_id: section.parent_id,
+      name: section.name.clone(),
+      depth,
+      total: 0,
+      executed: 0,
+      passed: 0,
+      failed: 0,
+      blocked: 0,
+    });
+    add_section_summaries(sections, Some(section.testsection_id), depth + 1, summaries);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_blocked_results_apart() {
+    let now = DateTime::now();
+    let (section_id, assignee_id) = (ObjectId::new(), ObjectId::new());
+    let testreport = Testreport {
+      id: ObjectId::new(),
+      account_id: ObjectId::new(),
+      project_id: ObjectId::new(),
+      testlist_id: ObjectId::new(),
+      parent_id: None,
+      name: "Release 2.1".to_string(),
+      description: String::new(),
+      execution: "manual".to_string(),
+      tags_filter: None,
+      status: TestreportStatus::InProgress,
+      signoff: None,
+      history: Vec::new(),
+      executors: None,
+      sections: vec![TestreportSection { testsection_id: section_id, parent_id: None, name: "Cart".to_string(), description: String::new(), position: 0 }],
+      testconfiguration: None,
+      build: None,
+      created_at: now,
+      updated_at: now,
+    };
+    let testresult = |updated: bool, pass: bool, blocked: bool| Testresult {
+      id: ObjectId::new(),
+      account_id: testreport.account_id,
+      testreport_id: testreport.id,
+      testcheck_id: ObjectId::new(),
+      section_id: Some(section_id),
+      testcheck_revision: 1,
+      name: "Check out".to_string(),
+      description: String::new(),
+      expected: String::new(),
+      tags: Vec::new(),
+      position: 0,
+      updated,
+      pass,
+      blocked,
+      flacky: false,
+      automated: false,
+      notes: String::new(),
+      url_issue: String::new(),
+      url_result: String::new(),
+      executors: Vec::new(),
+      assignee_id: Some(assignee_id),
+      steps: Vec::new(),
+      retries: 0,
+      duration_ms: None,
+      attachments: Vec::new(),
+      created_at: now,
+      updated_at: now,
+    };
+    let testresults = vec![testresult(true, true, false), testresult(true, false, false), testresult(true, false, true), testresult(false, false, false)];
+
+    let progress = get_testreport_progress(&testreport, &testresults);
+    assert_eq!((progress.total, progress.executed, progress.passed, progress.failed, progress.blocked), (4, 3, 1, 1, 1));
+
+    let workload = get_testreport_workload(&testresults);
+    assert_eq!((workload[0].total, workload[0].executed, workload[0].passed, workload[0].failed, workload[0].blocked), (4, 3, 1, 1, 1));
+
+    let summaries = summarize_testreport_sections(&testreport, &testresults);
+    assert_eq!(summaries.len(), 2);
+    for summary in summaries {
+      assert_eq!((summary.total, summary.executed, summary.passed, summary.failed, summary.blocked), (4, 3, 1, 1, 1));
+    }
+  }
+}
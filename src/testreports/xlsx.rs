@@ -0,0 +1,171 @@
+// Excel workbook of a report: a summary sheet computing the counters with formulas over one sheet
+// per section or tag, statuses colored with conditional formats.
+
+use std::collections::HashMap;
+
+use mongodb::bson::oid::ObjectId;
+use rust_xlsxwriter::{Color, ConditionalFormatCell, ConditionalFormatCellRule, Format, Formula, Workbook, Worksheet, XlsxError};
+
+use crate::{projects::schema::Project, service::xlsx::{write_xlsx_datetime, write_xlsx_table_header, write_xlsx_url, xlsx_header_format, xlsx_sheet_name, xlsx_sheet_reference, xlsx_text}, testresults::schema::{Testresult, TestresultStatus}};
+
+use super::{export::{count_testresults, get_executor_names, get_status_label, group_testreport_results, TestreportCounters, TestreportGroupBy, TestreportResultsGroup}, schema::Testreport};
+
+const SUMMARY_COLUMNS: [(&str, f64); 8] = [
+  ("Group", 40.0), ("Total", 10.0), ("Passed", 10.0), ("Failed", 10.0), ("Blocked", 10.0), ("Not executed", 13.0), ("Executed", 10.0), ("Pass rate", 10.0),
+];
+const RESULTS_COLUMNS: [(&str, f64); 12] = [
+  ("Status", 14.0), ("Check", 40.0), ("Section", 24.0), ("Tags", 20.0), ("Notes", 50.0), ("Issue", 30.0), ("Result", 30.0),
+  ("Executors", 24.0), ("Automated", 11.0), ("Flaky", 8.0), ("Duration (s)", 12.0), ("Updated", 17.0),
+];
+
+pub fn get_testreport_xlsx(project: &Project, testreport: &Testreport, testresults: &[Testresult], user_names: &HashMap<ObjectId, String>, group_by: TestreportGroupBy) -> Result<Vec<u8>, XlsxError> {
+  let groups = group_testreport_results(testreport, testresults, group_by);
+  // Section paths are listed on every row when the sheets are tags
+  let sections = match group_by {
+    TestreportGroupBy::Tag => group_testreport_results(testreport, testresults, TestreportGroupBy::Section),
+    TestreportGroupBy::Section => Vec::new(),
+  };
+  let mut section_names: HashMap<ObjectId, &str> = HashMap::new();
+  for section in &sections {
+    for testresult in &section.testresults {
+      section_names.insert(testresult.id, section.name.as_str());
+    }
+  }
+
+  let mut used = vec!["Summary".to_string()];
+  let sheet_names: Vec<String> = groups.iter().map(|group| xlsx_sheet_name(&group.name, &mut used)).collect();
+
+  let mut workbook = Workbook::new();
+  let summary = workbook.add_worksheet();
+  summary.set_name("Summary")?;
+  write_summary(summary, project, testreport, &count_testresults(testresults), &groups, &sheet_names, group_by)?;
+
+  for (group, sheet_name) in groups.iter().zip(&sheet_names) {
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet_name)?;
+    write_testresults(worksheet, group, &section_names, user_names)?;
+  }
+  workbook.save_to_buffer()
+}
+
+fn write_summary(worksheet: &mut Worksheet, project: &Project, testreport: &Testreport, counters: &TestreportCounters, groups: &[TestreportResultsGroup], sheet_names: &[String], group_by: TestreportGroupBy) -> Result<(), XlsxError> {
+  let percent_format = Format::new().set_num_format("0.0%");
+  let total_format = Format::new().set_bold();
+  let total_percent_format = Format::new().set_bold().set_num_format("0.0%");
+
+  for (index, (group, sheet_name)) in groups.iter().zip(sheet_names).enumerate() {
+    let row = index as u32 + 1;
+    let status = format!("{}!$A$2:$A${}", xlsx_sheet_reference(sheet_name), group.testresults.len() + 1);
+    worksheet.write_url_with_text(row, 0, format!("internal:{}!A1", xlsx_sheet_reference(sheet_name)).as_str(), xlsx_text(&group.name))?;
+    let counts = [
+      (format!("=COUNTA({})", status), group.counters.total),
+      (format!("=COUNTIF({},\"{}\")", status, get_status_label(TestresultStatus::Passed)), group.counters.passed),
+      (format!("=COUNTIF({},\"{}\")", status, get_status_label(TestresultStatus::Failed)), group.counters.failed),
+      (format!("=COUNTIF({},\"{}\")", status, get_status_label(TestresultStatus::Blocked)), group.counters.blocked),
+      (format!("=COUNTIF({},\"{}\")", status, get_status_label(TestresultStatus::NotExecuted)), group.counters.not_executed),
+      (format!("=B{0}-F{0}", row + 1), group.counters.executed),
+    ];
+    for (col, (formula, result)) in counts.into_iter().enumerate() {
+      worksheet.write_formula(row, col as u16 + 1, Formula::new(formula).set_result(result.to_string()))?;
+    }
+    let pass_rate = Formula::new(format!("=IF(G{0}=0,0,C{0}/G{0})", row + 1)).set_result((group.counters.pass_rate / 100.0).to_string());
+    worksheet.write_formula_with_format(row, 7, pass_rate, &percent_format)?;
+  }
+
+  let total_row = groups.len() as u32 + 1;
+  worksheet.write_string_with_format(total_row, 0, "Total", &total_format)?;
+  let totals = [counters.total, counters.passed, counters.failed, counters.blocked, counters.not_executed, counters.executed];
+  for (index, total) in totals.into_iter().enumerate() {
+    let col = index as u16 + 1;
+    match group_by {
+      TestreportGroupBy::Section => {
+        let column = (b'B' + index as u8) as char;
+        let formula = Formula::new(format!("=SUM({0}2:{0}{1})", column, total_row)).set_result(total.to_string());
+        worksheet.write_formula_with_format(total_row, col, formula, &total_format)?;
+      },
+      // A result is counted in each of its tags, the report totals are not the sum of the sheets
+      TestreportGroupBy::Tag => {
+        worksheet.write_number_with_format(total_row, col, total, &total_format)?;
+      },
+    }
+  }
+  let pass_rate = Formula::new(format!("=IF(G{0}=0,0,C{0}/G{0})", total_row + 1)).set_result((counters.pass_rate / 100.0).to_string());
+  worksheet.write_formula_with_format(total_row, 7, pass_rate, &total_percent_format)?;
+
+  let failed_format = Format::new().set_font_color(Color::RGB(0x9C0006)).set_background_color(Color::RGB(0xFFC7CE));
+  worksheet.add_conditional_format(1, 3, total_row, 3, &ConditionalFormatCell::new().set_rule(ConditionalFormatCellRule::GreaterThan(0)).set_format(failed_format))?;
+  write_xlsx_table_header(worksheet, &SUMMARY_COLUMNS, groups.len() as u32)?;
+
+  let mut fields: Vec<(&str, String)> = vec![
+    ("Testreport", testreport.name.clone()),
+    ("Project", if project.version.is_empty() { project.name.clone() } else { format!("{} {}", project.name, project.version) }),
+    ("Status", testreport.status.to_string().replace('_', " ")),
+  ];
+  if !testreport.execution.is_empty() {
+    fields.push(("Execution", testreport.execution.clone()));
+  }
+  if let Some(testconfiguration) = &testreport.testconfiguration {
+    fields.push(("Configuration", testconfiguration.name.clone()));
+  }
+  if let Some(build) = &testreport.build {
+    let build_fields = [("Branch", &build.branch), ("Tag", &build.tag), ("Commit", &build.commit_sha), ("Build", &build.build_number), ("Pipeline", &build.pipeline_url)];
+    for (name, value) in build_fields {
+      if let Some(value) = value {
+        fields.push((name, value.clone()));
+      }
+    }
+  }
+  let mut row = total_row + 2;
+  let header_format = xlsx_header_format();
+  for (name, value) in fields {
+    worksheet.write_string_with_format(row, 0, name, &header_format)?;
+    worksheet.write_string(row, 1, xlsx_text(&value))?;
+    row += 1;
+  }
+  worksheet.write_string_with_format(row, 0, "Created", &header_format)?;
+  write_xlsx_datetime(worksheet, row, 1, testreport.created_at)?;
+  if let Some(signoff) = &testreport.signoff {
+    worksheet.write_string_with_format(row + 1, 0, "Signed off", &header_format)?;
+    write_xlsx_datetime(worksheet, row + 1, 1, signoff.signed_at)?;
+  }
+  Ok(())
+}
+
+fn write_testresults(worksheet: &mut Worksheet, group: &TestreportResultsGroup, section_names: &HashMap<ObjectId, &str>, user_names: &HashMap<ObjectId, String>) -> Result<(), XlsxError> {
+  let wrap_format = Format::new().set_text_wrap();
+  for (index, testresult) in group.testresults.iter().enumerate() {
+    let row = index as u32 + 1;
+    worksheet.write_string(row, 0, get_status_label(testresult.status()))?;
+    worksheet.write_string(row, 1, xlsx_text(&testresult.name))?;
+    // Sheets of sections are named after the section
+    let section = section_names.get(&testresult.id).copied().unwrap_or(group.name.as_str());
+    worksheet.write_string(row, 2, xlsx_text(section))?;
+    worksheet.write_string(row, 3, xlsx_text(&testresult.tags.join(", ")))?;
+    worksheet.write_string_with_format(row, 4, xlsx_text(&testresult.notes), &wrap_format)?;
+    write_xlsx_url(worksheet, row, 5, &testresult.url_issue)?;
+    write_xlsx_url(worksheet, row, 6, &testresult.url_result)?;
+    worksheet.write_string(row, 7, xlsx_text(&get_executor_names(testresult, user_names).join(", ")))?;
+    worksheet.write_boolean(row, 8, testresult.automated)?;
+    worksheet.write_boolean(row, 9, testresult.flacky)?;
+    if let Some(duration_ms) = testresult.duration_ms {
+      worksheet.write_number(row, 10, duration_ms as f64 / 1000.0)?;
+    }
+    if testresult.updated {
+      write_xlsx_datetime(worksheet, row, 11, testresult.updated_at)?;
+    }
+  }
+
+  let last_row = (group.testresults.len() as u32).max(1);
+  let status_formats = [
+    (TestresultStatus::Passed, 0x006100, 0xC6EFCE),
+    (TestresultStatus::Failed, 0x9C0006, 0xFFC7CE),
+    (TestresultStatus::Blocked, 0x9C5700, 0xFFEB9C),
+    (TestresultStatus::NotExecuted, 0x595959, 0xEDEDED),
+  ];
+  for (status, font_color, background_color) in status_formats {
+    let format = Format::new().set_font_color(Color::RGB(font_color)).set_background_color(Color::RGB(background_color));
+    let conditional_format = ConditionalFormatCell::new().set_rule(ConditionalFormatCellRule::EqualTo(get_status_label(status))).set_format(format);
+    worksheet.add_conditional_format(1, 0, last_row, 0, &conditional_format)?;
+  }
+  write_xlsx_table_header(worksheet, &RESULTS_COLUMNS, group.testresults.len() as u32)
+}
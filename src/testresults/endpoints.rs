@@ -0,0 +1,140 @@
+use bson::{oid::ObjectId, DateTime};
+use log::{error, warn};
+use rocket::{get, put, routes, serde::json::Json, State};
+use crate::{projects::schema::{Project, ProjectFlakyPolicy}, service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testreports::{endpoints::get_testreport_by_id, schema::{TestExecutor, Testreport, TestreportStatus}}, users::{roles::is_admin, schema::User}};
+
+use super::{schema::{Testresult, TestresultDto}, service::get_testchecks_flakiness};
+
+#[get("/<id>")]
+async fn get_testresult(jwt: Result<JWT, JsonError>, id: &str, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testresult>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  match allowed_for_testresult(jwts, testresult_repo, id).await {
+    Ok(testresult) => Ok(Json(testresult)),
+    Err(e) => Err(e)
+  }
+}
+
+#[put("/<id>", format = "json", data = "<data>")]
+async fn update_testresult(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestresultDto>, testresult_repo: &State<MongoRepo<Testresult>>, testreport_repo: &State<MongoRepo<Testreport>>, project_repo: &State<MongoRepo<Project>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testresult>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let user_id = jwts.user.id;
+  let testresult = allowed_for_testresult(jwts, testresult_repo, id).await?;
+
+  let testreport = get_testreport_by_id(testreport_repo, &testresult.testreport_id.to_hex()).await?;
+  if testreport.status == TestreportStatus::SignedOff {
+    return Err(JsonError::Forbidden("Testresults of a signed off testreport are locked".to_string()));
+  }
+
+  // Whoever records a result is tracked as one of its executors, and of the report ones
+  let now = DateTime::from_chrono(chrono::Utc::now());
+  let mut executors = testresult.executors;
+  if !executors.iter().any(|executor| executor.user_id == user_id) {
+    executors.push(TestExecutor { user_id, start_date: now });
+  }
+  let mut testreport_executors = testreport.executors.clone().unwrap_or_default();
+  if !testreport_executors.iter().any(|executor| executor.user_id == user_id) {
+    testreport_executors.push(TestExecutor { user_id, start_date: now });
+    if let Err(e) = testreport_repo.update_testreport_executors(&testreport.id.to_hex(), testreport_executors).await {
+      error!("Error updating testreport executors: {}", e);
+      return Err(JsonError::Internal("Error updating testreport executors".to_string()));
+    }
+  }
+
+  let data = data.into_inner();
+  let flacky = data.flacky;
+  match testresult_repo.update_testresult(id.to_string(), data, executors).await {
+    Ok(updated) => {
+      if updated.matched_count == 0 {
+        warn!("Testresult not found: {}", id);
+        return Err(JsonError::NotFound("Testresult not found".to_string()));
+      }
+      if !flacky {
+        if let Some(policy) = get_auto_flag_flaky_policy(project_repo, &testreport).await? {
+          flag_flaky_testresult(testresult_repo, &policy, &testresult.testcheck_id, id).await?;
+        }
+      }
+      get_testresult_by_id(testresult_repo, id).await.map(Json)
+    },
+    Err(e) => {
+      error!("Error updating testresult: {}", e);
+      Err(JsonError::Internal("Error updating testresult".to_string()))
+    },
+  }
+}
+
+#[get("/assigned?<pending>")]
+async fn get_assigned_testresults(jwt: Result<JWT, JsonError>, pending: Option<bool>, testresult_repo: &State<MongoRepo<Testresult>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Vec<Testresult>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+
+  match testresult_repo.get_assigned_testresults(jwts.user.id, pending.unwrap_or(false)).await {
+    Ok(testresults) => Ok(Json(testresults)),
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      Err(JsonError::Internal("Error getting testresults".to_string()))
+    },
+  }
+}
+
+pub fn get_testresults_routes() -> Vec<rocket::Route> {
+  routes![get_testresult, update_testresult, get_assigned_testresults]
+}
+
+// Flaky policy of the report project, None when results are not flagged automatically
+pub async fn get_auto_flag_flaky_policy(project_repo: &State<MongoRepo<Project>>, testreport: &Testreport) -> Result<Option<ProjectFlakyPolicy>, JsonError> {
+  match project_repo.get_project_by_id(&testreport.project_id.to_hex()).await {
+    Ok(Some(project)) => Ok(Some(project.flaky).filter(|policy| policy.auto_flag)),
+    Ok(None) => Ok(None),
+    Err(e) => {
+      error!("Error getting project: {}", e);
+      Err(JsonError::Internal("Error getting project".to_string()))
+    },
+  }
+}
+
+// Applies the project flaky policy to a freshly recorded result
+pub async fn flag_flaky_testresult(testresult_repo: &State<MongoRepo<Testresult>>, policy: &ProjectFlakyPolicy, testcheck_id: &ObjectId, id: &str) -> Result<(), JsonError> {
+  let testresults = match testresult_repo.get_testcheck_executed_testresults(*testcheck_id, policy.window).await {
+    Ok(testresults) => testresults,
+    Err(e) => {
+      error!("Error getting testresults: {}", e);
+      return Err(JsonError::Internal("Error getting testresults".to_string()));
+    },
+  };
+  let flaky = get_testchecks_flakiness(&testresults, policy).iter().any(|testcheck| testcheck.flaky);
+  if flaky && testresult_repo.set_testresult_flacky(id).await.is_err() {
+    error!("Error flagging flaky testresult: {}", id);
+    return Err(JsonError::Internal("Error flagging flaky testresult".to_string()));
+  }
+  Ok(())
+}
+
+pub async fn get_testresult_by_id(testresult_repo: &State<MongoRepo<Testresult>>, id: &str) -> Result<Testresult, JsonError> {
+  match testresult_repo.get_testresult_by_id(id).await {
+    Ok(testresult) => match testresult {
+      Some(testresult) => Ok(testresult),
+      None => {
+        warn!("Testresult not found: {}", id);
+        Err(JsonError::NotFound("Testresult not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testresult: {}", e);
+      Err(JsonError::Internal("Error getting testresult".to_string()))
+    },
+  }
+}
+
+async fn allowed_for_testresult(jwts: JWTSessionAndUser, testresult_repo: &State<MongoRepo<Testresult>>, testresult_id: &str) -> Result<Testresult, JsonError>  {
+  if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
+    return Err(JsonError::Forbidden(
+      "You are not allowed to retrieve this testresult".to_string(),
+    ));
+  }
+  let testresult = get_testresult_by_id(testresult_repo, testresult_id).await?;
+  if let Some(accounts) = &jwts.user.accounts {
+    if !accounts.iter().any(|account| account.account_id == testresult.account_id) && !is_admin(&jwts.user) {
+      return Err(JsonError::Forbidden("You are not allowed to retrieve this testresult".to_string()));
+    }
+  }
+  Ok(testresult)
+}
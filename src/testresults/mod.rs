@@ -1,2 +1,3 @@
+pub mod endpoints;
 pub mod schema;
 pub mod service;
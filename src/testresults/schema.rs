@@ -2,7 +2,7 @@ use bson::DateTime;
 use mongodb::bson::oid::ObjectId;
 use rocket::serde::{Deserialize, Serialize};
 
-use crate::{service::db::{serialize_datetime, serialize_object_id}, testreports::schema::TestExecutor};
+use crate::{service::db::{serialize_datetime, serialize_object_id, serialize_option_object_id}, testreports::schema::TestExecutor};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Testresult {
@@ -14,6 +14,10 @@ pub struct Testresult {
   pub testreport_id: ObjectId,
   #[serde(serialize_with = "serialize_object_id")]
   pub testcheck_id: ObjectId,
+  #[serde(default, serialize_with = "serialize_option_object_id")]
+  pub section_id: Option<ObjectId>,
+  #[serde(default)]
+  pub testcheck_revision: u32,
   // Derived from testcheck
   pub name: String,
   pub description: String,
@@ -24,21 +28,54 @@ pub struct Testresult {
   pub updated: bool,
   // result
   pub pass: bool,
+  // Could not be executed, e.g. because of an environment issue
+  #[serde(default)]
+  pub blocked: bool,
   pub flacky: bool,
   pub automated: bool,
   pub notes: String,
   pub url_issue: String,
   pub url_result: String,
   pub executors: Vec<TestExecutor>,
+  // Tester in charge of executing the check
+  #[serde(default, serialize_with = "serialize_option_object_id")]
+  pub assignee_id: Option<ObjectId>,
+  // Filled by automated results ingestion
+  #[serde(default)]
+  pub steps: Vec<TestresultStep>,
+  #[serde(default)]
+  pub retries: u32,
+  #[serde(default)]
+  pub duration_ms: Option<u64>,
+  #[serde(default)]
+  pub attachments: Vec<TestresultAttachment>,
   #[serde(serialize_with = "serialize_datetime")]
   pub created_at: DateTime,
   #[serde(serialize_with = "serialize_datetime")]
   pub updated_at: DateTime,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestresultStep {
+  pub name: String,
+  pub status: TestresultStatus,
+  pub duration_ms: Option<u64>,
+  pub message: String,
+}
+
+// Embedded file, e.g. a screenshot, its data is base64 encoded
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestresultAttachment {
+  pub name: String,
+  pub content_type: String,
+  pub data: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TestresultDto {
   pub pass: bool,
+  #[serde(default)]
+  pub blocked: bool,
   pub flacky: bool,
   pub automated: bool,
   pub notes: String,
@@ -46,3 +83,61 @@ pub struct TestresultDto {
   pub url_result: String,
 }
 
+
+// Assigns the listed results and every result of the listed sections, subsections included
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestresultsAssignmentDto {
+  // None removes the assignment
+  pub assignee_id: Option<String>,
+  #[serde(default)]
+  pub testresult_ids: Vec<String>,
+  #[serde(default)]
+  pub testsection_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TestresultStatus {
+  Passed,
+  Failed,
+  Blocked,
+  NotExecuted,
+}
+
+impl std::fmt::Display for TestresultStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TestresultStatus::Passed => write!(f, "passed"),
+      TestresultStatus::Failed => write!(f, "failed"),
+      TestresultStatus::Blocked => write!(f, "blocked"),
+      TestresultStatus::NotExecuted => write!(f, "not_executed"),
+    }
+  }
+}
+
+impl Testresult {
+  pub fn status(&self) -> TestresultStatus {
+    if !self.updated {
+      TestresultStatus::NotExecuted
+    } else if self.blocked {
+      TestresultStatus::Blocked
+    } else if self.pass {
+      TestresultStatus::Passed
+    } else {
+      TestresultStatus::Failed
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestcheckFlakiness {
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testcheck_id: ObjectId,
+  pub name: String,
+  // Executions considered, latest first
+  pub runs: u32,
+  pub failures: u32,
+  pub flips: u32,
+  pub flip_rate: f64,
+  pub flaky: bool,
+}
@@ -2,10 +2,10 @@ use std::error::Error;
 
 use bson::DateTime;
 use mongodb::{
-  bson::{self, doc, oid::ObjectId}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
+  bson::{self, doc, oid::ObjectId}, options::FindOptions, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
 };
-use crate::{service::db::{ self, MongoRepo}, testchecks::schema::Testcheck};
-use super::schema::{Testresult, TestresultDto};
+use crate::{projects::schema::ProjectFlakyPolicy, service::db::{ self, MongoRepo}, testchecks::schema::Testcheck, testreports::schema::TestExecutor};
+use super::schema::{TestcheckFlakiness, Testresult, TestresultDto};
 use rocket::futures::TryStreamExt;
 
 pub fn get_testresults_repo(client: Client) -> MongoRepo<Testresult> {
@@ -13,6 +13,7 @@ pub fn get_testresults_repo(client: Client) -> MongoRepo<Testresult> {
 }
 
 impl MongoRepo<Testresult> {
+  #[allow(dead_code)]
   pub async fn get_all(&self) -> Result<Vec<Testresult>, Box<dyn Error + Send + Sync>> {
     let cursor = self.col.find(None, None).await?;
     let testresults: Vec<Testresult> = cursor.try_collect().await?;
@@ -40,6 +41,9 @@ impl MongoRepo<Testresult> {
       account_id: testcheck.account_id,
       testreport_id: ObjectId::parse_str(testreport_id)?,
       testcheck_id: testcheck.id,
+      section_id: testcheck.section_id,
+      // Checks without revisions get their baseline, revision 1, from this same content on their first edit
+      testcheck_revision: testcheck.revision.max(1),
       name: testcheck.name,
       description: testcheck.description,
       expected: testcheck.expected,
@@ -47,7 +51,13 @@ impl MongoRepo<Testresult> {
       position: testcheck.position,
       updated: false,
       executors: vec![],
+      assignee_id: None,
+      steps: vec![],
+      retries: 0,
+      duration_ms: None,
+      attachments: vec![],
       pass: false,
+      blocked: false,
       flacky: false,
       automated: false,
       notes: "".to_string(),
@@ -60,12 +70,100 @@ impl MongoRepo<Testresult> {
     Ok(result)
   }
 
-  pub async fn update_testresult(&self, id: String, data: TestresultDto) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+  pub async fn get_assigned_testresults(&self, assignee_id: ObjectId, pending: bool) -> Result<Vec<Testresult>, Box<dyn Error + Send + Sync>> {
+    let mut filter = doc! { "assignee_id": assignee_id };
+    if pending {
+      filter.insert("updated", false);
+    }
+    let options = FindOptions::builder().sort(doc! { "testreport_id": 1, "position": 1 }).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testresults: Vec<Testresult> = cursor.try_collect().await?;
+    Ok(testresults)
+  }
+
+  pub async fn assign_testresults(&self, testreport_id: ObjectId, testresult_ids: &[ObjectId], section_ids: &[ObjectId], assignee_id: Option<ObjectId>) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! {
+      "testreport_id": testreport_id,
+      "$or": [
+        { "_id": { "$in": testresult_ids } },
+        { "section_id": { "$in": section_ids } }
+      ]
+    };
+    let upd_doc = doc! { "$set": {
+      "assignee_id": assignee_id
+    } };
+    let result = self.col.update_many(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn get_testreports_testresults(&self, testreport_ids: &[ObjectId]) -> Result<Vec<Testresult>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testreport_id": { "$in": testreport_ids } };
+    let cursor = self.col.find(filter, None).await?;
+    let testresults: Vec<Testresult> = cursor.try_collect().await?;
+    Ok(testresults)
+  }
+
+  // Passed or failed results of the reports, latest first
+  pub async fn get_testreports_executed_testresults(&self, testreport_ids: &[ObjectId]) -> Result<Vec<Testresult>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testreport_id": { "$in": testreport_ids }, "updated": true, "blocked": { "$ne": true } };
+    let options = FindOptions::builder().sort(doc! { "updated_at": -1 }).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testresults: Vec<Testresult> = cursor.try_collect().await?;
+    Ok(testresults)
+  }
+
+  pub async fn get_testcheck_executed_testresults(&self, testcheck_id: ObjectId, limit: u32) -> Result<Vec<Testresult>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testcheck_id": testcheck_id, "updated": true, "blocked": { "$ne": true } };
+    let options = FindOptions::builder().sort(doc! { "updated_at": -1 }).limit(limit as i64).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testresults: Vec<Testresult> = cursor.try_collect().await?;
+    Ok(testresults)
+  }
+
+  pub async fn set_testresult_flacky(&self, id: &str) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(id)? };
+    let upd_doc = doc! { "$set": {
+      "flacky": true
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  // Copies the check of a previous result into a new report, ready to be executed again
+  pub async fn rerun_testresult(&self, testreport_id: ObjectId, testresult: Testresult) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let new_doc = Testresult {
+      id: ObjectId::new(),
+      testreport_id,
+      updated: false,
+      pass: false,
+      blocked: false,
+      flacky: false,
+      automated: false,
+      notes: "".to_string(),
+      url_issue: "".to_string(),
+      url_result: "".to_string(),
+      executors: vec![],
+      steps: vec![],
+      retries: 0,
+      duration_ms: None,
+      attachments: vec![],
+      created_at: now,
+      updated_at: now,
+      ..testresult
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testresult(&self, id: String, data: TestresultDto, executors: Vec<TestExecutor>) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
     let now = chrono::Utc::now();
     let filter = doc! { "_id": ObjectId::parse_str(&id)? };
     let upd_doc = doc! { "$set": {
+      "executors": bson::to_bson(&executors)?,
       "updated": true,
       "pass": data.pass,
+      "blocked": data.blocked,
       "flacky": data.flacky,
       "automated": data.automated,
       "notes": data.notes,
@@ -77,16 +175,132 @@ impl MongoRepo<Testresult> {
     Ok(result)
   }
 
+  #[allow(dead_code)]
   pub async fn delete_testresult(&self, id: &str) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
     let filter = doc! { "_id": ObjectId::parse_str(id)? };
     let result = self.col.delete_one(filter, None).await?;
     Ok(result)
   }
 
-  pub async fn delete_testreport_testresults(&self, testresult_id: &str) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
-    let filter = doc! { "testresult_id": ObjectId::parse_str(testresult_id)? };
+  pub async fn delete_testreport_testresults(&self, testreport_id: &str) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testreport_id": ObjectId::parse_str(testreport_id)? };
     let result = self.col.delete_many(filter, None).await?;
     Ok(result)
   }
 
 }
+
+// Expects executed results latest first, only the first `window` ones of each check are considered
+pub fn get_testchecks_flakiness(testresults: &[Testresult], policy: &ProjectFlakyPolicy) -> Vec<TestcheckFlakiness> {
+  let mut runs: Vec<(ObjectId, &str, Vec<bool>)> = vec![];
+  for testresult in testresults {
+    match runs.iter_mut().find(|(testcheck_id, _, _)| *testcheck_id == testresult.testcheck_id) {
+      Some((_, _, outcomes)) => {
+        if outcomes.len() < policy.window as usize {
+          outcomes.push(testresult.pass);
+        }
+      },
+      None => runs.push((testresult.testcheck_id, &testresult.name, vec![testresult.pass])),
+    }
+  }
+
+  let mut flakiness: Vec<TestcheckFlakiness> = runs.into_iter().map(|(testcheck_id, name, outcomes)| {
+    let flips = outcomes.windows(2).filter(|pair| pair[0] != pair[1]).count() as u32;
+    let flip_rate = if outcomes.len() > 1 { flips as f64 / (outcomes.len() - 1) as f64 } else { 0.0 };
+    TestcheckFlakiness {
+      testcheck_id,
+      name: name.to_string(),
+      runs: outcomes.len() as u32,
+      failures: outcomes.iter().filter(|pass| !**pass).count() as u32,
+      flips,
+      flip_rate,
+      flaky: outcomes.len() as u32 >= policy.min_runs && flips > 0 && flip_rate >= policy.threshold,
+    }
+  }).collect();
+  flakiness.sort_by(|a, b| b.flip_rate.total_cmp(&a.flip_rate).then(b.runs.cmp(&a.runs)));
+  flakiness
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn testresult(testcheck_id: ObjectId, name: &str, pass: bool) -> Testresult {
+    let now = DateTime::now();
+    Testresult {
+      id: ObjectId::new(),
+      account_id: ObjectId::new(),
+      testreport_id: ObjectId::new(),
+      testcheck_id,
+      section_id: None,
+      testcheck_revision: 1,
+      name: name.to_string(),
+      description: String::new(),
+      expected: String::new(),
+      tags: Vec::new(),
+      position: 0,
+      updated: true,
+      pass,
+      blocked: false,
+      flacky: false,
+      automated: true,
+      notes: String::new(),
+      url_issue: String::new(),
+      url_result: String::new(),
+      executors: Vec::new(),
+      assignee_id: None,
+      steps: Vec::new(),
+      retries: 0,
+      duration_ms: None,
+      attachments: Vec::new(),
+      created_at: now,
+      updated_at: now,
+    }
+  }
+
+  fn testresults(testcheck_id: ObjectId, name: &str, outcomes: &[bool]) -> Vec<Testresult> {
+    outcomes.iter().map(|pass| testresult(testcheck_id, name, *pass)).collect()
+  }
+
+  #[test]
+  fn computes_flips_and_flip_rate() {
+    let (login, logout) = (ObjectId::new(), ObjectId::new());
+    let mut results = testresults(login, "Log in", &[true, false, true, false, true]);
+    results.extend(testresults(logout, "Log out", &[false, false, false, true, true]));
+
+    let flakiness = get_testchecks_flakiness(&results, &ProjectFlakyPolicy::default());
+    assert_eq!(flakiness.len(), 2);
+    assert_eq!(flakiness[0].testcheck_id, login);
+    assert_eq!((flakiness[0].runs, flakiness[0].failures, flakiness[0].flips), (5, 2, 4));
+    assert_eq!(flakiness[0].flip_rate, 1.0);
+    assert!(flakiness[0].flaky);
+    assert_eq!((flakiness[1].runs, flakiness[1].failures, flakiness[1].flips), (5, 3, 1));
+    assert_eq!(flakiness[1].flip_rate, 0.25);
+    assert!(!flakiness[1].flaky);
+  }
+
+  #[test]
+  fn considers_only_the_window() {
+    let testcheck_id = ObjectId::new();
+    let results = testresults(testcheck_id, "Log in", &[true, true, true, true, false, true, false]);
+    let policy = ProjectFlakyPolicy { window: 4, threshold: 0.3, min_runs: 2, auto_flag: false };
+
+    let flakiness = get_testchecks_flakiness(&results, &policy);
+    assert_eq!((flakiness[0].runs, flakiness[0].flips), (4, 0));
+    assert!(!flakiness[0].flaky);
+  }
+
+  #[test]
+  fn needs_min_runs_to_be_flaky() {
+    let testcheck_id = ObjectId::new();
+    // A check passing then failing once is a regression, not a flaky check
+    let flakiness = get_testchecks_flakiness(&testresults(testcheck_id, "Log in", &[false, true]), &ProjectFlakyPolicy::default());
+    assert_eq!((flakiness[0].runs, flakiness[0].flips), (2, 1));
+    assert_eq!(flakiness[0].flip_rate, 1.0);
+    assert!(!flakiness[0].flaky);
+
+    let flakiness = get_testchecks_flakiness(&testresults(testcheck_id, "Log in", &[true]), &ProjectFlakyPolicy::default());
+    assert_eq!((flakiness[0].runs, flakiness[0].flips), (1, 0));
+    assert_eq!(flakiness[0].flip_rate, 0.0);
+  }
+}
@@ -0,0 +1,74 @@
+use bson::DateTime;
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::{service::db::{serialize_datetime, serialize_object_id, serialize_option_object_id}, testchecks::schema::{Testcheck, TestcheckDto}};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Testrevision {
+  #[serde(rename = "_id", serialize_with = "serialize_object_id")]
+  pub id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub account_id: ObjectId,
+  #[serde(serialize_with = "serialize_object_id")]
+  pub testcheck_id: ObjectId,
+  pub revision: u32,
+  // None for the baseline of checks created before revisions were recorded
+  #[serde(serialize_with = "serialize_option_object_id")]
+  pub author_id: Option<ObjectId>,
+  #[serde(flatten)]
+  pub content: TestcheckContent,
+  pub changes: Vec<TestrevisionChange>,
+  #[serde(serialize_with = "serialize_datetime")]
+  pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TestcheckContent {
+  pub name: String,
+  pub description: String,
+  pub expected: String,
+  pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestrevisionChange {
+  pub field: String,
+  pub from: String,
+  pub to: String,
+}
+
+impl From<&Testcheck> for TestcheckContent {
+  fn from(testcheck: &Testcheck) -> Self {
+    TestcheckContent {
+      name: testcheck.name.clone(),
+      description: testcheck.description.clone(),
+      expected: testcheck.expected.clone(),
+      tags: testcheck.tags.clone(),
+    }
+  }
+}
+
+impl From<&TestcheckDto> for TestcheckContent {
+  fn from(data: &TestcheckDto) -> Self {
+    TestcheckContent {
+      name: data.name.clone(),
+      description: data.description.clone(),
+      expected: data.expected.clone(),
+      tags: data.tags.clone(),
+    }
+  }
+}
+
+pub fn diff_testcheck_contents(from: &TestcheckContent, to: &TestcheckContent) -> Vec<TestrevisionChange> {
+  let fields = [
+    ("name", from.name.clone(), to.name.clone()),
+    ("description", from.description.clone(), to.description.clone()),
+    ("expected", from.expected.clone(), to.expected.clone()),
+    ("tags", from.tags.join(", "), to.tags.join(", ")),
+  ];
+  fields.into_iter()
+    .filter(|(_, from, to)| from != to)
+    .map(|(field, from, to)| TestrevisionChange { field: field.to_string(), from, to })
+    .collect()
+}
@@ -0,0 +1,52 @@
+use std::error::Error;
+
+use bson::DateTime;
+use mongodb::{
+  bson::{self, doc, oid::ObjectId}, options::FindOptions, results::InsertOneResult, Client
+};
+use crate::{service::db::{ self, MongoRepo}, testchecks::schema::Testcheck};
+use super::schema::{Testrevision, TestrevisionChange};
+use rocket::futures::TryStreamExt;
+
+pub fn get_testrevisions_repo(client: Client) -> MongoRepo<Testrevision> {
+  return db::get_mongo_repo(client, "test_boss", "testrevisions");
+}
+
+impl MongoRepo<Testrevision> {
+  pub async fn get_testcheck_testrevisions(&self, testcheck_id: &str) -> Result<Vec<Testrevision>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testcheck_id": ObjectId::parse_str(testcheck_id)? };
+    let options = FindOptions::builder().sort(doc! { "revision": 1 }).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testrevisions: Vec<Testrevision> = cursor.try_collect().await?;
+    Ok(testrevisions)
+  }
+
+  pub async fn get_testchecks_testrevisions(&self, testcheck_ids: &[ObjectId]) -> Result<Vec<Testrevision>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testcheck_id": { "$in": testcheck_ids } };
+    let cursor = self.col.find(filter, None).await?;
+    let testrevisions: Vec<Testrevision> = cursor.try_collect().await?;
+    Ok(testrevisions)
+  }
+
+  pub async fn get_testcheck_testrevision(&self, testcheck_id: &str, revision: u32) -> Result<Option<Testrevision>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testcheck_id": ObjectId::parse_str(testcheck_id)?, "revision": revision };
+    let result = self.col.find_one(filter, None).await?;
+    Ok(result)
+  }
+
+  pub async fn create_testrevision(&self, testcheck: &Testcheck, author_id: Option<ObjectId>, changes: Vec<TestrevisionChange>) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let new_doc = Testrevision {
+      id: ObjectId::new(),
+      account_id: testcheck.account_id,
+      testcheck_id: testcheck.id,
+      revision: testcheck.revision,
+      author_id,
+      content: testcheck.into(),
+      changes,
+      created_at: now,
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+}
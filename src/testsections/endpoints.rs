@@ -0,0 +1,203 @@
+use bson::oid::ObjectId;
+use log::{error, warn};
+use rocket::{delete, get, put, routes, serde::json::Json, State};
+use crate::{service::{db::MongoRepo, http_errors::JsonError}, sessions::{jwt::{get_jwt_session_and_user, JWT}, schema::{JWTSessionAndUser, Session}}, testchecks::{schema::Testcheck, service::parse_testchecks_ids}, users::{roles::is_admin, schema::User}};
+
+use super::schema::{Testsection, TestsectionDto};
+
+#[get("/<id>")]
+async fn get_testsection(jwt: Result<JWT, JsonError>, id: &str, testsection_repo: &State<MongoRepo<Testsection>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testsection>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  match allowed_for_testsection(jwts, testsection_repo, id).await {
+    Ok(testsection) => Ok(Json(testsection)),
+    Err(e) => Err(e)
+  }
+}
+
+#[put("/<id>", format = "json", data = "<data>")]
+async fn update_testsection(jwt: Result<JWT, JsonError>, id: &str, data: Json<TestsectionDto>, testsection_repo: &State<MongoRepo<Testsection>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testsection>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testsection = allowed_for_testsection(jwts, testsection_repo, id).await?;
+  let data = data.into_inner();
+  let parent_id = get_parent_testsection_id(testsection_repo, testsection.testlist_id, data.parent_id.as_deref(), Some(testsection.id)).await?;
+
+  match testsection_repo.update_testsection(id.to_string(), parent_id, data).await {
+    Ok(updated) => {
+      if updated.matched_count == 0 {
+        warn!("Testsection not found: {}", id);
+        return Err(JsonError::NotFound("Testsection not found".to_string()));
+      }
+      match testsection_repo.get_testsection_by_id(id).await {
+        Ok(testsection) => match testsection {
+          Some(testsection) => Ok(Json(testsection)),
+          None => {
+            warn!("Testsection not found: {}", id);
+            Err(JsonError::NotFound("Testsection not found".to_string()))
+          },
+        },
+        Err(e) => {
+          error!("Error getting testsection: {}", e);
+          Err(JsonError::Internal("Error getting testsection".to_string()))
+        },
+      }
+    },
+    Err(e) => {
+      error!("Error updating testsection: {}", e);
+      Err(JsonError::Internal("Error updating testsection".to_string()))
+    },
+  }
+}
+
+#[delete("/<id>")]
+async fn delete_testsection(jwt: Result<JWT, JsonError>, id: &str, testsection_repo: &State<MongoRepo<Testsection>>, testcheck_repo: &State<MongoRepo<Testcheck>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>) -> Result<Json<Testsection>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testsection = allowed_for_testsection(jwts, testsection_repo, id).await?;
+
+  // Subsections and checks are moved up to the parent of the deleted section
+  if testsection_repo.reparent_testsections(testsection.id, testsection.parent_id).await.is_err() {
+    error!("Error moving testsection subsections: {}", id);
+    return Err(JsonError::Internal("Error moving testsection subsections".to_string()));
+  }
+  if testcheck_repo.reparent_testchecks(testsection.id, testsection.parent_id).await.is_err() {
+    error!("Error moving testsection testchecks: {}", id);
+    return Err(JsonError::Internal("Error moving testsection testchecks".to_string()));
+  }
+  if testcheck_repo.renumber_testlist_testchecks(&testsection.testlist_id.to_hex()).await.is_err() {
+    error!("Error updating testchecks positions: {}", id);
+    return Err(JsonError::Internal("Error updating testchecks positions".to_string()));
+  }
+
+  match testsection_repo.delete_testsection(id.to_string()).await {
+    Ok(deleted) => {
+      if deleted.deleted_count == 0 {
+        warn!("Testsection not found: {}", id);
+        return Err(JsonError::NotFound("Testsection not found".to_string()));
+      }
+      Ok(Json(testsection))
+    },
+    Err(e) => {
+      error!("Error deleting testsection: {}", e);
+      Err(JsonError::Internal("Error deleting testsection".to_string()))
+    },
+  }
+}
+
+#[get("/<testsection_id>/testchecks")]
+async fn get_testsection_testchecks(jwt: Result<JWT, JsonError>, testsection_id: &str, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testsection_repo: &State<MongoRepo<Testsection>>, testcheck_repo: &State<MongoRepo<Testcheck>>) -> Result<Json<Vec<Testcheck>>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  allowed_for_testsection(jwts, testsection_repo, testsection_id).await?;
+
+  match testcheck_repo.get_testsection_testchecks(testsection_id).await {
+    Ok(testchecks) => Ok(Json(testchecks)),
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      Err(JsonError::Internal("Error getting testchecks".to_string()))
+    },
+  }
+}
+
+#[put("/<testsection_id>/testchecks", format = "json", data = "<data>")]
+async fn update_testsection_testchecks_positions(jwt: Result<JWT, JsonError>, testsection_id: &str, data: Json<Vec<String>>, sessions_repo: &State<MongoRepo<Session>>, users_repo: &State<MongoRepo<User>>, testsection_repo: &State<MongoRepo<Testsection>>, testcheck_repo: &State<MongoRepo<Testcheck>>) -> Result<Json<i32>, JsonError> {
+  let jwts = get_jwt_session_and_user(sessions_repo, users_repo, jwt).await?;
+  let testsection = allowed_for_testsection(jwts, testsection_repo, testsection_id).await?;
+  let ids = parse_testchecks_ids(&data.into_inner()).map_err(JsonError::BadRequest)?;
+
+  match testcheck_repo.get_testsection_testchecks(testsection_id).await {
+    Ok(testchecks) => {
+      if ids.iter().any(|id| !testchecks.iter().any(|testcheck| testcheck.id == *id)) {
+        return Err(JsonError::BadRequest("Testchecks must belong to the testsection".to_string()));
+      }
+    },
+    Err(e) => {
+      error!("Error getting testchecks: {}", e);
+      return Err(JsonError::Internal("Error getting testchecks".to_string()));
+    },
+  }
+
+  let res = testcheck_repo.update_testsection_testchecks_positions(testsection.id, &ids).await;
+  match res {
+    Ok(updated) => Ok(Json(updated)),
+    Err(e) => {
+      error!("Error updating testchecks positions: {}", e);
+      Err(JsonError::Internal("Error updating testchecks positions".to_string()))
+    },
+  }
+}
+
+pub fn get_testsections_routes() -> Vec<rocket::Route> {
+  routes![get_testsection, update_testsection, delete_testsection, get_testsection_testchecks, update_testsection_testchecks_positions]
+}
+
+// Resolves the parent of a section, rejecting parents from other testlists and cycles
+pub async fn get_parent_testsection_id(testsection_repo: &State<MongoRepo<Testsection>>, testlist_id: ObjectId, parent_id: Option<&str>, testsection_id: Option<ObjectId>) -> Result<Option<ObjectId>, JsonError> {
+  let parent_id = match parent_id {
+    Some(parent_id) => parent_id,
+    None => return Ok(None),
+  };
+  let testsections = match testsection_repo.get_testlist_testsections(&testlist_id.to_hex()).await {
+    Ok(testsections) => testsections,
+    Err(e) => {
+      error!("Error getting testsections: {}", e);
+      return Err(JsonError::Internal("Error getting testsections".to_string()));
+    },
+  };
+
+  let parent = match testsections.iter().find(|testsection| testsection.id.to_hex() == parent_id) {
+    Some(parent) => parent,
+    None => return Err(JsonError::BadRequest("Parent testsection not found in testlist".to_string())),
+  };
+
+  let mut ancestor = Some(parent);
+  while let Some(section) = ancestor {
+    if Some(section.id) == testsection_id {
+      return Err(JsonError::BadRequest("A testsection cannot be nested inside itself".to_string()));
+    }
+    ancestor = section.parent_id.and_then(|id| testsections.iter().find(|testsection| testsection.id == id));
+  }
+
+  Ok(Some(parent.id))
+}
+
+pub async fn check_testlist_testsection(testsection_repo: &State<MongoRepo<Testsection>>, testlist_id: ObjectId, testsection_id: Option<&str>) -> Result<(), JsonError> {
+  let testsection_id = match testsection_id {
+    Some(testsection_id) => testsection_id,
+    None => return Ok(()),
+  };
+  match testsection_repo.get_testsection_by_id(testsection_id).await {
+    Ok(Some(testsection)) if testsection.testlist_id == testlist_id => Ok(()),
+    Ok(_) => Err(JsonError::BadRequest("Testsection not found in testlist".to_string())),
+    Err(e) => {
+      error!("Error getting testsection: {}", e);
+      Err(JsonError::Internal("Error getting testsection".to_string()))
+    },
+  }
+}
+
+async fn allowed_for_testsection(jwts: JWTSessionAndUser, testsection_repo: &State<MongoRepo<Testsection>>, testsection_id: &str) -> Result<Testsection, JsonError>  {
+  if jwts.user.accounts.is_none() && !is_admin(&jwts.user) {
+    return Err(JsonError::Forbidden(
+      "You are not allowed to retrieve this testsection".to_string(),
+    ));
+  }
+  match testsection_repo.get_testsection_by_id(testsection_id).await {
+    Ok(testsection) => match testsection {
+      Some(testsection) => {
+        let account_id = testsection.account_id;
+        if let Some(accounts) = &jwts.user.accounts {
+          if !accounts.iter().any(|account| account.account_id == account_id) && !is_admin(&jwts.user) {
+            return Err(JsonError::Forbidden("You are not allowed to retrieve this testsection".to_string()));
+          }
+        }
+        Ok(testsection)
+      },
+      None => {
+        warn!("Testsection not found: {}", testsection_id);
+        Err(JsonError::NotFound("Testsection not found".to_string()))
+      },
+    },
+    Err(e) => {
+      error!("Error getting testsection: {}", e);
+      Err(JsonError::Internal("Error getting testsection".to_string()))
+    },
+  }
+}
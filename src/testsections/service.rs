@@ -0,0 +1,119 @@
+use std::error::Error;
+
+use bson::DateTime;
+use mongodb::{
+  bson::{self, doc, oid::ObjectId}, options::{FindOneOptions, FindOptions}, results::{DeleteResult, InsertOneResult, UpdateResult}, Client
+};
+use crate::{service::db::{ self, MongoRepo}, testlists::schema::Testlist};
+use super::schema::{Testsection, TestsectionDto};
+use rocket::futures::TryStreamExt;
+
+pub fn get_testsections_repo(client: Client) -> MongoRepo<Testsection> {
+  return db::get_mongo_repo(client, "test_boss", "testsections");
+}
+
+impl MongoRepo<Testsection> {
+  pub async fn get_testlist_testsections(&self, testlist_id: &str) -> Result<Vec<Testsection>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testlist_id": ObjectId::parse_str(testlist_id)? };
+    let options = FindOptions::builder().sort(doc! { "position": 1 }).build();
+    let cursor = self.col.find(filter, options).await?;
+    let testsections: Vec<Testsection> = cursor.try_collect().await?;
+    Ok(testsections)
+  }
+
+  pub async fn get_project_testsections(&self, project_id: &str) -> Result<Vec<Testsection>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "project_id": ObjectId::parse_str(project_id)? };
+    let cursor = self.col.find(filter, None).await?;
+    let testsections: Vec<Testsection> = cursor.try_collect().await?;
+    Ok(testsections)
+  }
+
+  pub async fn get_testsection_by_id(&self, id: &str) -> Result<Option<Testsection>, Box<dyn Error + Send + Sync>> {
+    let oid = ObjectId::parse_str(id)?;
+    let filter = doc! { "_id": oid };
+    let result = self.col.find_one(filter, None).await?;
+    Ok(result)
+  }
+
+  async fn get_next_position(&self, testlist_id: ObjectId, parent_id: Option<ObjectId>) -> Result<u16, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testlist_id": testlist_id, "parent_id": parent_id };
+    let options = FindOneOptions::builder()
+      .sort(doc! { "position": -1 })
+      .build();
+    let result = self.col.find_one(filter, options).await?;
+    if let Some(testsection) = result {
+      return Ok(testsection.position + 1);
+    }
+    // Positions start at 1, as when reordering
+    Ok(1)
+  }
+
+  pub async fn create_testsection(&self, testlist: &Testlist, parent_id: Option<ObjectId>, data: TestsectionDto) -> Result<InsertOneResult, Box<dyn Error + Send + Sync>> {
+    let position = self.get_next_position(testlist.id, parent_id).await?;
+    let now = DateTime::from_chrono(chrono::Utc::now());
+    let new_doc = Testsection {
+      id: ObjectId::new(),
+      account_id: testlist.account_id,
+      project_id: testlist.project_id,
+      testlist_id: testlist.id,
+      parent_id,
+      name: data.name,
+      description: data.description,
+      position,
+      created_at: now,
+      updated_at: now,
+    };
+    let result = self.col.insert_one(new_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testsection(&self, id: String, parent_id: Option<ObjectId>, data: TestsectionDto) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let now = chrono::Utc::now();
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let upd_doc = doc! { "$set": {
+      "name": data.name,
+      "description": data.description,
+      "parent_id": parent_id,
+      "updated_at": DateTime::from_chrono(now)
+    } };
+    let result = self.col.update_one(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn reparent_testsections(&self, parent_id: ObjectId, new_parent_id: Option<ObjectId>) -> Result<UpdateResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "parent_id": parent_id };
+    let upd_doc = doc! { "$set": {
+      "parent_id": new_parent_id
+    } };
+    let result = self.col.update_many(filter, upd_doc, None).await?;
+    Ok(result)
+  }
+
+  pub async fn update_testsections_positions(&self, testlist_id: &str, testsections_ids: Vec<String>) -> Result<i32, Box<dyn Error + Send + Sync>> {
+    let mut updates = 0;
+    for (index, id) in testsections_ids.iter().enumerate() {
+      let position = index as i32 + 1;
+      let filter = doc! { "_id": ObjectId::parse_str(id)?, "testlist_id": ObjectId::parse_str(testlist_id)? };
+      let upd_doc = doc! { "$set": {
+        "position": position
+      } };
+      let result = self.col.update_one(filter, upd_doc, None).await?;
+      if result.modified_count > 0 {
+        updates += 1;
+      }
+    }
+    Ok(updates)
+  }
+
+  pub async fn delete_testsection(&self, id: String) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": ObjectId::parse_str(&id)? };
+    let result = self.col.delete_one(filter, None).await?;
+    Ok(result)
+  }
+
+  pub async fn delete_testlist_testsections(&self, testlist_id: &str) -> Result<DeleteResult, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "testlist_id": ObjectId::parse_str(testlist_id)? };
+    let result = self.col.delete_many(filter, None).await?;
+    Ok(result)
+  }
+}
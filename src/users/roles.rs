@@ -8,11 +8,11 @@ pub enum Role {
   AccountManager,
 }
 
-impl Role {
-  fn to_string(&self) -> String {
+impl std::fmt::Display for Role {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
       match self {
-        Role::Admin => "admin".to_string(),
-        Role::AccountManager => "account_manager".to_string(),
+        Role::Admin => write!(f, "admin"),
+        Role::AccountManager => write!(f, "account_manager"),
       }
   }
 }
@@ -51,6 +51,7 @@ pub struct UserDetailsDto {
   pub lastname: String,
 }
 
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserPasswordDto {
   pub password: String,
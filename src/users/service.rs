@@ -60,6 +60,13 @@ impl MongoRepo<User> {
     Ok(result)
   }
 
+  pub async fn get_users_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<User>, Box<dyn Error + Send + Sync>> {
+    let filter = doc! { "_id": { "$in": ids } };
+    let cursor = self.col.find(filter, None).await?;
+    let users: Vec<User> = cursor.try_collect().await?;
+    Ok(users)
+  }
+
   pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, Box<dyn Error + Send + Sync>> {
     let filter = doc! { "email": email };
     let result = self.col.find_one(filter, None).await?;
@@ -72,7 +79,7 @@ impl MongoRepo<User> {
     let new_doc = User {
       id: ObjectId::new(),
       email: data.email,
-      pwdhash: pwdhash,
+      pwdhash,
       firstname: data.firstname,
       lastname: data.lastname,
       roles: Some(data.roles),